@@ -5,13 +5,16 @@ use framework::exception::Exception;
 use framework::load_config;
 use framework::log;
 use framework::log::metrics::MetricsCollector;
+use framework::retry::RetryPolicy;
 use framework::system::System;
 use framework::warn;
 use framework_kafka::Topic;
+use framework_kafka::consumer::Ack;
 use framework_kafka::consumer::ConsumerConfig;
 use framework_kafka::consumer::Message;
 use framework_kafka::consumer::MessageConsumer;
 use framework_kafka::producer::Producer;
+use framework_kafka::producer::ProducerConfig;
 use serde::Deserialize;
 use serde::Serialize;
 use tokio::sync::mpsc;
@@ -25,6 +28,8 @@ struct TestMessage {
 struct State {
     topics: Topics,
     producer: Producer,
+    // forwards messages of test_bulk with exactly once semantics
+    transactional_producer: Producer,
     tx: mpsc::Sender<TestMessage>,
 }
 
@@ -41,7 +46,12 @@ pub async fn main() -> Result<(), Exception> {
     let (tx, rx) = mpsc::channel::<TestMessage>(1000);
     let state = Arc::new(State {
         topics: Topics { test_single: Topic::new("test_single"), test_bulk: Topic::new("test") },
-        producer: Producer::new("dev.internal:9092".to_owned(), env!("CARGO_BIN_NAME")),
+        producer: Producer::new("dev.internal:9092".to_owned(), env!("CARGO_BIN_NAME"), &ProducerConfig::default()),
+        transactional_producer: Producer::new(
+            "dev.internal:9092".to_owned(),
+            env!("CARGO_BIN_NAME"),
+            &ProducerConfig { transactional_id: Some("demo-forward".to_owned()), ..ProducerConfig::default() },
+        ),
         tx,
     });
 
@@ -54,7 +64,7 @@ pub async fn main() -> Result<(), Exception> {
         MessageConsumer::new("dev.internal:9092".to_owned(), env!("CARGO_BIN_NAME"), &ConsumerConfig::default());
 
     consumer.add_handler(&state.topics.test_single, handler_single);
-    consumer.add_bulk_handler(&state.topics.test_bulk, handler_bulk);
+    consumer.add_bulk_handler_with_ack(&state.topics.test_bulk, handler_bulk, RetryPolicy::default());
     collector.add(consumer.consumer_metrics());
 
     system.spawn(consumer.start(state, system.shutdown_signal()));
@@ -89,11 +99,26 @@ async fn process_message(mut rx: Receiver<TestMessage>) {
     println!("finished");
 }
 
-async fn handler_bulk(state: Arc<State>, messages: Vec<Message<TestMessage>>) -> Result<(), Exception> {
+async fn handler_bulk(state: Arc<State>, messages: Vec<Message<TestMessage>>, ack: Ack) -> Result<(), Exception> {
+    let producer = &state.transactional_producer;
+    producer.begin_transaction()?;
+    match forward_messages(&state, messages).await {
+        Ok(()) => producer.commit_transaction_with_ack(ack).await,
+        Err(e) => {
+            producer.abort_transaction().await?;
+            Err(e)
+        }
+    }
+}
+
+async fn forward_messages(state: &State, messages: Vec<Message<TestMessage>>) -> Result<(), Exception> {
     for message in messages {
         if let Some(ref key) = message.key {
             if key == "1" {
-                state.producer.send(&state.topics.test_single, Some("xxx".to_owned()), &message.payload).await?;
+                state
+                    .transactional_producer
+                    .send(&state.topics.test_single, Some("xxx".to_owned()), &message.payload)
+                    .await?;
                 warn!(error_code = "TRIGGER", "test");
             } else {
                 println!("Received message: {}", message.payload.name);
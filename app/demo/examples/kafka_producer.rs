@@ -5,7 +5,11 @@ use framework::log;
 use framework::spawn_action;
 use framework::task;
 use framework_kafka::Topic;
+use framework_kafka::admin::Admin;
+use framework_kafka::admin::TopicConfig;
 use framework_kafka::producer::Producer;
+use framework_kafka::producer::ProducerConfig;
+use framework_kafka::security::SecurityConfig;
 use serde::Deserialize;
 use serde::Serialize;
 
@@ -16,10 +20,20 @@ struct TestMessage {
 
 #[tokio::main]
 pub async fn main() -> Result<(), Exception> {
-    let producer = Producer::new("dev.internal:9092".to_owned(), env!("CARGO_BIN_NAME"));
+    let producer = Producer::new("dev.internal:9092".to_owned(), env!("CARGO_BIN_NAME"), &ProducerConfig::default());
 
     log::init("console", env!("CARGO_BIN_NAME"));
 
+    let admin = Admin::new("dev.internal:9092", &SecurityConfig::default());
+    admin
+        .create_topic(&TopicConfig {
+            name: "test",
+            partitions: 3,
+            replication_factor: 1,
+            retention: Some(Duration::from_hours(24)),
+        })
+        .await?;
+
     spawn_action!("produce", async move {
         let topic = Topic::new("test");
 
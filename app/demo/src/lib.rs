@@ -66,7 +66,11 @@ pub async fn run() -> Result<(), Exception> {
     system.spawn(start_http_server(
         app,
         system.shutdown_signal(),
-        HttpServerConfig { shutdown_grace_period: Duration::ZERO, ..Default::default() },
+        HttpServerConfig {
+            shutdown_grace_period: Duration::ZERO,
+            log_excluded_paths: vec!["/static/", "/favicon.ico"],
+            ..Default::default()
+        },
     ));
 
     let mut collector = MetricsCollector::new();
@@ -59,10 +59,12 @@ pub struct UpdateUserRequest {
 #[api]
 pub trait UserService {
     #[post]
+    #[validate]
     #[path("/user/create")]
     async fn create(&self, request: CreateUserRequest) -> Result<Uuid, Exception>;
 
     #[get]
+    #[validate]
     #[path("/user/get_by_name")]
     async fn get_by_name(&self, request: GetUserByNameRequest) -> Result<Option<GetUserResponse>, Exception>;
 
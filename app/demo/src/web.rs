@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::time::Duration;
 
 use axum::Router;
@@ -5,17 +6,23 @@ use axum::http::StatusCode;
 use axum::routing::get;
 use framework::asset_path;
 use framework::exception::Exception;
-use framework::web::server::ServeDir;
 use framework::web::server::ServeFile;
+use framework::web::static_files::StaticFiles;
+use framework::web::static_files::StaticFilesConfig;
 use tokio::time::sleep;
 
 pub(crate) fn routes() -> Result<Router, Exception> {
     let router = Router::new();
     let router = router.route("/503", get(http_503));
     let router = router.route("/long", get(long));
+    let static_files = StaticFiles::new(StaticFilesConfig {
+        dir: asset_path!("assets/web/"),
+        cache_control: HashMap::from([("css", "public, max-age=3600"), ("js", "public, max-age=3600")]),
+        fingerprint: true,
+    })?;
     let router = router
         .route_service("/", ServeFile::new(asset_path!("assets/web/index.html")))
-        .route_service("/static/{*path}", ServeDir::new(asset_path!("assets/web/")));
+        .route_service("/static/{*path}", static_files.into_service());
     //     .fallback_service(ServeFile::new(asset_path!("assets/web/index.html")?))
     Ok(router)
 }
@@ -12,7 +12,9 @@ use framework::web::server::HttpServerConfig;
 use framework::web::server::http_server_metrics;
 use framework::web::server::start_http_server;
 use framework_kafka::Topic;
+use framework_kafka::appender::KafkaAppender;
 use framework_kafka::producer::Producer;
+use framework_kafka::producer::ProducerConfig;
 use kafka::EventMessage;
 use serde::Deserialize;
 
@@ -37,14 +39,31 @@ struct Topics {
 #[tokio::main]
 async fn main() -> Result<(), Exception> {
     let config: AppConfig = load_config!("assets/conf.json");
-    log::init(&config.log_appender, env!("CARGO_PKG_NAME"));
-
     let mut system = System::new();
     let mut collector = MetricsCollector::new();
 
+    if config.log_appender == "kafka" {
+        let appender = KafkaAppender::new(Producer::new(
+            config.kafka_uri.clone(),
+            env!("CARGO_BIN_NAME"),
+            &ProducerConfig::default(),
+        ));
+        collector.add(appender.appender_metrics());
+        log::init_with_appender(appender, env!("CARGO_PKG_NAME"));
+    } else {
+        log::init(&config.log_appender, env!("CARGO_PKG_NAME"));
+    }
+
+    let producer = Producer::new(
+        config.kafka_uri,
+        env!("CARGO_BIN_NAME"),
+        &ProducerConfig { linger: Duration::from_millis(50), ..ProducerConfig::default() },
+    );
+    collector.add(producer.producer_metrics());
     let state = Arc::new(AppState {
         topics: Topics { event: Topic::new("event") },
-        producer: Producer::new(config.kafka_uri, env!("CARGO_BIN_NAME")),
+        // collected events favor throughput over latency
+        producer,
     });
 
     let app = Router::new();
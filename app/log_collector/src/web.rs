@@ -5,13 +5,12 @@ use std::sync::Arc;
 use axum::Extension;
 use axum::Router;
 use axum::debug_handler;
-use axum::extract::Path;
 use axum::extract::State;
 use axum::http::HeaderMap;
 use axum::http::HeaderValue;
+use axum::http::Method;
 use axum::http::header;
 use axum::routing::get;
-use axum::routing::options;
 use axum::routing::post;
 use chrono::DateTime;
 use chrono::Utc;
@@ -27,7 +26,11 @@ use framework::validation_error;
 use framework::warn;
 use framework::web::body::TextBody;
 use framework::web::client_info::ClientInfo;
+use framework::web::cors::CorsConfig;
+use framework::web::cors::cors;
 use framework::web::error::HttpResult;
+use framework::web::extract::Path;
+use framework_kafka::producer::SendOptions;
 use framework_macro::Validate;
 use serde::Deserialize;
 use serde::Serialize;
@@ -36,11 +39,18 @@ use crate::AppState;
 use crate::kafka::EventMessage;
 
 pub(super) fn routes(state: Arc<AppState>) -> Router {
-    Router::new()
-        .route("/robots.txt", get(robots_txt))
-        .route("/event/{app}", options(event_options))
-        .route("/event/{app}", post(event_post))
-        .with_state(state)
+    let event = cors(
+        Router::new().route("/event/{app}", post(event_post)),
+        CorsConfig {
+            allowed_origins: vec!["*".to_owned()],
+            allowed_methods: vec![Method::POST, Method::PUT, Method::OPTIONS],
+            allowed_headers: vec![header::ACCEPT, header::CONTENT_TYPE],
+            // beacon endpoint doesn't use cookies
+            allow_credentials: false,
+            max_age: None,
+        },
+    );
+    Router::new().route("/robots.txt", get(robots_txt)).merge(event).with_state(state)
 }
 
 #[debug_handler]
@@ -57,31 +67,14 @@ Disallow: /",
     )
 }
 
-#[debug_handler]
-async fn event_options(headers: HeaderMap) -> HttpResult<HeaderMap> {
-    let mut response_headers = HeaderMap::new();
-
-    let origin = headers
-        .get(header::ORIGIN)
-        .ok_or_else(|| exception!("access denied", severity = Severity::Warn, code = error_code::FORBIDDEN))?;
-    response_headers.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, origin.clone());
-
-    response_headers.insert(header::ACCESS_CONTROL_ALLOW_METHODS, HeaderValue::from_static("POST, PUT, OPTIONS"));
-    response_headers.insert(header::ACCESS_CONTROL_ALLOW_HEADERS, HeaderValue::from_static("Accept, Content-Type"));
-    response_headers.insert(header::ACCESS_CONTROL_ALLOW_CREDENTIALS, HeaderValue::from_static("true"));
-
-    Ok(response_headers)
-}
-
 // event will be sent via ajax or navigator.sendBeacon(), refer to https://developer.mozilla.org/en-US/docs/Web/API/Navigator/sendBeacon
 #[debug_handler]
 async fn event_post(
     State(state): State<Arc<AppState>>,
     Path(app): Path<String>,
-    headers: HeaderMap,
     Extension(client_info): Extension<Arc<ClientInfo>>,
     body: TextBody,
-) -> HttpResult<HeaderMap> {
+) -> HttpResult<()> {
     if !body.is_empty() {
         let request: SendEventRequest = json::from_json(&body).map_err(|err| {
             exception!(
@@ -94,14 +87,7 @@ async fn event_post(
         request.validate()?;
         process_events(state, &app, request, client_info).await?;
     }
-
-    let origin = headers.get(header::ORIGIN);
-    let mut response_headers = HeaderMap::new();
-    if let Some(origin) = origin {
-        response_headers.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, origin.clone());
-        response_headers.insert(header::ACCESS_CONTROL_ALLOW_CREDENTIALS, HeaderValue::from_static("true"));
-    }
-    Ok(response_headers)
+    Ok(())
 }
 
 async fn process_events(
@@ -138,9 +124,9 @@ async fn process_events(
 
         message.context.insert("client_ip".to_owned(), client_info.client_ip.clone());
 
-        state.producer.send(&state.topics.event, None, &message).await?;
+        // delivery failure is reported by producer metrics, client doesn't retry anyway
+        state.producer.send_async(&state.topics.event, &message, SendOptions::default()).await?;
     }
-
     Ok(())
 }
 
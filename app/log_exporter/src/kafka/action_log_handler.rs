@@ -9,6 +9,7 @@ use chrono::DateTime;
 use chrono::Utc;
 use framework::exception::Exception;
 use framework::json;
+use framework_kafka::consumer::Ack;
 use framework_kafka::consumer::Message;
 use serde::Deserialize;
 use serde::Serialize;
@@ -50,6 +51,7 @@ struct PerformanceStatMessage {
 pub(crate) async fn action_log_message_handler(
     state: Arc<AppState>,
     messages: Vec<Message<ActionLogMessage>>,
+    ack: Ack,
 ) -> Result<(), Exception> {
     let now = Utc::now().date_naive();
     let path = local_file_path("action", now, &state)?;
@@ -62,6 +64,7 @@ pub(crate) async fn action_log_message_handler(
         writer.write_all(b"\n")?;
     }
     writer.flush()?;
+    ack.commit();
 
     Ok(())
 }
@@ -9,6 +9,7 @@ use chrono::DateTime;
 use chrono::Utc;
 use framework::exception::Exception;
 use framework::json;
+use framework_kafka::consumer::Ack;
 use framework_kafka::consumer::Message;
 use serde::Deserialize;
 use serde::Serialize;
@@ -35,6 +36,7 @@ pub(crate) struct EventMessage {
 pub(crate) async fn event_message_handler(
     state: Arc<AppState>,
     messages: Vec<Message<EventMessage>>,
+    ack: Ack,
 ) -> Result<(), Exception> {
     let now = Utc::now().date_naive();
     let path = local_file_path("event", now, &state)?;
@@ -47,6 +49,7 @@ pub(crate) async fn event_message_handler(
         writer.write_all(b"\n")?;
     }
     writer.flush()?;
+    ack.commit();
 
     Ok(())
 }
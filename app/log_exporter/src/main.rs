@@ -11,6 +11,8 @@ use framework::log;
 use framework::log::metrics::MetricsCollector;
 use framework::log::metrics::container_mem_max;
 use framework::network::hostname;
+use framework::retry::RetryPolicy;
+use framework::schedule::OverlapPolicy;
 use framework::schedule::Scheduler;
 use framework::system::System;
 use framework::task;
@@ -19,6 +21,7 @@ use framework::web::server::http_server_metrics;
 use framework::web::server::start_http_server;
 use framework_kafka::Topic;
 use framework_kafka::consumer::ConsumerConfig;
+use framework_kafka::consumer::ConsumerControl;
 use framework_kafka::consumer::MessageConsumer;
 use job::process_log_job;
 use kafka::action_log_handler::ActionLogMessage;
@@ -50,6 +53,8 @@ pub struct AppState {
     bucket: String,
 
     duckdb_memory_limit: u64, // in bytes
+
+    consumer_control: ConsumerControl,
 }
 
 fn hash(hostname: &str) -> String {
@@ -80,6 +85,8 @@ async fn main() -> Result<(), Exception> {
     let mut system = System::new();
     let mut collector = MetricsCollector::new();
 
+    let mut consumer = MessageConsumer::new(config.kafka_uri, env!("CARGO_BIN_NAME"), &ConsumerConfig::default());
+
     let state = Arc::new({
         let hash = hash(hostname());
 
@@ -89,6 +96,7 @@ async fn main() -> Result<(), Exception> {
             hash,
             bucket: config.bucket,
             duckdb_memory_limit: duckdb_memory_limit(),
+            consumer_control: consumer.control(),
         }
     });
 
@@ -99,12 +107,20 @@ async fn main() -> Result<(), Exception> {
         process_log_job,
         NaiveTime::from_hms_opt(1, 0, 0).expect("value must be valid"),
     );
+    // slow run with large backlog must not process same files concurrently with the next one
+    scheduler.overlap_policy("process_log_job", OverlapPolicy::Skip);
+    // hung duckdb or gcloud command must not block following runs
+    scheduler.timeout("process_log_job", Duration::from_hours(6));
     system.spawn(scheduler.start(scheduler_state, system.shutdown_signal()));
 
     let consumer_state = Arc::clone(&state);
-    let mut consumer = MessageConsumer::new(config.kafka_uri, env!("CARGO_BIN_NAME"), &ConsumerConfig::default());
-    consumer.add_bulk_handler(&consumer_state.topics.action, action_log_message_handler);
-    consumer.add_bulk_handler(&consumer_state.topics.event, event_message_handler);
+    // commit after messages are flushed to local file
+    consumer.add_bulk_handler_with_ack(
+        &consumer_state.topics.action,
+        action_log_message_handler,
+        RetryPolicy::default(),
+    );
+    consumer.add_bulk_handler_with_ack(&consumer_state.topics.event, event_message_handler, RetryPolicy::default());
     collector.add(consumer.consumer_metrics());
     system.spawn(consumer.start(consumer_state, system.shutdown_signal()));
 
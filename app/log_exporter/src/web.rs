@@ -21,6 +21,15 @@ trait OperationWebService {
     #[put]
     #[path("/upload")]
     async fn upload(&self, request: UploadRequest) -> Result<(), Exception>;
+
+    // e.g. pause when disk is full, resume after cleanup
+    #[put]
+    #[path("/consumer/pause")]
+    async fn pause_consumer(&self, request: ConsumerControlRequest) -> Result<(), Exception>;
+
+    #[put]
+    #[path("/consumer/resume")]
+    async fn resume_consumer(&self, request: ConsumerControlRequest) -> Result<(), Exception>;
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -28,6 +37,11 @@ struct UploadRequest {
     date: NaiveDate,
 }
 
+#[derive(Serialize, Deserialize, Debug)]
+struct ConsumerControlRequest {
+    topic: String,
+}
+
 struct OperationWebServiceImpl {
     state: Arc<AppState>,
 }
@@ -38,4 +52,14 @@ impl OperationWebService for OperationWebServiceImpl {
         spawn_action!("upload", async move { upload_archive(request.date, state).await });
         Ok(())
     }
+
+    async fn pause_consumer(&self, request: ConsumerControlRequest) -> Result<(), Exception> {
+        self.state.consumer_control.pause_topic(&request.topic);
+        Ok(())
+    }
+
+    async fn resume_consumer(&self, request: ConsumerControlRequest) -> Result<(), Exception> {
+        self.state.consumer_control.resume_topic(&request.topic);
+        Ok(())
+    }
 }
@@ -67,12 +67,7 @@ impl Elasticsearch {
     pub(crate) async fn state(&self) -> Result<ClusterStateResponse, Exception> {
         let _span = span!("es");
         let uri = &self.uri;
-        let request = HttpRequest::new(Method::GET, format!("{uri}/_cluster/state"));
-        let response = self.client.execute(request).await?;
-        if response.status != 200 {
-            return Err(exception!(format!("failed to get state")));
-        }
-        json::from_json(&response.body)
+        self.client.get_json(format!("{uri}/_cluster/state")).await
     }
 
     pub(crate) async fn close_index(&self, index: String) -> Result<(), Exception> {
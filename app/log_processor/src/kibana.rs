@@ -8,8 +8,8 @@ use framework::http::Method;
 
 pub(crate) async fn import(kibana_uri: &str, objects: String) -> Result<(), Exception> {
     let http_client = HttpClient::new(HttpClientConfig::default());
-    let mut request =
-        HttpRequest::new(Method::POST, format!("{kibana_uri}/api/saved_objects/_bulk_create?overwrite=true"));
+    let mut request = HttpRequest::new(Method::POST, format!("{kibana_uri}/api/saved_objects/_bulk_create"));
+    request.query("overwrite", "true");
     request.header(HeaderName::from_static("kbn-xsrf"), "true")?;
     // request.headers.insert(HeaderName::from_static("osd-xsrf"), "true".to_string());
     request.body(objects, "application/json");
@@ -1,9 +1,12 @@
+use std::collections::HashMap;
 use std::fs;
 use std::sync::Arc;
 use std::time::Duration;
 
+use chrono::DateTime;
 use chrono::FixedOffset;
 use chrono::NaiveTime;
+use chrono::Utc;
 use framework::asset_path;
 use framework::config::EnvString;
 use framework::console;
@@ -12,6 +15,7 @@ use framework::exception::Exception;
 use framework::load_config;
 use framework::log;
 use framework::log::metrics::MetricsCollector;
+use framework::retry::RetryPolicy;
 use framework::schedule::Scheduler;
 use framework::spawn_action;
 use framework::system::System;
@@ -41,6 +45,8 @@ struct AppConfig {
     kibana_uri: String,
     banner: String,
     clickhouse: Option<ClickhouseConfig>,
+    // replay action logs from given time, e.g. after elasticsearch index is rebuilt
+    replay_action_log_from: Option<DateTime<Utc>>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -90,6 +96,11 @@ async fn main() -> Result<(), Exception> {
         cleanup_old_index_job,
         NaiveTime::from_hms_opt(1, 0, 0).expect("value must be valid"),
     );
+    // elasticsearch may be briefly unavailable, e.g. during node restart
+    scheduler.retry(
+        "cleanup_old_index_job",
+        RetryPolicy { max_attempts: 3, initial_backoff: Duration::from_secs(10), ..RetryPolicy::default() },
+    );
     system.spawn(scheduler.start(scheduler_state, system.shutdown_signal()));
 
     if let Some(config) = &config.clickhouse {
@@ -102,11 +113,22 @@ async fn main() -> Result<(), Exception> {
     let mut consumer = MessageConsumer::new(
         config.kafka_uri,
         env!("CARGO_BIN_NAME"),
-        &ConsumerConfig { poll_max_wait_time: Duration::from_secs(3), poll_max_records: 5_000 },
+        &ConsumerConfig {
+            poll_max_wait_time: Duration::from_secs(3),
+            poll_max_records: 5_000,
+            // flood of events must not starve action log processing
+            topic_max_records: HashMap::from([("event", 2_000)]),
+            ..ConsumerConfig::default()
+        },
     );
-    consumer.add_bulk_handler(&Topic::new("action-log-v2"), action_log_message_handler);
-    consumer.add_bulk_handler(&Topic::new("stat"), stat_message_handler);
-    consumer.add_bulk_handler(&Topic::new("event"), event_message_handler);
+    // retry on transient elasticsearch failure, e.g. rejected bulk request or node restart
+    let retry = RetryPolicy { max_attempts: 3, ..RetryPolicy::default() };
+    consumer.add_bulk_handler_with_retry(&Topic::new("action-log-v2"), action_log_message_handler, retry);
+    if let Some(from) = config.replay_action_log_from {
+        consumer.seek_to_timestamp("action-log-v2", from);
+    }
+    consumer.add_bulk_handler_with_retry(&Topic::new("stat"), stat_message_handler, retry);
+    consumer.add_bulk_handler_with_retry(&Topic::new("event"), event_message_handler, retry);
     collector.add(consumer.consumer_metrics());
     system.spawn(consumer.start(state, system.shutdown_signal()));
 
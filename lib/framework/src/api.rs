@@ -3,7 +3,7 @@ use serde::Serialize;
 
 use crate::exception::Severity;
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ErrorResponse {
     pub severity: Severity,
     pub code: Option<String>,
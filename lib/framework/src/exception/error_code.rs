@@ -2,4 +2,9 @@ pub const VALIDATION_ERROR: &str = "VALIDATION_ERROR";
 
 pub const BAD_REQUEST: &str = "BAD_REQUEST";
 pub const NOT_FOUND: &str = "NOT_FOUND";
+pub const UNAUTHORIZED: &str = "UNAUTHORIZED";
 pub const FORBIDDEN: &str = "FORBIDDEN";
+pub const CONFLICT: &str = "CONFLICT";
+pub const PAYLOAD_TOO_LARGE: &str = "PAYLOAD_TOO_LARGE";
+pub const TOO_MANY_REQUESTS: &str = "TOO_MANY_REQUESTS";
+pub const REQUEST_TIMEOUT: &str = "REQUEST_TIMEOUT";
@@ -1,13 +1,22 @@
 use std::collections::HashMap;
 use std::collections::VecDeque;
+use std::fmt::Debug;
+use std::io::Read as _;
 use std::mem;
+use std::path::Path;
+use std::path::PathBuf;
 use std::pin::Pin;
+use std::sync::Arc;
 use std::task::Context;
 use std::task::Poll;
 use std::time::Duration;
 use std::time::Instant;
 
+use base64::Engine as _;
+use base64::prelude::BASE64_STANDARD;
+use brotli::Decompressor;
 use bytes::Bytes;
+use flate2::read::GzDecoder;
 use futures::Stream;
 use futures::TryStreamExt as _;
 use http::HeaderMap;
@@ -17,30 +26,82 @@ pub use http::header;
 use reqwest::Body;
 use reqwest::Certificate;
 pub use reqwest::Method;
+use reqwest::Proxy;
 use reqwest::Request;
 use reqwest::Response;
 use reqwest::Url;
+use reqwest::cookie::CookieStore as _;
+pub use reqwest::cookie::Jar;
+use reqwest::redirect::Policy;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use tokio::fs;
+use tokio::fs::File;
+use tokio::io::AsyncWriteExt as _;
+use tokio::task;
+use tokio::time;
 use tokio::time::sleep;
 pub use tokio_stream::StreamExt;
 
 use crate::exception::Exception;
 use crate::exception::Severity;
+use crate::http::circuit_breaker::CircuitBreaker;
+use crate::http::circuit_breaker::CircuitBreakerConfig;
+use crate::http::multipart::Multipart;
+use crate::json;
 use crate::log;
+use crate::log::current_action_id;
+use crate::log::mask::is_masked_field;
 use crate::span;
 use crate::stats;
 use crate::warn;
+use crate::web::CORRELATION_ID;
+use crate::web::REF_ID;
+use crate::web::TRACEPARENT;
+
+pub mod circuit_breaker;
+pub mod mock;
+pub mod multipart;
+pub mod websocket;
+
+// guards against decompression bomb
+const MAX_DECOMPRESSED_SIZE: u64 = 100 * 1024 * 1024;
 
 #[derive(Clone)]
 pub struct HttpClient {
-    client: reqwest::Client,
+    transport: Arc<dyn HttpTransport>,
     retry: RetryConfig,
+    traceparent: bool,
+    cookie_jar: Option<Arc<Jar>>,
+    circuit_breaker: Option<Arc<CircuitBreaker>>,
+}
+
+// sends built request, reqwest::Client by default, replaced by mock::MockTransport in tests
+pub trait HttpTransport: Send + Sync {
+    fn execute(&self, request: Request) -> Pin<Box<dyn Future<Output = Result<Response, reqwest::Error>> + Send>>;
+}
+
+impl HttpTransport for reqwest::Client {
+    fn execute(&self, request: Request) -> Pin<Box<dyn Future<Output = Result<Response, reqwest::Error>> + Send>> {
+        Box::pin(reqwest::Client::execute(self, request))
+    }
 }
 
 #[allow(unused)]
 #[derive(Clone)]
 pub struct RetryConfig {
     pub max_attempts: u32,
+    // backoff of first retry, doubled on each following attempt, capped by max_interval
     pub interval: Duration,
+    pub max_interval: Duration,
+    pub retry_status: &'static [u16],
+}
+
+impl RetryConfig {
+    fn backoff(&self, attempt: u32) -> Duration {
+        let multiplier = 2_u32.saturating_pow(attempt.saturating_sub(1));
+        self.interval.saturating_mul(multiplier).min(self.max_interval)
+    }
 }
 
 #[allow(unused)]
@@ -48,10 +109,35 @@ pub struct HttpClientConfig {
     pub accept_invalid_cert: bool,
     pub accept_certs: Option<Vec<Certificate>>,
     pub timeout: Duration,
+    pub connect_timeout: Duration,
+    // max idle time between reads, resets on each read, to detect stalled sse or download streams
+    pub read_timeout: Option<Duration>,
+    pub pool_max_idle_per_host: usize,
+    pub pool_idle_timeout: Duration,
+    // e.g. http://proxy:3128, applies to both http and https
+    pub proxy: Option<String>,
+    pub user_agent: Option<String>,
     pub retry: RetryConfig,
+    // per host, disabled if none
+    pub circuit_breaker: Option<CircuitBreakerConfig>,
+    // ref-id header is always sent, traceparent is for downstream services with w3c trace context,
+    // trace id is propagated from incoming traceparent, otherwise derived from action id
+    pub traceparent: bool,
+    // keep cookies across requests, for session based apis, can be shared by multiple clients
+    pub cookie_jar: Option<Arc<Jar>>,
+    pub redirect: RedirectPolicy,
     pub prefer_http2: bool,
 }
 
+#[derive(Clone, Copy)]
+pub enum RedirectPolicy {
+    Follow { max_redirects: usize },
+    // fail with exception on 3xx
+    Reject,
+    // surface 3xx response to caller
+    Return,
+}
+
 impl Default for HttpClientConfig {
     // for docker image used on cloud env, must install "ca-certificates"
     fn default() -> Self {
@@ -59,7 +145,22 @@ impl Default for HttpClientConfig {
             accept_invalid_cert: false,
             accept_certs: None,
             timeout: Duration::from_secs(30),
-            retry: RetryConfig { max_attempts: 1, interval: Duration::from_millis(500) },
+            connect_timeout: Duration::from_secs(5),
+            read_timeout: None,
+            pool_max_idle_per_host: usize::MAX,
+            pool_idle_timeout: Duration::from_mins(5),
+            proxy: None,
+            user_agent: None,
+            retry: RetryConfig {
+                max_attempts: 1,
+                interval: Duration::from_millis(500),
+                max_interval: Duration::from_secs(5),
+                retry_status: &[503],
+            },
+            circuit_breaker: None,
+            traceparent: false,
+            cookie_jar: None,
+            redirect: RedirectPolicy::Follow { max_redirects: 10 },
             prefer_http2: false,
         }
     }
@@ -72,7 +173,22 @@ impl HttpClientConfig {
             accept_invalid_cert: true,
             accept_certs: Some(vec![]),
             timeout: Duration::from_secs(30),
-            retry: RetryConfig { max_attempts: 3, interval: Duration::from_millis(500) },
+            connect_timeout: Duration::from_secs(5),
+            read_timeout: None,
+            pool_max_idle_per_host: usize::MAX,
+            pool_idle_timeout: Duration::from_mins(5),
+            proxy: None,
+            user_agent: None,
+            retry: RetryConfig {
+                max_attempts: 3,
+                interval: Duration::from_millis(500),
+                max_interval: Duration::from_secs(5),
+                retry_status: &[502, 503, 504],
+            },
+            circuit_breaker: None,
+            traceparent: false,
+            cookie_jar: None,
+            redirect: RedirectPolicy::Follow { max_redirects: 10 },
             prefer_http2: true,
         }
     }
@@ -81,13 +197,27 @@ impl HttpClientConfig {
 pub struct HttpRequest {
     pub method: Method,
     pub url: String,
+    // overrides HttpClientConfig.timeout for this request, e.g. slow bulk calls
+    pub timeout: Option<Duration>,
+    query: Vec<(String, String)>,
     headers: HeaderMap,
-    body: Option<String>,
+    body: Option<RequestBody>,
+}
+
+enum RequestBody {
+    Text(String),
+    Binary(Bytes),
+    Multipart(Multipart),
 }
 
 impl HttpRequest {
     pub fn new(method: Method, url: impl Into<String>) -> Self {
-        HttpRequest { method, url: url.into(), headers: HeaderMap::new(), body: None }
+        HttpRequest { method, url: url.into(), timeout: None, query: vec![], headers: HeaderMap::new(), body: None }
+    }
+
+    // appended to url with url encoding when request is built
+    pub fn query(&mut self, key: impl Into<String>, value: impl Into<String>) {
+        self.query.push((key.into(), value.into()));
     }
 
     pub fn header(&mut self, name: HeaderName, value: &str) -> Result<(), Exception> {
@@ -99,24 +229,104 @@ impl HttpRequest {
         Ok(())
     }
 
+    pub fn basic_auth(&mut self, user: &str, password: &str) -> Result<(), Exception> {
+        let credentials = BASE64_STANDARD.encode(format!("{user}:{password}"));
+        self.authorization(format!("Basic {credentials}"))
+    }
+
+    // fails if token contains control characters, e.g. CR/LF
+    pub fn bearer_token(&mut self, token: &str) -> Result<(), Exception> {
+        self.authorization(format!("Bearer {token}"))
+    }
+
+    fn authorization(&mut self, value: String) -> Result<(), Exception> {
+        // value is not included in error, as it's credential
+        let mut value = HeaderValue::try_from(value)
+            .map_err(|err| exception!("invalid authorization header value", source = err))?;
+        value.set_sensitive(true);
+        self.headers.insert(header::AUTHORIZATION, value);
+        Ok(())
+    }
+
     pub fn body(&mut self, body: String, content_type: &'static str) {
-        self.body = Some(body);
+        self.body = Some(RequestBody::Text(body));
         self.headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(content_type));
     }
+
+    // e.g. form(&[("grant_type", "client_credentials")]), used by oauth token endpoints
+    pub fn form<T>(&mut self, form: &T) -> Result<(), Exception>
+    where
+        T: Serialize + ?Sized,
+    {
+        let body = serde_html_form::to_string(form)?;
+        self.body(body, "application/x-www-form-urlencoded");
+        Ok(())
+    }
+
+    // for binary content, e.g. gzip, protobuf or parquet
+    pub fn binary_body(&mut self, body: impl Into<Bytes>, content_type: &'static str) {
+        self.body = Some(RequestBody::Binary(body.into()));
+        self.headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(content_type));
+    }
+
+    pub fn multipart(&mut self, multipart: Multipart) {
+        let content_type = format!("multipart/form-data; boundary={}", multipart.boundary);
+        self.headers
+            .insert(header::CONTENT_TYPE, HeaderValue::from_str(&content_type).expect("boundary must be valid"));
+        self.body = Some(RequestBody::Multipart(multipart));
+    }
 }
 
 pub struct HttpResponse {
     pub status: u16,
     pub headers: HashMap<HeaderName, String>,
-    pub body: String,
+    pub body: Bytes,
+}
+
+impl HttpResponse {
+    pub fn text(&self) -> Result<&str, Exception> {
+        str::from_utf8(&self.body).map_err(|err| exception!("response body is not valid utf-8", source = err))
+    }
+}
+
+pub struct ResponseStream {
+    pub status: u16,
+    pub headers: HashMap<HeaderName, String>,
+    stream: Pin<Box<dyn Stream<Item = Result<Bytes, reqwest::Error>> + Send>>,
+    read_bytes: usize,
+}
+
+impl Stream for ResponseStream {
+    type Item = Result<Bytes, Exception>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        match self.stream.as_mut().poll_next(cx) {
+            Poll::Ready(Some(Ok(bytes))) => {
+                self.read_bytes += bytes.len();
+                Poll::Ready(Some(Ok(bytes)))
+            }
+            Poll::Ready(Some(Err(err))) => Poll::Ready(Some(Err(request_error(err)))),
+            Poll::Ready(None) => Poll::Ready(None),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl Drop for ResponseStream {
+    fn drop(&mut self) {
+        stats!(http_read_bytes = self.read_bytes);
+    }
 }
 
 impl HttpClient {
     pub fn new(config: HttpClientConfig) -> Self {
         let mut builder = reqwest::Client::builder()
             .timeout(config.timeout)
+            .connect_timeout(config.connect_timeout)
             .tls_danger_accept_invalid_certs(config.accept_invalid_cert)
-            .pool_idle_timeout(Duration::from_mins(5))
+            .pool_max_idle_per_host(config.pool_max_idle_per_host)
+            .pool_idle_timeout(config.pool_idle_timeout)
+            .redirect(redirect_policy(config.redirect))
             .connection_verbose(false);
 
         if config.prefer_http2 {
@@ -127,34 +337,206 @@ impl HttpClient {
             builder = builder.tls_certs_only(certs);
         }
 
+        if let Some(read_timeout) = config.read_timeout {
+            builder = builder.read_timeout(read_timeout);
+        }
+
+        if let Some(ref proxy) = config.proxy {
+            builder = builder.proxy(Proxy::all(proxy).expect("proxy url must be valid"));
+        }
+
+        if let Some(user_agent) = config.user_agent {
+            builder = builder.user_agent(user_agent);
+        }
+
+        if let Some(ref jar) = config.cookie_jar {
+            builder = builder.cookie_provider(Arc::clone(jar));
+        }
+
         let client = builder.build().expect("build cannot fail");
-        HttpClient { client, retry: config.retry }
+        HttpClient {
+            transport: Arc::new(client),
+            retry: config.retry,
+            traceparent: config.traceparent,
+            cookie_jar: config.cookie_jar,
+            circuit_breaker: config.circuit_breaker.map(|config| Arc::new(CircuitBreaker::new(config))),
+        }
+    }
+
+    pub fn with_transport(transport: Arc<dyn HttpTransport>, retry: RetryConfig) -> Self {
+        HttpClient { transport, retry, traceparent: false, cookie_jar: None, circuit_breaker: None }
     }
 
-    pub async fn execute(&self, request: HttpRequest) -> Result<HttpResponse, Exception> {
+    pub async fn execute(&self, mut request: HttpRequest) -> Result<HttpResponse, Exception> {
         let _span = span!("http");
+        request.headers.entry(header::ACCEPT_ENCODING).or_insert(HeaderValue::from_static("gzip, br"));
+        let response = self.send(&request).await?;
+
+        let status = response.status().as_u16();
+        log!("[response] status={status}");
+
+        let mut headers = parse_headers(&response)?;
+
+        let mut body = response.bytes().await.map_err(request_error)?;
+        stats!(http_read_bytes = body.len());
+        if let Some(encoding) = headers.get(&header::CONTENT_ENCODING).map(|value| value.trim().to_ascii_lowercase())
+            && matches!(encoding.as_str(), "gzip" | "x-gzip" | "br")
+        {
+            // decompression is cpu bound, run outside of async workers
+            body = task::spawn_blocking(move || decompress(&encoding, body, MAX_DECOMPRESSED_SIZE)).await??;
+            stats!(http_decompressed_bytes = body.len());
+            // headers describe encoded body, which is not returned
+            headers.remove(&header::CONTENT_ENCODING);
+            headers.remove(&header::CONTENT_LENGTH);
+        }
+        if let Some(content_type) = headers.get(&header::CONTENT_TYPE)
+            && (content_type.contains("json") || content_type.contains("text"))
+        {
+            log!("[response] body={}", String::from_utf8_lossy(&body));
+        }
+
+        Ok(HttpResponse { status, headers, body })
+    }
+
+    pub async fn get_json<Res>(&self, url: impl Into<String>) -> Result<Res, Exception>
+    where
+        Res: DeserializeOwned,
+    {
+        self.execute_json(HttpRequest::new(Method::GET, url)).await
+    }
+
+    pub async fn post_json<Req, Res>(&self, url: impl Into<String>, request: &Req) -> Result<Res, Exception>
+    where
+        Req: Serialize + Debug,
+        Res: DeserializeOwned,
+    {
+        let mut http_request = HttpRequest::new(Method::POST, url);
+        http_request.body(json::to_json(request)?, "application/json");
+        self.execute_json(http_request).await
+    }
+
+    pub async fn put_json<Req, Res>(&self, url: impl Into<String>, request: &Req) -> Result<Res, Exception>
+    where
+        Req: Serialize + Debug,
+        Res: DeserializeOwned,
+    {
+        let mut http_request = HttpRequest::new(Method::PUT, url);
+        http_request.body(json::to_json(request)?, "application/json");
+        self.execute_json(http_request).await
+    }
+
+    async fn execute_json<Res>(&self, request: HttpRequest) -> Result<Res, Exception>
+    where
+        Res: DeserializeOwned,
+    {
+        let method = request.method.clone();
+        let url = request.url.clone();
+        let response = self.execute(request).await?;
+        let status = response.status;
+        if !(200..300).contains(&status) {
+            return Err(exception!(format!(
+                "failed to call http, method={method}, url={url}, status={status}, body={}",
+                String::from_utf8_lossy(&response.body)
+            )));
+        }
+        json::from_json(response.text()?)
+    }
+
+    // for large response, body is not buffered, read bytes are reported to stats when stream is dropped
+    pub async fn execute_stream(&self, request: HttpRequest) -> Result<ResponseStream, Exception> {
+        let _span = span!("http");
+        let response = self.send(&request).await?;
+
+        let status = response.status().as_u16();
+        log!("[response] status={status}");
+
+        let headers = parse_headers(&response)?;
+
+        Ok(ResponseStream { status, headers, stream: Box::pin(response.bytes_stream()), read_bytes: 0 })
+    }
+
+    pub async fn download_to_file(&self, request: HttpRequest, path: &Path) -> Result<u64, Exception> {
+        let mut response = self.execute_stream(request).await?;
+        if !(200..300).contains(&response.status) {
+            return Err(exception!(format!(
+                "failed to download file, status={}, path={}",
+                response.status,
+                path.display()
+            )));
+        }
+
+        log!("download to file, path={}", path.display());
+        // write to temp file first, so incomplete download won't leave truncated file at path
+        let mut temp_path = path.as_os_str().to_owned();
+        temp_path.push(".tmp");
+        let temp_path = PathBuf::from(temp_path);
+        let result = async {
+            let mut file = File::create(&temp_path).await?;
+            let mut length = 0;
+            while let Some(bytes) = response.next().await {
+                let bytes = bytes?;
+                file.write_all(&bytes).await?;
+                length += bytes.len() as u64;
+            }
+            file.flush().await?;
+            Ok::<_, Exception>(length)
+        }
+        .await;
+        match result {
+            Ok(length) => {
+                fs::rename(&temp_path, path).await?;
+                Ok(length)
+            }
+            Err(e) => {
+                let _result = fs::remove_file(&temp_path).await;
+                Err(e)
+            }
+        }
+    }
+
+    async fn send(&self, request: &HttpRequest) -> Result<Response, Exception> {
         let max_attempts = self.retry.max_attempts.max(1);
-        let interval = self.retry.interval;
 
         let mut attempt: u32 = 0;
         let response = loop {
             attempt += 1;
-            let http_request = create_request(&request)?;
-            match self.client.execute(http_request).await {
+            let mut http_request = create_request(request)?;
+            self.link_context(&mut http_request);
+            if let Some(timeout) = request.timeout {
+                *http_request.timeout_mut() = Some(timeout);
+            }
+            let host = format!(
+                "{}:{}",
+                http_request.url().host_str().unwrap_or_default(),
+                http_request.url().port_or_known_default().unwrap_or_default()
+            );
+            if let Some(ref circuit_breaker) = self.circuit_breaker {
+                circuit_breaker.acquire(&host)?;
+            }
+            let result = self.transport.execute(http_request).await;
+            if let Some(ref circuit_breaker) = self.circuit_breaker {
+                match result {
+                    Ok(ref response) if !response.status().is_server_error() => circuit_breaker.success(&host),
+                    Ok(_) | Err(_) => circuit_breaker.failure(&host),
+                }
+            }
+            match result {
                 Ok(response) => {
                     let status = response.status().as_u16();
-                    if status == 503 && attempt < max_attempts {
+                    if self.retry.retry_status.contains(&status) && attempt < max_attempts {
                         warn!(
                             error_code = "HTTP_REQUEST_FAILED",
                             "http request failed, retry soon, attempt={attempt}, status={status}"
                         );
-                        sleep(interval * attempt).await;
+                        stats!(http_retries = 1);
+                        sleep(self.retry.backoff(attempt)).await;
                         continue;
                     }
                     break response;
                 }
                 Err(err) => {
-                    if request.method.is_idempotent() && attempt < max_attempts {
+                    // connect errors mean the request was never sent, safe to retry for any method
+                    if (err.is_connect() || request.method.is_idempotent()) && attempt < max_attempts {
                         // TODO: refactor log exception
                         warn!(
                             error_code = "HTTP_REQUEST_FAILED",
@@ -166,61 +548,150 @@ impl HttpClient {
                                 source = err
                             )
                         );
-                        sleep(interval * attempt).await;
+                        stats!(http_retries = 1);
+                        sleep(self.retry.backoff(attempt)).await;
                         continue;
                     }
-                    return Err(exception!("http request failed", code = "HTTP_REQUEST_FAILED", source = err));
+                    return Err(request_error(err));
                 }
             }
         };
+        Ok(response)
+    }
 
-        let status = response.status().as_u16();
-        log!("[response] status={status}");
-
-        let headers = parse_headers(&response)?;
-
-        let body = response.text().await?;
-        if let Some(content_type) = headers.get(&header::CONTENT_TYPE)
-            && (content_type.contains("json") || content_type.contains("text"))
-        {
-            log!("[response] body={body}");
-        }
-        stats!(http_read_bytes = body.len());
+    pub async fn sse(&self, request: HttpRequest) -> Result<EventSource, Exception> {
+        self.sse_stream(request, false).await
+    }
 
-        Ok(HttpResponse { status, headers, body })
+    // reconnects with Last-Event-ID header when connection drops, until server responds with non 200 status
+    pub async fn sse_with_reconnect(&self, request: HttpRequest) -> Result<EventSource, Exception> {
+        self.sse_stream(request, true).await
     }
 
-    pub async fn sse(&self, mut request: HttpRequest) -> Result<EventSource, Exception> {
+    async fn sse_stream(&self, mut request: HttpRequest, reconnect: bool) -> Result<EventSource, Exception> {
         let _span = span!("sse");
         request.headers.insert(header::ACCEPT, HeaderValue::from_static("text/event-stream"));
-        let http_request = create_request(&request)?;
+        let http_request = self.sse_request(&request, None)?;
 
-        let response = self.client.execute(http_request).await?;
-        let status = response.status().as_u16();
-        log!("[response] status={status}");
+        // only bound the handshake, the event stream itself is long-lived
+        let response = if let Some(timeout) = request.timeout {
+            time::timeout(timeout, self.transport.execute(http_request)).await.map_err(|_elapsed| {
+                exception!(format!("sse handshake timed out, timeout={timeout:?}"), code = "HTTP_REQUEST_TIMEOUT")
+            })?
+        } else {
+            self.transport.execute(http_request).await
+        }
+        .map_err(request_error)?;
+        let response = sse_response(response).await?;
 
-        let headers = parse_headers(&response)?;
+        let stream = response.bytes_stream();
+        let mut event_source = EventSource::new(Box::pin(stream.map_err(request_error)));
+        if reconnect {
+            event_source.reconnect =
+                Some(Reconnect { client: self.clone(), request, retry: Duration::from_secs(3), connecting: None });
+        }
+        Ok(event_source)
+    }
 
-        if status == 200
-            && let Some(content_type) = headers.get(&header::CONTENT_TYPE)
-            && content_type.starts_with("text/event-stream")
+    // shared by first connect and reconnect
+    fn sse_request(&self, request: &HttpRequest, last_event_id: Option<&str>) -> Result<Request, Exception> {
+        let mut http_request = create_request(request)?;
+        self.link_context(&mut http_request);
+        if let Some(last_event_id) = last_event_id {
+            http_request
+                .headers_mut()
+                .insert(HeaderName::from_static("last-event-id"), HeaderValue::from_str(last_event_id)?);
+        }
+        Ok(http_request)
+    }
+
+    fn link_context(&self, http_request: &mut Request) {
+        let Some(action_id) = current_action_id() else {
+            return;
+        };
+        let headers = http_request.headers_mut();
+        if !headers.contains_key(REF_ID)
+            && let Ok(value) = HeaderValue::from_str(&action_id)
         {
-            let stream = response.bytes_stream();
-            Ok(EventSource::new(Box::pin(stream.map_err(reqwest::Error::into))))
-        } else {
-            let body = response.text().await?;
-            log!("[response] body={body}");
-            let content_type = headers.get(&header::CONTENT_TYPE);
-            Err(exception!(format!("invalid sse response, status={status}, content_type={content_type:?}")))
+            log!("[header] {REF_ID}={action_id}");
+            headers.insert(REF_ID, value);
+        }
+        if !headers.contains_key(CORRELATION_ID)
+            && let Some(correlation_id) = log::current_correlation_id()
+            && let Ok(value) = HeaderValue::from_str(&correlation_id)
+        {
+            log!("[header] {CORRELATION_ID}={correlation_id}");
+            headers.insert(CORRELATION_ID, value);
+        }
+        if self.traceparent
+            && !headers.contains_key(TRACEPARENT)
+            && let Some(traceparent) = log::current_traceparent()
+            && let Ok(value) = HeaderValue::from_str(&traceparent)
+        {
+            log!("[header] {TRACEPARENT}={}", value.to_str().unwrap_or_default());
+            headers.insert(TRACEPARENT, value);
+        }
+        // cookie header is added by transport, log here to make session flow observable
+        if let Some(ref jar) = self.cookie_jar
+            && let Some(cookies) = jar.cookies(http_request.url())
+        {
+            log!("[cookie] {}", cookies.to_str().unwrap_or_default());
         }
     }
 }
 
+fn redirect_policy(redirect: RedirectPolicy) -> Policy {
+    match redirect {
+        RedirectPolicy::Return => Policy::none(),
+        RedirectPolicy::Follow { .. } | RedirectPolicy::Reject => Policy::custom(move |attempt| {
+            log!("[redirect] status={}, url={}", attempt.status().as_u16(), attempt.url());
+            match redirect {
+                RedirectPolicy::Follow { max_redirects } if attempt.previous().len() <= max_redirects => {
+                    attempt.follow()
+                }
+                RedirectPolicy::Follow { max_redirects } => {
+                    attempt.error(format!("too many redirects, max_redirects={max_redirects}"))
+                }
+                RedirectPolicy::Reject | RedirectPolicy::Return => attempt.error("redirect is not allowed"),
+            }
+        }),
+    }
+}
+
+fn request_error(err: reqwest::Error) -> Exception {
+    if err.is_timeout() {
+        exception!("http request timed out", code = "HTTP_REQUEST_TIMEOUT", source = err)
+    } else {
+        exception!("http request failed", code = "HTTP_REQUEST_FAILED", source = err)
+    }
+}
+
+// small compressed body may expand to gigabytes, reads one more byte than max_size to detect overflow
+fn decompress(encoding: &str, body: Bytes, max_size: u64) -> Result<Bytes, Exception> {
+    let mut decompressed = vec![];
+    match encoding {
+        "gzip" | "x-gzip" => GzDecoder::new(body.as_ref()).take(max_size + 1).read_to_end(&mut decompressed),
+        "br" => Decompressor::new(body.as_ref(), 4096).take(max_size + 1).read_to_end(&mut decompressed),
+        _ => return Ok(body),
+    }
+    .map_err(|err| exception!(format!("failed to decompress response body, encoding={encoding}"), source = err))?;
+    if decompressed.len() as u64 > max_size {
+        return Err(exception!(format!(
+            "decompressed response body exceeds max size, encoding={encoding}, max_size={max_size}"
+        )));
+    }
+    Ok(Bytes::from(decompressed))
+}
+
 fn parse_headers(response: &Response) -> Result<HashMap<HeaderName, String>, Exception> {
     let mut headers = HashMap::new();
     for (key, value) in response.headers() {
         let value = value.to_str()?;
-        log!("[header] {key}={value}");
+        if is_masked_field(key.as_str()) {
+            log!("[header] {key}=******");
+        } else {
+            log!("[header] {key}={value}");
+        }
         headers.insert(key.to_owned(), value.to_owned());
     }
     Ok(headers)
@@ -228,18 +699,41 @@ fn parse_headers(response: &Response) -> Result<HashMap<HeaderName, String>, Exc
 
 fn create_request(request: &HttpRequest) -> Result<Request, Exception> {
     log!("[request] method={}", request.method.as_str());
-    log!("[request] url={}", request.url);
-    let url =
+    let mut url =
         Url::parse(&request.url).map_err(|e| exception!(format!("invalid url, url={}", &request.url), source = e))?;
+    if !request.query.is_empty() {
+        url.query_pairs_mut().extend_pairs(&request.query);
+    }
+    log!("[request] url={url}");
     let mut http_request = Request::new(request.method.clone(), url);
     http_request.headers_mut().extend(request.headers.clone());
     for (key, value) in http_request.headers() {
-        log!("[header] {key}={}", value.to_str()?);
+        if value.is_sensitive() || is_masked_field(key.as_str()) {
+            log!("[header] {key}=******");
+        } else {
+            log!("[header] {key}={}", value.to_str()?);
+        }
     }
-    if let Some(ref body) = request.body {
-        log!("[request] body={body}");
-        stats!(http_write_bytes = body.len());
-        *http_request.body_mut() = Some(Body::from(body.to_owned()));
+    match request.body {
+        Some(RequestBody::Text(ref body)) => {
+            log!("[request] body={body}");
+            stats!(http_write_bytes = body.len());
+            *http_request.body_mut() = Some(Body::from(body.to_owned()));
+        }
+        Some(RequestBody::Binary(ref body)) => {
+            log!("[request] body_length={}", body.len());
+            stats!(http_write_bytes = body.len());
+            *http_request.body_mut() = Some(Body::from(body.clone()));
+        }
+        Some(RequestBody::Multipart(ref multipart)) => {
+            for part in &multipart.parts {
+                log!("[request] {part:?}");
+            }
+            let body = multipart.encode();
+            stats!(http_write_bytes = body.len());
+            *http_request.body_mut() = Some(Body::from(body));
+        }
+        None => {}
     }
     Ok(http_request)
 }
@@ -257,10 +751,58 @@ pub struct EventSource {
     events: VecDeque<Event>,
     last_id: Option<String>,
     last_type: Option<String>,
+    last_event_id: Option<String>,
+    reconnect: Option<Reconnect>,
 
     start_time: Instant,
     read_bytes: usize,
     read_entries: usize,
+    reconnects: usize,
+}
+
+const INVALID_SSE_RESPONSE: &str = "INVALID_SSE_RESPONSE";
+
+// fails if status is not 200 or content type is not event stream
+async fn sse_response(response: Response) -> Result<Response, Exception> {
+    let status = response.status().as_u16();
+    log!("[response] status={status}");
+    let headers = parse_headers(&response)?;
+    if status == 200
+        && let Some(content_type) = headers.get(&header::CONTENT_TYPE)
+        && content_type.starts_with("text/event-stream")
+    {
+        return Ok(response);
+    }
+    let body = response.text().await?;
+    log!("[response] body={body}");
+    let content_type = headers.get(&header::CONTENT_TYPE);
+    Err(exception!(
+        format!("invalid sse response, status={status}, content_type={content_type:?}"),
+        code = INVALID_SSE_RESPONSE
+    ))
+}
+
+type ConnectFuture = Pin<Box<dyn Future<Output = Result<Response, Exception>> + Send>>;
+
+struct Reconnect {
+    client: HttpClient,
+    request: HttpRequest,
+    // updated by "retry:" field from server
+    retry: Duration,
+    connecting: Option<ConnectFuture>,
+}
+
+impl Reconnect {
+    fn connect(&self, last_event_id: Option<&str>) -> Result<ConnectFuture, Exception> {
+        let http_request = self.client.sse_request(&self.request, last_event_id)?;
+        let transport = Arc::clone(&self.client.transport);
+        let retry = self.retry;
+        Ok(Box::pin(async move {
+            sleep(retry).await;
+            let response = transport.execute(http_request).await.map_err(request_error)?;
+            sse_response(response).await
+        }))
+    }
 }
 
 impl EventSource {
@@ -271,10 +813,61 @@ impl EventSource {
             events: VecDeque::new(),
             last_id: None,
             last_type: None,
+            last_event_id: None,
+            reconnect: None,
             start_time: Instant::now(),
             read_bytes: 0,
             read_entries: 0,
+            reconnects: 0,
+        }
+    }
+
+    fn parse(&mut self, bytes: Bytes) {
+        for byte in bytes {
+            if byte == b'\n' {
+                let current_bytes = mem::take(&mut self.buffer);
+                let line = String::from_utf8_lossy(&current_bytes);
+                log!("[sse] {line}");
+                self.read_bytes += line.len();
+
+                if !line.is_empty()
+                    && let Some(index) = line.find(": ")
+                {
+                    let field = &line[0..index];
+                    let value = &line[index + 2..];
+                    match field {
+                        "id" => {
+                            self.last_id = Some(value.to_owned());
+                            self.last_event_id = Some(value.to_owned());
+                        }
+                        "event" => self.last_type = Some(value.to_owned()),
+                        "data" => {
+                            let id = self.last_id.take();
+                            let r#type = self.last_type.take();
+                            self.events.push_back(Event { id, r#type, data: value.to_owned() });
+                            self.read_entries += 1;
+                        }
+                        "retry" => {
+                            if let Some(ref mut reconnect) = self.reconnect
+                                && let Ok(retry) = value.parse()
+                            {
+                                reconnect.retry = Duration::from_millis(retry);
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+            } else {
+                self.buffer.push(byte);
+            }
+        }
+    }
+
+    fn start_reconnect(&mut self) -> Result<(), Exception> {
+        if let Some(ref mut reconnect) = self.reconnect {
+            reconnect.connecting = Some(reconnect.connect(self.last_event_id.as_deref())?);
         }
+        Ok(())
     }
 }
 
@@ -284,6 +877,7 @@ impl Drop for EventSource {
             sse_read_entries = self.read_entries,
             sse_read_bytes = self.read_bytes,
             sse_elapsed = self.start_time.elapsed().as_nanos(),
+            sse_reconnects = self.reconnects,
             sse_count = 1
         );
     }
@@ -293,49 +887,255 @@ impl Stream for EventSource {
     type Item = Result<Event, Exception>;
 
     fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
         loop {
-            if let Some(event) = self.events.pop_front() {
+            if let Some(event) = this.events.pop_front() {
                 return Poll::Ready(Some(Ok(event)));
             }
 
-            match self.response.as_mut().poll_next(cx) {
-                Poll::Ready(Some(Ok(bytes))) => {
-                    for byte in bytes {
-                        if byte == b'\n' {
-                            let current_bytes = mem::take(&mut self.buffer);
-                            let line = String::from_utf8_lossy(&current_bytes);
-                            log!("[sse] {line}");
-                            self.read_bytes += line.len();
-
-                            if !line.is_empty()
-                                && let Some(index) = line.find(": ")
-                            {
-                                let field = &line[0..index];
-                                match field {
-                                    "id" => self.last_id = Some(line[index + 2..].to_string()),
-                                    "event" => self.last_type = Some(line[index + 2..].to_string()),
-                                    "data" => {
-                                        let id = self.last_id.take();
-                                        let r#type = self.last_type.take();
-                                        self.events.push_back(Event {
-                                            id,
-                                            r#type,
-                                            data: line[index + 2..].to_string(),
-                                        });
-                                        self.read_entries += 1;
-                                    }
-                                    _ => {}
-                                }
-                            }
-                        } else {
-                            self.buffer.push(byte);
+            if let Some(ref mut reconnect) = this.reconnect
+                && let Some(ref mut connecting) = reconnect.connecting
+            {
+                match connecting.as_mut().poll(cx) {
+                    Poll::Ready(Ok(response)) => {
+                        reconnect.connecting = None;
+                        log!("[sse] reconnected");
+                        this.response = Box::pin(response.bytes_stream().map_err(request_error));
+                        this.buffer.clear();
+                        this.reconnects += 1;
+                    }
+                    Poll::Ready(Err(err)) if err.code == Some(INVALID_SSE_RESPONSE) => {
+                        // per spec, non 200 response means server wants client to stop reconnecting
+                        this.reconnect = None;
+                        return Poll::Ready(Some(Err(err)));
+                    }
+                    Poll::Ready(Err(err)) => {
+                        warn!(
+                            error_code = "SSE_RECONNECT_FAILED",
+                            "failed to reconnect sse, retry={:?}, error={err}", reconnect.retry
+                        );
+                        if let Err(connect_error) = this.start_reconnect() {
+                            return Poll::Ready(Some(Err(connect_error)));
                         }
                     }
+                    Poll::Pending => return Poll::Pending,
+                }
+                continue;
+            }
+
+            match this.response.as_mut().poll_next(cx) {
+                Poll::Ready(Some(Ok(bytes))) => this.parse(bytes),
+                Poll::Ready(Some(Err(err))) => {
+                    if this.reconnect.is_none() {
+                        return Poll::Ready(Some(Err(err)));
+                    }
+                    warn!(error_code = "SSE_CONNECTION_FAILED", "sse connection failed, reconnect, error={err}");
+                    if let Err(connect_error) = this.start_reconnect() {
+                        return Poll::Ready(Some(Err(connect_error)));
+                    }
+                }
+                Poll::Ready(None) => {
+                    if this.reconnect.is_none() {
+                        return Poll::Ready(None);
+                    }
+                    log!("[sse] connection closed, reconnect");
+                    if let Err(err) = this.start_reconnect() {
+                        return Poll::Ready(Some(Err(err)));
+                    }
                 }
-                Poll::Ready(Some(Err(err))) => return Poll::Ready(Some(Err(err))),
-                Poll::Ready(None) => return Poll::Ready(None),
                 Poll::Pending => return Poll::Pending,
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::io::Write as _;
+    use std::process;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use bytes::Bytes;
+    use flate2::Compression;
+    use flate2::write::GzEncoder;
+    use futures::StreamExt as _;
+    use futures::stream;
+    use http::HeaderMap;
+    use http::HeaderValue;
+
+    use crate::http::EventSource;
+    use crate::http::HttpClient;
+    use crate::http::HttpClientConfig;
+    use crate::http::HttpRequest;
+    use crate::http::INVALID_SSE_RESPONSE;
+    use crate::http::Method;
+    use crate::http::Reconnect;
+    use crate::http::RetryConfig;
+    use crate::http::decompress;
+    use crate::http::header;
+    use crate::http::mock::MockTransport;
+    use crate::log::trace_id;
+    use crate::log::traceparent;
+
+    #[test]
+    fn retry_backoff_with_max_interval() {
+        let retry = RetryConfig {
+            max_attempts: 5,
+            interval: Duration::from_millis(500),
+            max_interval: Duration::from_secs(3),
+            retry_status: &[503],
+        };
+        assert_eq!(retry.backoff(1), Duration::from_millis(500));
+        assert_eq!(retry.backoff(2), Duration::from_secs(1));
+        assert_eq!(retry.backoff(3), Duration::from_secs(2));
+        assert_eq!(retry.backoff(4), Duration::from_secs(3));
+        assert_eq!(retry.backoff(40), Duration::from_secs(3));
+    }
+
+    #[test]
+    fn reject_invalid_authorization() {
+        let mut request = HttpRequest::new(Method::GET, "http://localhost");
+        request.bearer_token("token\r\nx-injected: value").unwrap_err();
+        assert!(!request.headers.contains_key(header::AUTHORIZATION));
+
+        request.basic_auth("user", "password").unwrap();
+        assert!(request.headers[header::AUTHORIZATION].is_sensitive());
+    }
+
+    #[test]
+    fn decompress_gzip() {
+        let mut encoder = GzEncoder::new(vec![], Compression::default());
+        encoder.write_all(b"{\"key\":\"value\"}").unwrap();
+        let body = Bytes::from(encoder.finish().unwrap());
+
+        assert_eq!(decompress("gzip", body.clone(), 1024).unwrap(), "{\"key\":\"value\"}");
+        assert_eq!(decompress("x-gzip", body, 1024).unwrap(), "{\"key\":\"value\"}");
+        assert_eq!(decompress("identity", Bytes::from("text"), 1024).unwrap(), "text");
+    }
+
+    #[test]
+    fn decompress_exceeds_max_size() {
+        let mut encoder = GzEncoder::new(vec![], Compression::default());
+        encoder.write_all(&[0; 10_000]).unwrap();
+        let body = Bytes::from(encoder.finish().unwrap());
+
+        decompress("gzip", body.clone(), 9_999).unwrap_err();
+        assert_eq!(decompress("gzip", body, 10_000).unwrap().len(), 10_000);
+    }
+
+    #[test]
+    fn parse_event_with_retry() {
+        let mut event_source = EventSource::new(Box::pin(stream::empty()));
+        event_source.reconnect = Some(Reconnect {
+            client: HttpClient::new(HttpClientConfig::default()),
+            request: HttpRequest::new(Method::GET, "http://localhost/events"),
+            retry: Duration::from_secs(3),
+            connecting: None,
+        });
+        event_source.parse(Bytes::from("retry: 1000\nid: 1\nevent: message\ndata: hello\n\n"));
+
+        let event = event_source.events.pop_front().unwrap();
+        assert_eq!(event.id.as_deref(), Some("1"));
+        assert_eq!(event.r#type.as_deref(), Some("message"));
+        assert_eq!(event.data, "hello");
+        assert_eq!(event_source.last_event_id.as_deref(), Some("1"));
+        assert_eq!(event_source.reconnect.as_ref().unwrap().retry, Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn execute_with_mock_transport() {
+        let transport = Arc::new(MockTransport::new());
+        transport.respond_json(200, r#"{"status":"ok"}"#);
+        let client = HttpClient::with_transport(
+            Arc::<MockTransport>::clone(&transport),
+            RetryConfig { max_attempts: 1, interval: Duration::ZERO, max_interval: Duration::ZERO, retry_status: &[] },
+        );
+
+        let mut request = HttpRequest::new(Method::POST, "http://localhost/api");
+        request.query("key", "a b");
+        request.body(r#"{"name":"value"}"#.to_owned(), "application/json");
+        let response = client.execute(request).await.unwrap();
+        assert_eq!(response.status, 200);
+        assert_eq!(response.text().unwrap(), r#"{"status":"ok"}"#);
+
+        let requests = transport.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].url, "http://localhost/api?key=a+b");
+        assert_eq!(requests[0].body.as_deref(), Some(br#"{"name":"value"}"#.as_slice()));
+    }
+
+    #[tokio::test]
+    async fn execute_decompresses_body() {
+        let mut encoder = GzEncoder::new(vec![], Compression::default());
+        encoder.write_all(b"{\"status\":\"ok\"}").unwrap();
+        let body = encoder.finish().unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert(header::CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        headers.insert(header::CONTENT_ENCODING, HeaderValue::from_static(" GZIP "));
+        headers.insert(header::CONTENT_LENGTH, HeaderValue::from(body.len()));
+        let transport = Arc::new(MockTransport::new());
+        transport.respond_with_headers(200, headers, body);
+        let client = HttpClient::with_transport(
+            Arc::<MockTransport>::clone(&transport),
+            RetryConfig { max_attempts: 1, interval: Duration::ZERO, max_interval: Duration::ZERO, retry_status: &[] },
+        );
+
+        let response = client.execute(HttpRequest::new(Method::GET, "http://localhost/api")).await.unwrap();
+        assert_eq!(response.text().unwrap(), r#"{"status":"ok"}"#);
+        assert!(!response.headers.contains_key(&header::CONTENT_ENCODING));
+        assert!(!response.headers.contains_key(&header::CONTENT_LENGTH));
+    }
+
+    #[tokio::test]
+    async fn download_to_file() {
+        let transport = Arc::new(MockTransport::new());
+        transport.respond(200, "application/octet-stream", "content");
+        let client = HttpClient::with_transport(
+            Arc::<MockTransport>::clone(&transport),
+            RetryConfig { max_attempts: 1, interval: Duration::ZERO, max_interval: Duration::ZERO, retry_status: &[] },
+        );
+
+        let path = env::temp_dir().join(format!("download-{}.txt", process::id()));
+        let length =
+            client.download_to_file(HttpRequest::new(Method::GET, "http://localhost/file"), &path).await.unwrap();
+        assert_eq!(length, 7);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "content");
+        assert!(!path.with_extension("txt.tmp").exists());
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn reconnect_stops_on_invalid_response() {
+        let transport = Arc::new(MockTransport::new());
+        transport.respond(200, "text/event-stream", "retry: 0\nid: 1\ndata: hello\n\n");
+        transport.respond_json(200, r#"{"error":"not sse"}"#);
+        let client = HttpClient::with_transport(
+            Arc::<MockTransport>::clone(&transport),
+            RetryConfig { max_attempts: 1, interval: Duration::ZERO, max_interval: Duration::ZERO, retry_status: &[] },
+        );
+
+        let mut event_source =
+            client.sse_with_reconnect(HttpRequest::new(Method::GET, "http://localhost/events")).await.unwrap();
+        assert_eq!(event_source.next().await.unwrap().unwrap().data, "hello");
+        let error = event_source.next().await.unwrap().unwrap_err();
+        assert_eq!(error.code, Some(INVALID_SSE_RESPONSE));
+        assert!(event_source.reconnect.is_none());
+
+        let requests = transport.requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[1].headers.get("last-event-id").unwrap(), "1");
+        assert_eq!(requests[1].headers.get("accept").unwrap(), "text/event-stream");
+    }
+
+    #[test]
+    fn traceparent_from_action_id() {
+        let traceparent = traceparent(&trace_id("0123456789ABCDEF0123", &[]));
+        assert_eq!(traceparent.len(), 55);
+        assert!(traceparent.starts_with("00-0000000000000123456789abcdef0123-"));
+        assert!(traceparent.ends_with("-01"));
+    }
+}
@@ -0,0 +1,131 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
+
+use crate::exception::Exception;
+use crate::exception::Severity;
+use crate::warn;
+
+#[derive(Clone)]
+pub struct CircuitBreakerConfig {
+    // open circuit after consecutive failures (connection error or 5xx) to same host
+    pub failure_threshold: u32,
+    // reject requests while open, then let one probe request through (half open)
+    pub open_duration: Duration,
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        Self { failure_threshold: 5, open_duration: Duration::from_secs(30) }
+    }
+}
+
+pub(super) struct CircuitBreaker {
+    config: CircuitBreakerConfig,
+    hosts: Mutex<HashMap<String, HostState>>,
+}
+
+#[derive(Default)]
+struct HostState {
+    failures: u32,
+    opened_time: Option<Instant>,
+    probing: bool,
+}
+
+impl CircuitBreaker {
+    pub(super) fn new(config: CircuitBreakerConfig) -> Self {
+        Self { config, hosts: Mutex::new(HashMap::new()) }
+    }
+
+    pub(super) fn acquire(&self, host: &str) -> Result<(), Exception> {
+        if self.allow(host) {
+            Ok(())
+        } else {
+            Err(exception!(
+                format!("circuit breaker is open, host={host}"),
+                severity = Severity::Warn,
+                code = "CIRCUIT_BREAKER_OPEN"
+            ))
+        }
+    }
+
+    fn allow(&self, host: &str) -> bool {
+        let mut hosts = self.hosts.lock().unwrap();
+        let Some(state) = hosts.get_mut(host) else {
+            return true;
+        };
+        let Some(opened_time) = state.opened_time else {
+            return true;
+        };
+        // restart timer on each probe, in case probe request is cancelled and never reports back
+        if opened_time.elapsed() >= self.config.open_duration {
+            warn!(
+                error_code = "CIRCUIT_BREAKER_HALF_OPEN",
+                "circuit breaker half open, send probe request, host={host}"
+            );
+            state.opened_time = Some(Instant::now());
+            state.probing = true;
+            return true;
+        }
+        false
+    }
+
+    pub(super) fn success(&self, host: &str) {
+        let mut hosts = self.hosts.lock().unwrap();
+        if let Some(state) = hosts.remove(host)
+            && state.opened_time.is_some()
+        {
+            warn!(error_code = "CIRCUIT_BREAKER_CLOSED", "circuit breaker closed, host={host}");
+        }
+    }
+
+    pub(super) fn failure(&self, host: &str) {
+        let mut hosts = self.hosts.lock().unwrap();
+        let state = hosts.entry(host.to_owned()).or_default();
+        state.failures += 1;
+        if state.probing || (state.opened_time.is_none() && state.failures >= self.config.failure_threshold) {
+            warn!(
+                error_code = "CIRCUIT_BREAKER_OPEN",
+                "circuit breaker opened, host={host}, failures={}", state.failures
+            );
+            state.opened_time = Some(Instant::now());
+            state.probing = false;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+    use std::time::Duration;
+
+    use super::CircuitBreaker;
+    use super::CircuitBreakerConfig;
+
+    #[test]
+    fn open_and_close() {
+        let open_duration = Duration::from_millis(50);
+        let breaker = CircuitBreaker::new(CircuitBreakerConfig { failure_threshold: 2, open_duration });
+        let host = "localhost:9200";
+
+        breaker.failure(host);
+        assert!(breaker.allow(host));
+        breaker.failure(host);
+        assert!(!breaker.allow(host));
+
+        // half open, only one probe is allowed
+        thread::sleep(open_duration);
+        assert!(breaker.allow(host));
+        assert!(!breaker.allow(host));
+
+        // failed probe opens circuit again
+        breaker.failure(host);
+        assert!(!breaker.allow(host));
+        thread::sleep(open_duration);
+        assert!(breaker.allow(host));
+        breaker.success(host);
+        assert!(breaker.allow(host));
+        assert!(breaker.allow(host));
+    }
+}
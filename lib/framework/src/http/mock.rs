@@ -0,0 +1,85 @@
+use std::collections::VecDeque;
+use std::future;
+use std::future::Future;
+use std::mem;
+use std::pin::Pin;
+use std::sync::Mutex;
+
+use bytes::Bytes;
+use http::HeaderMap;
+use http::HeaderValue;
+use http::StatusCode;
+use http::header;
+use reqwest::Method;
+use reqwest::Request;
+use reqwest::Response;
+
+use crate::http::HttpTransport;
+
+// records requests and replies with canned responses in order, for unit tests without live server
+#[derive(Default)]
+pub struct MockTransport {
+    responses: Mutex<VecDeque<MockResponse>>,
+    requests: Mutex<Vec<MockRequest>>,
+}
+
+struct MockResponse {
+    status: u16,
+    headers: HeaderMap,
+    body: Bytes,
+}
+
+#[derive(Debug)]
+pub struct MockRequest {
+    pub method: Method,
+    pub url: String,
+    pub headers: HeaderMap,
+    pub body: Option<Bytes>,
+}
+
+impl MockTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn respond(&self, status: u16, content_type: &'static str, body: impl Into<Bytes>) {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(content_type));
+        self.respond_with_headers(status, headers, body);
+    }
+
+    pub fn respond_with_headers(&self, status: u16, headers: HeaderMap, body: impl Into<Bytes>) {
+        self.responses.lock().unwrap().push_back(MockResponse { status, headers, body: body.into() });
+    }
+
+    pub fn respond_json(&self, status: u16, body: impl Into<Bytes>) {
+        self.respond(status, "application/json", body);
+    }
+
+    pub fn requests(&self) -> Vec<MockRequest> {
+        mem::take(&mut *self.requests.lock().unwrap())
+    }
+}
+
+impl HttpTransport for MockTransport {
+    fn execute(&self, request: Request) -> Pin<Box<dyn Future<Output = Result<Response, reqwest::Error>> + Send>> {
+        let body = request.body().and_then(|body| body.as_bytes()).map(Bytes::copy_from_slice);
+        self.requests.lock().unwrap().push(MockRequest {
+            method: request.method().clone(),
+            url: request.url().to_string(),
+            headers: request.headers().clone(),
+            body,
+        });
+
+        let response = self
+            .responses
+            .lock()
+            .unwrap()
+            .pop_front()
+            .unwrap_or_else(|| panic!("no mock response left, method={}, url={}", request.method(), request.url()));
+        let mut http_response = http::Response::new(response.body);
+        *http_response.status_mut() = StatusCode::from_u16(response.status).expect("status must be valid");
+        *http_response.headers_mut() = response.headers;
+        Box::pin(future::ready(Ok(Response::from(http_response))))
+    }
+}
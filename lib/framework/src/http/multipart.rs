@@ -0,0 +1,122 @@
+use std::fmt;
+use std::fmt::Debug;
+use std::fmt::Formatter;
+
+use bytes::BufMut as _;
+use bytes::Bytes;
+use bytes::BytesMut;
+
+// multipart/form-data body per RFC 7578, encoded on each attempt so retries resend the same content
+pub struct Multipart {
+    pub(super) boundary: String,
+    pub(super) parts: Vec<Part>,
+}
+
+pub(super) struct Part {
+    name: String,
+    file_name: Option<String>,
+    content_type: Option<&'static str>,
+    content: Bytes,
+}
+
+impl Debug for Part {
+    // content could be large binary, only log its size
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "part, name={}", self.name)?;
+        if let Some(ref file_name) = self.file_name {
+            write!(f, ", file_name={file_name}")?;
+        }
+        if let Some(content_type) = self.content_type {
+            write!(f, ", content_type={content_type}")?;
+        }
+        write!(f, ", length={}", self.content.len())
+    }
+}
+
+impl Multipart {
+    pub fn new() -> Self {
+        Self { boundary: format!("{:016x}{:016x}", rand::random::<u64>(), rand::random::<u64>()), parts: vec![] }
+    }
+
+    pub fn text(&mut self, name: impl Into<String>, value: impl Into<String>) {
+        self.parts.push(Part { name: name.into(), file_name: None, content_type: None, content: value.into().into() });
+    }
+
+    pub fn file(
+        &mut self,
+        name: impl Into<String>,
+        file_name: impl Into<String>,
+        content_type: &'static str,
+        content: impl Into<Bytes>,
+    ) {
+        self.parts.push(Part {
+            name: name.into(),
+            file_name: Some(file_name.into()),
+            content_type: Some(content_type),
+            content: content.into(),
+        });
+    }
+
+    pub(super) fn encode(&self) -> Bytes {
+        let boundary = &self.boundary;
+        let mut body = BytesMut::with_capacity(self.parts.iter().map(|part| part.content.len() + 128).sum());
+        for part in &self.parts {
+            body.put_slice(format!("--{boundary}\r\n").as_bytes());
+            body.put_slice(b"Content-Disposition: form-data; name=\"");
+            body.put_slice(escape(&part.name).as_bytes());
+            body.put_u8(b'"');
+            if let Some(ref file_name) = part.file_name {
+                body.put_slice(b"; filename=\"");
+                body.put_slice(escape(file_name).as_bytes());
+                body.put_u8(b'"');
+            }
+            body.put_slice(b"\r\n");
+            if let Some(content_type) = part.content_type {
+                body.put_slice(format!("Content-Type: {content_type}\r\n").as_bytes());
+            }
+            body.put_slice(b"\r\n");
+            body.put_slice(&part.content);
+            body.put_slice(b"\r\n");
+        }
+        body.put_slice(format!("--{boundary}--\r\n").as_bytes());
+        body.freeze()
+    }
+}
+
+impl Default for Multipart {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// percent-encode quote and line breaks in name/filename, same as browsers do
+fn escape(value: &str) -> String {
+    value.replace('"', "%22").replace('\r', "%0D").replace('\n', "%0A")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Multipart;
+
+    #[test]
+    fn encode_text_and_file() {
+        let mut multipart = Multipart::new();
+        multipart.boundary = "boundary".to_owned();
+        multipart.text("name", "value");
+        multipart.file("file", "a\"b.txt", "text/plain", "content");
+
+        assert_eq!(
+            multipart.encode(),
+            "--boundary\r\n\
+             Content-Disposition: form-data; name=\"name\"\r\n\
+             \r\n\
+             value\r\n\
+             --boundary\r\n\
+             Content-Disposition: form-data; name=\"file\"; filename=\"a%22b.txt\"\r\n\
+             Content-Type: text/plain\r\n\
+             \r\n\
+             content\r\n\
+             --boundary--\r\n"
+        );
+    }
+}
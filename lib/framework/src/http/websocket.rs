@@ -0,0 +1,135 @@
+use std::pin::Pin;
+use std::task::Context;
+use std::task::Poll;
+use std::time::Instant;
+
+use bytes::Bytes;
+use futures::Sink;
+use futures::Stream;
+use tokio::net::TcpStream;
+use tokio_tungstenite::MaybeTlsStream;
+use tokio_tungstenite::WebSocketStream;
+use tokio_tungstenite::tungstenite;
+
+use crate::exception::Exception;
+use crate::log;
+use crate::span;
+use crate::stats;
+
+#[derive(Debug)]
+pub enum Message {
+    Text(String),
+    Binary(Bytes),
+}
+
+// ping/pong and close frames are handled by underlying protocol, only data frames are surfaced
+pub struct WebSocketClient {
+    stream: WebSocketStream<MaybeTlsStream<TcpStream>>,
+
+    start_time: Instant,
+    read_bytes: usize,
+    read_entries: usize,
+    write_bytes: usize,
+    write_entries: usize,
+}
+
+impl WebSocketClient {
+    pub async fn connect(url: &str) -> Result<Self, Exception> {
+        let _span = span!("ws");
+        log!("[request] url={url}");
+        let (stream, response) = tokio_tungstenite::connect_async(url).await.map_err(|err| {
+            exception!(format!("failed to connect websocket, url={url}"), code = "HTTP_REQUEST_FAILED", source = err)
+        })?;
+        log!("[response] status={}", response.status().as_u16());
+        Ok(WebSocketClient {
+            stream,
+            start_time: Instant::now(),
+            read_bytes: 0,
+            read_entries: 0,
+            write_bytes: 0,
+            write_entries: 0,
+        })
+    }
+}
+
+impl Drop for WebSocketClient {
+    fn drop(&mut self) {
+        stats!(
+            ws_read_entries = self.read_entries,
+            ws_read_bytes = self.read_bytes,
+            ws_write_entries = self.write_entries,
+            ws_write_bytes = self.write_bytes,
+            ws_elapsed = self.start_time.elapsed().as_nanos(),
+            ws_count = 1
+        );
+    }
+}
+
+impl Stream for WebSocketClient {
+    type Item = Result<Message, Exception>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            match Pin::new(&mut self.stream).poll_next(cx) {
+                Poll::Ready(Some(Ok(tungstenite::Message::Text(text)))) => {
+                    log!("[ws] read, text={}", text.as_str());
+                    self.read_bytes += text.len();
+                    self.read_entries += 1;
+                    return Poll::Ready(Some(Ok(Message::Text(text.as_str().to_owned()))));
+                }
+                Poll::Ready(Some(Ok(tungstenite::Message::Binary(bytes)))) => {
+                    log!("[ws] read, binary_length={}", bytes.len());
+                    self.read_bytes += bytes.len();
+                    self.read_entries += 1;
+                    return Poll::Ready(Some(Ok(Message::Binary(bytes))));
+                }
+                Poll::Ready(Some(Ok(
+                    tungstenite::Message::Ping(_)
+                    | tungstenite::Message::Pong(_)
+                    | tungstenite::Message::Close(_)
+                    | tungstenite::Message::Frame(_),
+                ))) => {}
+                Poll::Ready(Some(Err(err))) => return Poll::Ready(Some(Err(websocket_error(err)))),
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+impl Sink<Message> for WebSocketClient {
+    type Error = Exception;
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.stream).poll_ready(cx).map_err(websocket_error)
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, message: Message) -> Result<(), Self::Error> {
+        let message = match message {
+            Message::Text(text) => {
+                log!("[ws] write, text={text}");
+                self.write_bytes += text.len();
+                tungstenite::Message::Text(text.into())
+            }
+            Message::Binary(bytes) => {
+                log!("[ws] write, binary_length={}", bytes.len());
+                self.write_bytes += bytes.len();
+                tungstenite::Message::Binary(bytes)
+            }
+        };
+        self.write_entries += 1;
+        Pin::new(&mut self.stream).start_send(message).map_err(websocket_error)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.stream).poll_flush(cx).map_err(websocket_error)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.stream).poll_close(cx).map_err(websocket_error)
+    }
+}
+
+fn websocket_error(err: tungstenite::Error) -> Exception {
+    exception!("websocket failed", code = "HTTP_REQUEST_FAILED", source = err)
+}
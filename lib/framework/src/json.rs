@@ -12,6 +12,16 @@ where
     serde_json::from_str(json).map_err(|err| exception!(format!("failed to deserialize, json={json}"), source = err))
 }
 
+// deserializes from bytes directly, without utf-8 conversion
+pub fn from_json_slice<'a, T>(json: &'a [u8]) -> Result<T, Exception>
+where
+    T: Deserialize<'a>,
+{
+    serde_json::from_slice(json).map_err(|err| {
+        exception!(format!("failed to deserialize, json={}", String::from_utf8_lossy(json)), source = err)
+    })
+}
+
 pub fn to_json<T>(object: &T) -> Result<String, Exception>
 where
     T: Serialize + Debug,
@@ -11,6 +11,7 @@ pub mod log;
 pub mod network;
 pub mod number;
 pub mod pool;
+pub mod retry;
 pub mod schedule;
 pub mod shell;
 pub mod string;
@@ -1,22 +1,33 @@
-use std::borrow::Cow;
 use std::cell::RefCell;
+use std::panic::AssertUnwindSafe;
 use std::sync::OnceLock;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
 use std::time::Instant;
 
 pub use chrono::SecondsFormat;
 pub use chrono::Utc;
+use futures::FutureExt as _;
 use tokio::task_local;
 
 use crate::exception::Exception;
 use crate::exception::Severity;
 use crate::log::action::Action;
+use crate::log::appender::ActionLogAppender;
 use crate::log::appender::Appender;
+use crate::log::appender::file::FileAppender;
+use crate::log::appender::file::FileAppenderConfig;
 use crate::write_str;
 
 mod action;
+pub mod active;
 pub mod appender;
 pub mod id_generator;
+pub mod mask;
 pub mod metrics;
+mod panic;
+pub(crate) mod prometheus;
 
 // used for logging without action context
 #[macro_export]
@@ -35,13 +46,99 @@ pub fn init(appender: &str, app: &'static str) {
     let appender = match appender {
         "console" => Appender::Console,
         "gcloud" => Appender::GoogleCloud,
+        "json" => Appender::JsonConsole,
+        // writes to ./log with default rotation, use init_with_appender() to customize
+        "file" => Appender::Custom(Box::new(FileAppender::new(FileAppenderConfig::default()))),
         _ => panic!("unknown appender, value={appender}"),
     };
+    panic::install_hook();
 
     CONTEXT.set(Context { app, appender }).unwrap_or_else(|_| panic!("init can only be called once"));
 }
 
+// e.g. log::init_with_appender(KafkaAppender::new(producer), app), to forward actions to log_processor
+pub fn init_with_appender(appender: impl ActionLogAppender + 'static, app: &'static str) {
+    console!("init log appender, appender=custom");
+    panic::install_hook();
+    CONTEXT
+        .set(Context { app, appender: Appender::Custom(Box::new(appender)) })
+        .unwrap_or_else(|_| panic!("init can only be called once"));
+}
+
 static CONTEXT: OnceLock<Context> = OnceLock::new();
+static SLOW_ACTION_THRESHOLDS: OnceLock<Vec<(&'static str, Duration)>> = OnceLock::new();
+static TRACE_SAMPLING: OnceLock<TraceSampling> = OnceLock::new();
+
+// traces are always kept for actions with warning or error, sampling keeps traces of ok actions for performance investigation
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TraceSampling {
+    // 0.0 to 1.0, e.g. 0.01 keeps trace of 1% ok actions
+    pub rate: f64,
+    // always keep trace if action is slower than threshold
+    pub slow_threshold: Option<Duration>,
+}
+
+impl TraceSampling {
+    fn keep(&self, elapsed: Duration) -> bool {
+        self.slow_threshold.is_some_and(|threshold| elapsed >= threshold)
+            || (self.rate > 0.0 && rand::random::<f64>() < self.rate)
+    }
+}
+
+pub fn set_trace_sampling(sampling: TraceSampling) {
+    TRACE_SAMPLING.set(sampling).unwrap_or_else(|_| panic!("trace sampling can only be set once"));
+}
+
+pub(crate) fn keep_trace(elapsed: Duration) -> bool {
+    TRACE_SAMPLING.get().is_some_and(|sampling| sampling.keep(elapsed))
+}
+
+// key is action kind, e.g. "http", "message" or "job", slow actions are marked as WARN with error_code SLOW_ACTION and trace is kept
+pub fn set_slow_action_thresholds(thresholds: Vec<(&'static str, Duration)>) {
+    SLOW_ACTION_THRESHOLDS.set(thresholds).unwrap_or_else(|_| panic!("slow action thresholds can only be set once"));
+}
+
+pub(crate) fn slow_action_threshold(kind: &str) -> Option<Duration> {
+    let thresholds = SLOW_ACTION_THRESHOLDS.get()?;
+    thresholds.iter().find(|(action_kind, _)| *action_kind == kind).map(|(_, threshold)| *threshold)
+}
+
+static ERROR_TO_STDERR: AtomicBool = AtomicBool::new(false);
+
+// console and json appenders print WARN/ERROR actions and metrics to stderr, others to stdout,
+// e.g. for container log collectors classifying severity by stream
+pub fn set_error_to_stderr(enabled: bool) {
+    ERROR_TO_STDERR.store(enabled, Ordering::Relaxed);
+}
+
+pub(crate) fn error_to_stderr() -> bool {
+    ERROR_TO_STDERR.load(Ordering::Relaxed)
+}
+
+static TRACE_LIMIT: OnceLock<TraceLimit> = OnceLock::new();
+
+// when exceeded, middle lines are replaced with "...N lines truncated..." marker, head and tail lines are kept
+#[derive(Debug, Clone, Copy)]
+pub struct TraceLimit {
+    pub max_lines: usize,
+    // total length of lines in bytes
+    pub max_length: usize,
+}
+
+impl Default for TraceLimit {
+    fn default() -> Self {
+        // keep trace under kafka default max message size
+        Self { max_lines: 2000, max_length: 900_000 }
+    }
+}
+
+pub fn set_trace_limit(limit: TraceLimit) {
+    TRACE_LIMIT.set(limit).unwrap_or_else(|_| panic!("trace limit can only be set once"));
+}
+
+pub(crate) fn trace_limit() -> TraceLimit {
+    TRACE_LIMIT.get().copied().unwrap_or_default()
+}
 
 struct Context {
     app: &'static str,
@@ -61,18 +158,28 @@ where
         let now = Utc::now();
         let id = id_generator::next_id(now.timestamp_millis());
         let action = Action::new(id, kind, ref_id, now);
+        let active_action = active::track(&action);
         CURRENT_ACTION
             .scope(RefCell::new(action), async move {
-                let result = task.await;
+                let _active_action = active_action;
+                // panic fails the action with error_code PANIC, instead of unwinding through handler or task
+                let result = AssertUnwindSafe(task).catch_unwind().await;
                 CURRENT_ACTION.with(|current_action| {
                     let mut current_action = current_action.borrow_mut();
-                    if let Err(e) = &result {
+                    let action_result = result.unwrap_or_else(|payload| {
+                        if let Some(backtrace) = panic::take_backtrace() {
+                            current_action.log(&backtrace, concat!(module_path!(), ":", line!()));
+                        }
+                        Err(panic::panic_exception(payload.as_ref()))
+                    });
+                    if let Err(e) = &action_result {
                         current_action.log_exception(e);
                     }
                     current_action.finish();
+                    prometheus::record_action(&current_action);
                     appender.append_action(&current_action, app);
-                });
-                result
+                    action_result
+                })
             })
             .await
     } else {
@@ -87,7 +194,8 @@ where
 pub struct Span {
     name: &'static str,
     start_time: Instant,
-    log_index: usize,
+    log_count: usize,
+    index: Option<usize>,
 }
 
 #[macro_export]
@@ -100,25 +208,21 @@ macro_rules! span {
 #[doc(hidden)]
 #[inline]
 pub fn __span(name: &'static str, location: &'static str) -> Span {
-    let mut log_index: usize = 0;
+    let mut log_count: usize = 0;
+    let mut index = None;
     let _result = CURRENT_ACTION.try_with(|action| {
         let mut action = action.borrow_mut();
         action.log(&format!("[span:{name}] >"), location);
-        log_index = action.logs.len();
+        log_count = action.log_count;
+        index = action.start_span(name);
     });
-    Span { name, start_time: Instant::now(), log_index }
+    Span { name, start_time: Instant::now(), log_count, index }
 }
 
 impl Span {
     pub fn clear(&self) {
         let _result = CURRENT_ACTION.try_with(|action| {
-            let mut action = action.borrow_mut();
-            action.logs.truncate(self.log_index);
-            if let Some(last) = action.logs.last_mut()
-                && last.ends_with('>')
-            {
-                last.push_str(" ...(truncated)");
-            }
+            action.borrow_mut().clear_logs(self.log_count);
         });
     }
 }
@@ -134,12 +238,11 @@ impl Drop for Span {
             let (minutes, seconds, nanos) = elapsed(action.start_time);
             let mut log = String::with_capacity(256);
             write_str!(log, "{minutes:02}:{seconds:02}.{nanos:09} [span:{name}] elapsed={span_elapsed:?} <");
-            action.logs.push(log);
-
-            let total_elapsed = action.stats.entry(Cow::Owned(format!("{name}_elapsed"))).or_default();
-            *total_elapsed += span_elapsed.as_nanos() as u64;
-            let count = action.stats.entry(Cow::Owned(format!("{name}_count"))).or_default();
-            *count += 1;
+            action.add_log(log);
+            if let Some(index) = self.index {
+                action.end_span(index, span_elapsed);
+            }
+            action.add_perf_stat(name, span_elapsed);
         });
     }
 }
@@ -213,6 +316,71 @@ pub fn current_action_id() -> Option<String> {
     CURRENT_ACTION.try_with(|action| Some(action.borrow().id.to_string())).unwrap_or(None)
 }
 
+// from incoming correlation id stored in context, otherwise current action id, as the action starts the flow
+pub fn current_correlation_id() -> Option<String> {
+    CURRENT_ACTION
+        .try_with(|action| {
+            let action = action.borrow();
+            Some(correlation_id(&action.context).map_or_else(|| action.id.to_string(), str::to_owned))
+        })
+        .unwrap_or(None)
+}
+
+// context!(correlation_id = ...) is set by http server, kafka consumer and spawned actions,
+// with multiple values (e.g. bulk message handler), current action starts new flow
+fn correlation_id<'a>(context: &'a [(&'static str, Vec<String>)]) -> Option<&'a str> {
+    context
+        .iter()
+        .find(|(key, values)| *key == "correlation_id" && values.len() == 1)
+        .and_then(|(_, values)| values.first())
+        .map(String::as_str)
+}
+
+// w3c trace context, from incoming traceparent stored in context, otherwise derived from action id
+pub fn current_trace_id() -> Option<String> {
+    CURRENT_ACTION
+        .try_with(|action| {
+            let action = action.borrow();
+            Some(trace_id(&action.id.to_string(), &action.context))
+        })
+        .unwrap_or(None)
+}
+
+pub fn current_traceparent() -> Option<String> {
+    current_trace_id().map(|trace_id| traceparent(&trace_id))
+}
+
+// returns trace id if valid, e.g. 00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01
+pub fn parse_traceparent(value: &str) -> Option<&str> {
+    let is_hex = |field: &str, len: usize| {
+        field.len() == len && field.bytes().all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+    };
+    let mut parts = value.split('-');
+    let (Some(version), Some(trace_id), Some(parent_id), Some(flags)) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return None;
+    };
+    // future versions may append fields
+    let valid_version = is_hex(version, 2) && version != "ff" && (version != "00" || parts.next().is_none());
+    let valid_trace_id = is_hex(trace_id, 32) && trace_id.bytes().any(|b| b != b'0');
+    (valid_version && valid_trace_id && is_hex(parent_id, 16) && is_hex(flags, 2)).then_some(trace_id)
+}
+
+// context!(trace_id = ...) is set by http server and kafka consumer from incoming traceparent
+pub(crate) fn trace_id(action_id: &str, context: &[(&'static str, Vec<String>)]) -> String {
+    context
+        .iter()
+        .find(|(key, values)| *key == "trace_id" && values.len() == 1)
+        .and_then(|(_, values)| values.first().cloned())
+        .unwrap_or_else(|| format!("{:0>32}", action_id.to_ascii_lowercase()))
+}
+
+// version-trace_id-parent_id-flags, parent id is random as spans are not propagated
+pub(crate) fn traceparent(trace_id: &str) -> String {
+    format!("00-{trace_id}-{:016x}-01", rand::random::<u64>())
+}
+
 #[macro_export]
 macro_rules! context {
     ($($key:ident = $value:expr),+ $(,)?) => {
@@ -266,12 +434,15 @@ pub fn __context(key: &'static str, values: Vec<String>, location: &'static str)
         if values.len() == 1
             && let Some(value) = values.first()
         {
+            if prometheus::NAME_CONTEXT_KEYS.contains(&key) {
+                active::update_name(&action, value);
+            }
             action.log(&format!("[content] {key}={value}"), location);
         } else {
             action.log(&format!("[content] {key}={values:?}"), location);
         }
 
-        action.context.push((key, values));
+        action.add_context(key, values);
     });
 }
 
@@ -295,9 +466,7 @@ pub fn __stats(key: &'static str, value: u64, location: &'static str) {
         let mut action = action.borrow_mut();
 
         action.log(&format!("[stats] {key}={value}"), location);
-
-        let stats_value = action.stats.entry(Cow::Borrowed(key)).or_default();
-        *stats_value += value;
+        action.add_stats(key, value);
     });
 }
 
@@ -329,8 +498,57 @@ fn elapsed(start: Instant) -> (u64, u64, u32) {
 
 #[cfg(test)]
 mod tests {
+    use std::time::Duration;
+
+    use crate::log::TraceSampling;
+    use crate::log::correlation_id;
+    use crate::log::parse_traceparent;
+    use crate::log::trace_id;
     use crate::log::truncate;
 
+    #[test]
+    fn parse_w3c_traceparent() {
+        assert_eq!(
+            parse_traceparent("00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01"),
+            Some("0af7651916cd43dd8448eb211c80319c")
+        );
+        assert_eq!(
+            parse_traceparent("01-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01-future"),
+            Some("0af7651916cd43dd8448eb211c80319c")
+        );
+        assert_eq!(parse_traceparent("00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01-future"), None);
+        assert_eq!(parse_traceparent("00-00000000000000000000000000000000-b7ad6b7169203331-01"), None);
+        assert_eq!(parse_traceparent("00-0AF7651916CD43DD8448EB211C80319C-b7ad6b7169203331-01"), None);
+        assert_eq!(parse_traceparent("ff-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01"), None);
+        assert_eq!(parse_traceparent("invalid"), None);
+    }
+
+    #[test]
+    fn trace_id_from_context() {
+        assert_eq!(trace_id("0123456789ABCDEF0123", &[]), "0000000000000123456789abcdef0123");
+        let context = vec![("trace_id", vec!["0af7651916cd43dd8448eb211c80319c".to_owned()])];
+        assert_eq!(trace_id("0123456789ABCDEF0123", &context), "0af7651916cd43dd8448eb211c80319c");
+    }
+
+    #[test]
+    fn correlation_id_from_context() {
+        assert_eq!(correlation_id(&[]), None);
+        let single = vec![("correlation_id", vec!["id1".to_owned()])];
+        assert_eq!(correlation_id(&single), Some("id1"));
+        let multiple = vec![("correlation_id", vec!["id1".to_owned(), "id2".to_owned()])];
+        assert_eq!(correlation_id(&multiple), None);
+    }
+
+    #[test]
+    fn trace_sampling() {
+        assert!(!TraceSampling::default().keep(Duration::from_secs(10)));
+        assert!(TraceSampling { rate: 1.0, slow_threshold: None }.keep(Duration::ZERO));
+
+        let slow = TraceSampling { rate: 0.0, slow_threshold: Some(Duration::from_secs(1)) };
+        assert!(!slow.keep(Duration::from_millis(999)));
+        assert!(slow.keep(Duration::from_secs(1)));
+    }
+
     #[test]
     fn truncate_with_unicode() {
         let value = "123老虎456".to_owned();
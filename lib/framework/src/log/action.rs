@@ -1,6 +1,7 @@
 use std::borrow::Cow;
 use std::collections::HashMap;
 use std::thread;
+use std::time::Duration;
 use std::time::Instant;
 
 use chrono::DateTime;
@@ -9,8 +10,15 @@ use chrono::Utc;
 
 use crate::exception::Exception;
 use crate::exception::Severity;
+use crate::log::TraceLimit;
+use crate::log::appender::PerformanceStat;
+use crate::log::appender::SpanLog;
 use crate::log::elapsed;
 use crate::log::id_generator::LogId;
+use crate::log::keep_trace;
+use crate::log::mask::mask;
+use crate::log::slow_action_threshold;
+use crate::log::trace_limit;
 use crate::log::truncate;
 use crate::network::hostname;
 use crate::write_str;
@@ -24,7 +32,19 @@ pub(crate) struct Action {
     pub(crate) error: Option<Error>,
     pub(crate) context: Vec<(&'static str, Vec<String>)>,
     pub(crate) stats: HashMap<Cow<'static, str>, u64>,
+    pub(crate) perf_stats: HashMap<&'static str, PerformanceStat>,
     pub(crate) logs: Vec<String>,
+    // total length of logs
+    log_length: usize,
+    // number of lines ever logged, span uses it to clear own lines
+    pub(crate) log_count: usize,
+    // (index, lines) of "...N lines truncated..." marker within logs
+    truncated: Option<(usize, usize)>,
+    // decided on finish
+    trace: bool,
+    pub(crate) spans: Vec<SpanLog>,
+    // indexes of spans not finished yet, last one is parent of new span
+    open_spans: Vec<usize>,
 }
 
 pub struct Error {
@@ -44,12 +64,19 @@ impl Action {
             error: None,
             context: Vec::new(),
             stats: HashMap::new(),
+            perf_stats: HashMap::new(),
             logs: Vec::with_capacity(32),
+            log_length: 0,
+            log_count: 0,
+            truncated: None,
+            trace: false,
+            spans: vec![],
+            open_spans: vec![],
         };
 
         let date_string = action.date.to_rfc3339_opts(SecondsFormat::Nanos, true);
 
-        action.logs.push(format!(
+        action.add_log(format!(
             "# [action] id={}, date={date_string}, kind={kind}\nthread={:?}\nhost={}\nref_id={:?}",
             action.id,
             thread::current().id(),
@@ -61,19 +88,14 @@ impl Action {
     }
 
     pub(crate) const fn flush_trace(&self) -> bool {
-        self.error.is_some()
+        self.trace
     }
 
     pub(crate) fn log(&mut self, message: &str, location: &'static str) {
-        const MAX_LOGS: usize = 2000;
-        if self.logs.len() >= MAX_LOGS {
-            return;
-        }
-
         let mut log = String::with_capacity(256);
         let (minutes, seconds, nanos) = elapsed(self.start_time);
         write_str!(log, "{minutes:02}:{seconds:02}.{nanos:09} {location} {message}");
-        self.logs.push(log);
+        self.add_log(log);
     }
 
     pub(crate) fn log_with_severity(
@@ -83,11 +105,6 @@ impl Action {
         error_code: Option<&'static str>,
         location: &'static str,
     ) {
-        const MAX_LOGS: usize = 2000;
-        if self.logs.len() >= MAX_LOGS {
-            return;
-        }
-
         let mut log = String::with_capacity(256);
         let (minutes, seconds, nanos) = elapsed(self.start_time);
         write_str!(log, "{minutes:02}:{seconds:02}.{nanos:09} {location} ");
@@ -98,7 +115,7 @@ impl Action {
             write_str!(log, "[{error_code}] ");
         }
         write_str!(log, "{message}");
-        self.logs.push(log);
+        self.add_log(log);
 
         if let Some(severity) = severity {
             self.update_error(severity, error_code, message);
@@ -111,7 +128,7 @@ impl Action {
             self.error = Some(Error {
                 severity,
                 code: error_code,
-                message: truncate(error_message.to_owned(), MAX_ERROR_MESSAGE_LEN, None),
+                message: truncate(mask(error_message).into_owned(), MAX_ERROR_MESSAGE_LEN, None),
             });
         }
     }
@@ -128,7 +145,7 @@ impl Action {
             write_str!(log, "[{error_code}] ");
         }
         write_str!(log, "{}\n{}", exception.message, exception.backtrace());
-        self.logs.push(log);
+        self.add_log(log);
 
         self.update_error(exception.severity, exception.code, &exception.message);
     }
@@ -136,8 +153,289 @@ impl Action {
     pub(crate) fn finish(&mut self) {
         let elapsed = self.start_time.elapsed();
         self.stats.insert(Cow::Borrowed("elapsed"), elapsed.as_nanos() as u64);
-        if self.flush_trace() {
-            self.logs.push(format!("# [action] elapsed={elapsed:?}"));
+        self.warn_if_slow(elapsed, slow_action_threshold(self.kind));
+        self.trace = self.error.is_some() || keep_trace(elapsed);
+        if self.trace {
+            self.add_log(format!("# [action] elapsed={elapsed:?}"));
+            self.truncate_logs(trace_limit());
+            // masked only if trace is written, as most actions drop trace
+            for log in &mut self.logs {
+                if let Cow::Owned(masked) = mask(log) {
+                    *log = masked;
+                }
+            }
+        }
+    }
+
+    fn warn_if_slow(&mut self, elapsed: Duration, threshold: Option<Duration>) {
+        if let Some(threshold) = threshold
+            && elapsed > threshold
+        {
+            self.log_with_severity(
+                &format!("action is slow, elapsed={elapsed:?}, threshold={threshold:?}"),
+                Some(Severity::Warn),
+                Some("SLOW_ACTION"),
+                concat!(module_path!(), ":", line!()),
+            );
+        }
+    }
+
+    // stats of operation entries and bytes are recorded as perf stats, e.g. db_read_rows, kafka_write_messages or http_read_bytes
+    pub(crate) fn add_stats(&mut self, key: &'static str, value: u64) {
+        if let Some((operation, field)) = perf_stat_field(key) {
+            let stat = self.perf_stats.entry(operation).or_default();
+            *field(stat).get_or_insert(0) += value;
+        } else {
+            *self.stats.entry(Cow::Borrowed(key)).or_default() += value;
+        }
+    }
+
+    pub(crate) fn add_perf_stat(&mut self, operation: &'static str, elapsed: Duration) {
+        let stat = self.perf_stats.entry(operation).or_default();
+        stat.total_elapsed += elapsed.as_nanos() as u64;
+        stat.count += 1;
+    }
+
+    // values of same key are merged, same as java ActionLog.context,
+    // number of keys and values per key are capped to keep action log message bounded, e.g. context!() within loop
+    pub(crate) fn add_context(&mut self, key: &'static str, mut values: Vec<String>) {
+        const MAX_CONTEXT_KEYS: usize = 100;
+        const MAX_CONTEXT_VALUES: usize = 5_000;
+
+        if !self.context.iter().any(|(context_key, _)| *context_key == key) {
+            if self.context.len() >= MAX_CONTEXT_KEYS {
+                self.add_log(format!("too many context keys, key={key} is dropped"));
+                return;
+            }
+            self.context.push((key, Vec::with_capacity(values.len())));
+        }
+        let Some((_, existing)) = self.context.iter_mut().find(|(context_key, _)| *context_key == key) else {
+            return;
+        };
+        let remaining = MAX_CONTEXT_VALUES.saturating_sub(existing.len());
+        let dropped = values.len().saturating_sub(remaining);
+        values.truncate(remaining);
+        existing.append(&mut values);
+        if dropped > 0 {
+            self.add_log(format!("too many context values, key={key}, dropped={dropped}"));
+        }
+    }
+
+    // returns none if too many spans, e.g. span within loop
+    pub(crate) fn start_span(&mut self, name: &'static str) -> Option<usize> {
+        const MAX_SPANS: usize = 1000;
+        if self.spans.len() >= MAX_SPANS {
+            return None;
+        }
+        let index = self.spans.len();
+        self.spans.push(SpanLog {
+            name,
+            parent: self.open_spans.last().copied(),
+            start: self.start_time.elapsed(),
+            elapsed: Duration::ZERO,
+        });
+        self.open_spans.push(index);
+        Some(index)
+    }
+
+    pub(crate) fn end_span(&mut self, index: usize, elapsed: Duration) {
+        if let Some(span) = self.spans.get_mut(index) {
+            span.elapsed = elapsed;
+        }
+        // spans may end out of order if started by concurrent futures
+        if let Some(position) = self.open_spans.iter().rposition(|&open| open == index) {
+            self.open_spans.remove(position);
+        }
+    }
+
+    pub(crate) fn add_log(&mut self, log: String) {
+        self.log_length += log.len();
+        self.log_count += 1;
+        self.logs.push(log);
+        let limit = trace_limit();
+        // compact once logs reach twice of limit, to amortize cost of dropping middle lines
+        if self.logs.len() > limit.max_lines * 2 || self.log_length > limit.max_length * 2 {
+            self.truncate_logs(limit);
+        }
+    }
+
+    // removes lines logged after log_count, used by span
+    pub(crate) fn clear_logs(&mut self, log_count: usize) {
+        let removed = self.log_count.saturating_sub(log_count);
+        // lines before marker are kept, lines after marker are dropped oldest first, so span lines are always at the end
+        let min_len = self.truncated.map_or(0, |(index, _)| index + 1);
+        let len = self.logs.len().saturating_sub(removed).max(min_len);
+        for log in self.logs.drain(len..) {
+            self.log_length -= log.len();
         }
+        self.log_count = log_count;
+        if let Some(last) = self.logs.last_mut()
+            && last.ends_with('>')
+        {
+            last.push_str(" ...(truncated)");
+            self.log_length += " ...(truncated)".len();
+        }
+    }
+
+    // keeps head and tail lines, replaces middle lines with marker
+    fn truncate_logs(&mut self, limit: TraceLimit) {
+        let (start, truncated_lines) = self.truncated.unwrap_or_else(|| (self.head_lines(limit), 0));
+        // first truncation inserts marker line, later ones replace it
+        let mut lines = self.logs.len() + usize::from(self.truncated.is_none());
+        let mut length = self.log_length;
+        let mut end = if self.truncated.is_some() { start + 1 } else { start };
+        // always keep last line
+        for log in self.logs.iter().take(self.logs.len().saturating_sub(1)).skip(end) {
+            if lines <= limit.max_lines && length <= limit.max_length {
+                break;
+            }
+            lines -= 1;
+            length -= log.len();
+            end += 1;
+        }
+
+        let dropped = end - start - usize::from(self.truncated.is_some());
+        if dropped == 0 {
+            return;
+        }
+        let truncated_lines = truncated_lines + dropped;
+        self.logs.splice(start..end, [format!("...{truncated_lines} lines truncated...")]);
+        self.truncated = Some((start, truncated_lines));
+        self.log_length = self.logs.iter().map(String::len).sum();
+    }
+
+    fn head_lines(&self, limit: TraceLimit) -> usize {
+        let mut length = 0;
+        let mut lines = 0;
+        for log in self.logs.iter().take(limit.max_lines / 2) {
+            length += log.len();
+            if length > limit.max_length / 2 {
+                break;
+            }
+            lines += 1;
+        }
+        // always keep action header
+        lines.max(1)
+    }
+}
+
+type PerformanceStatField = fn(&mut PerformanceStat) -> &mut Option<u64>;
+
+fn perf_stat_field(key: &'static str) -> Option<(&'static str, PerformanceStatField)> {
+    const FIELDS: [(&str, PerformanceStatField); 8] = [
+        ("_read_entries", |stat| &mut stat.read_entries),
+        ("_read_rows", |stat| &mut stat.read_entries),
+        ("_read_messages", |stat| &mut stat.read_entries),
+        ("_write_entries", |stat| &mut stat.write_entries),
+        ("_write_rows", |stat| &mut stat.write_entries),
+        ("_write_messages", |stat| &mut stat.write_entries),
+        ("_read_bytes", |stat| &mut stat.read_bytes),
+        ("_write_bytes", |stat| &mut stat.write_bytes),
+    ];
+    FIELDS.iter().find_map(|(suffix, field)| key.strip_suffix(suffix).map(|operation| (operation, *field)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use chrono::Utc;
+
+    use super::Action;
+    use crate::exception::Severity;
+    use crate::log::TraceLimit;
+    use crate::log::appender::PerformanceStat;
+    use crate::log::id_generator::next_id;
+
+    #[test]
+    fn truncate_logs() {
+        let now = Utc::now();
+        let mut action = Action::new(next_id(now.timestamp_millis()), "test", None, now);
+        for i in 1..=20 {
+            action.add_log(format!("line-{i}"));
+        }
+        let limit = TraceLimit { max_lines: 6, max_length: 1_000_000 };
+        action.truncate_logs(limit);
+        assert_eq!(action.logs.len(), 6);
+        assert_eq!(action.logs[1..], ["line-1", "line-2", "...16 lines truncated...", "line-19", "line-20"]);
+
+        for i in 21..=25 {
+            action.add_log(format!("line-{i}"));
+        }
+        action.truncate_logs(limit);
+        assert_eq!(action.logs[1..], ["line-1", "line-2", "...21 lines truncated...", "line-24", "line-25"]);
+
+        // span lines are cleared up to marker
+        action.clear_logs(action.log_count - 3);
+        assert_eq!(action.logs[1..], ["line-1", "line-2", "...21 lines truncated..."]);
+        assert_eq!(action.log_length, action.logs.iter().map(String::len).sum::<usize>());
+    }
+
+    #[test]
+    fn mask_trace_on_finish() {
+        let now = Utc::now();
+        let mut action = Action::new(next_id(now.timestamp_millis()), "test", None, now);
+        action.log_with_severity("password=secret", Some(Severity::Error), None, "test");
+        assert!(action.logs[1].ends_with("password=secret"));
+        assert_eq!(action.error.as_ref().unwrap().message, "password=******");
+
+        action.finish();
+        assert!(action.logs[1].ends_with("password=******"));
+    }
+
+    #[test]
+    fn add_context() {
+        let now = Utc::now();
+        let mut action = Action::new(next_id(now.timestamp_millis()), "test", None, now);
+        action.add_context("key", vec!["v1".to_owned()]);
+        action.add_context("key", vec!["v2".to_owned(), "v3".to_owned()]);
+        assert_eq!(action.context, vec![("key", vec!["v1".to_owned(), "v2".to_owned(), "v3".to_owned()])]);
+
+        action.add_context("values", vec![String::new(); 6_000]);
+        assert_eq!(action.context[1].1.len(), 5_000);
+
+        for i in 0..200 {
+            action.add_context(format!("key-{i}").leak(), vec!["value".to_owned()]);
+        }
+        assert_eq!(action.context.len(), 100);
+    }
+
+    #[test]
+    fn add_stats() {
+        let now = Utc::now();
+        let mut action = Action::new(next_id(now.timestamp_millis()), "test", None, now);
+        action.add_perf_stat("db", Duration::from_nanos(100));
+        action.add_perf_stat("db", Duration::from_nanos(50));
+        action.add_stats("db_read_rows", 3);
+        action.add_stats("db_write_rows", 1);
+        action.add_stats("kafka_write_bytes", 10);
+        action.add_stats("kafka_poll_elapsed", 20);
+
+        assert_eq!(
+            action.perf_stats["db"],
+            PerformanceStat {
+                total_elapsed: 150,
+                count: 2,
+                read_entries: Some(3),
+                write_entries: Some(1),
+                ..PerformanceStat::default()
+            }
+        );
+        assert_eq!(action.perf_stats["kafka"].write_bytes, Some(10));
+        assert_eq!(action.stats["kafka_poll_elapsed"], 20);
+    }
+
+    #[test]
+    fn warn_if_slow() {
+        let now = Utc::now();
+        let mut action = Action::new(next_id(now.timestamp_millis()), "test", None, now);
+        action.warn_if_slow(Duration::from_millis(100), None);
+        action.warn_if_slow(Duration::from_millis(100), Some(Duration::from_millis(100)));
+        assert!(action.error.is_none());
+
+        action.warn_if_slow(Duration::from_millis(101), Some(Duration::from_millis(100)));
+        let error = action.error.as_ref().unwrap();
+        assert_eq!(error.severity, Severity::Warn);
+        assert_eq!(error.code, Some("SLOW_ACTION"));
     }
 }
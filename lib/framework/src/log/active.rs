@@ -0,0 +1,131 @@
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::time::Instant;
+
+use chrono::DateTime;
+use chrono::Utc;
+use serde::Serialize;
+
+use crate::log::action::Action;
+use crate::log::metrics::Metrics;
+
+// tracks actions not finished yet, e.g. to find stuck requests or jobs, exposed by web::diagnostic::routes()
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static REGISTRY: Mutex<BTreeMap<String, ActiveAction>> = Mutex::new(BTreeMap::new());
+
+struct ActiveAction {
+    kind: &'static str,
+    name: Option<String>,
+    date: DateTime<Utc>,
+    start_time: Instant,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ActiveActionView {
+    pub id: String,
+    pub kind: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub date: DateTime<Utc>,
+    // in nanoseconds
+    pub elapsed: u64,
+}
+
+pub(crate) fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+// removes action from registry on drop, including when action future is cancelled, e.g. by request timeout
+pub(crate) struct ActiveActionGuard(Option<String>);
+
+impl Drop for ActiveActionGuard {
+    fn drop(&mut self) {
+        if let Some(id) = self.0.take() {
+            REGISTRY.lock().unwrap().remove(&id);
+        }
+    }
+}
+
+pub(crate) fn track(action: &Action) -> ActiveActionGuard {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return ActiveActionGuard(None);
+    }
+    let id = action.id.to_string();
+    REGISTRY.lock().unwrap().insert(
+        id.clone(),
+        ActiveAction { kind: action.kind, name: None, date: action.date, start_time: action.start_time },
+    );
+    ActiveActionGuard(Some(id))
+}
+
+// name is from same context keys as prometheus, e.g. matched_path or job
+pub(crate) fn update_name(action: &Action, name: &str) {
+    if ENABLED.load(Ordering::Relaxed)
+        && let Some(active_action) = REGISTRY.lock().unwrap().get_mut(&action.id.to_string())
+        && active_action.name.is_none()
+    {
+        active_action.name = Some(name.to_owned());
+    }
+}
+
+// longest running first
+pub fn active_actions() -> Vec<ActiveActionView> {
+    let mut actions: Vec<ActiveActionView> = REGISTRY
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|(id, action)| ActiveActionView {
+            id: id.clone(),
+            kind: action.kind,
+            name: action.name.clone(),
+            date: action.date,
+            elapsed: action.start_time.elapsed().as_nanos() as u64,
+        })
+        .collect();
+    actions.sort_by_key(|action| Reverse(action.elapsed));
+    actions
+}
+
+// enables tracking, reports count and longest elapsed of active actions
+pub fn active_action_metrics() -> impl Fn(&mut Metrics) {
+    enable();
+    |metrics| {
+        let registry = REGISTRY.lock().unwrap();
+        let max_elapsed = registry.values().map(|action| action.start_time.elapsed().as_nanos() as u64).max();
+        metrics.stats.push(("active_actions", registry.len() as u64));
+        metrics.stats.push(("active_action_max_elapsed", max_elapsed.unwrap_or_default()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use super::active_actions;
+    use super::enable;
+    use super::track;
+    use super::update_name;
+    use crate::log::action::Action;
+    use crate::log::id_generator::next_id;
+
+    #[test]
+    fn track_active_action() {
+        enable();
+        let now = Utc::now();
+        let action = Action::new(next_id(now.timestamp_millis()), "job", None, now);
+        let id = action.id.to_string();
+
+        let guard = track(&action);
+        update_name(&action, "cleanup");
+        let active = active_actions();
+        let tracked = active.iter().find(|active_action| active_action.id == id).unwrap();
+        assert_eq!(tracked.kind, "job");
+        assert_eq!(tracked.name.as_deref(), Some("cleanup"));
+
+        drop(guard);
+        assert!(active_actions().iter().all(|active_action| active_action.id != id));
+    }
+}
@@ -1,7 +1,8 @@
 use std::any::Any;
 use std::borrow::Cow;
 use std::collections::HashMap;
-use std::io::Write as _;
+use std::io::Write;
+use std::io::stderr;
 use std::io::stdout;
 use std::time::Duration;
 
@@ -15,13 +16,116 @@ use crate::exception::Severity;
 use crate::json;
 use crate::log::Action;
 use crate::log::action::Error;
+use crate::log::error_to_stderr;
 use crate::log::metrics::Metrics;
 use crate::network::hostname;
 use crate::write_str;
 
+pub mod file;
+pub mod otlp;
+
 pub enum Appender {
     Console,
     GoogleCloud,
+    // one json line per action/metrics, for log collectors parsing stdout, e.g. fluent-bit
+    JsonConsole,
+    // actions and metrics are printed to console as well
+    Custom(Box<dyn ActionLogAppender>),
+}
+
+// e.g. file::FileAppender, or implemented outside framework, e.g. framework_kafka::appender::KafkaAppender
+pub trait ActionLogAppender: Send + Sync {
+    // called when action finishes, must not block or emit log
+    fn append(&self, action: &ActionLog<'_>);
+
+    // called by MetricsCollector periodically, must not block or emit log
+    fn append_metrics(&self, _metrics: &Metrics, _app: &'static str) {}
+}
+
+// e.g. log::init_with_appender(vec![Box::new(kafka_appender) as Box<dyn ActionLogAppender>, Box::new(otlp_appender)], app)
+impl ActionLogAppender for Vec<Box<dyn ActionLogAppender>> {
+    fn append(&self, action: &ActionLog<'_>) {
+        for appender in self {
+            appender.append(action);
+        }
+    }
+
+    fn append_metrics(&self, metrics: &Metrics, app: &'static str) {
+        for appender in self {
+            appender.append_metrics(metrics, app);
+        }
+    }
+}
+
+pub struct ActionLog<'a> {
+    pub id: String,
+    pub date: DateTime<Utc>,
+    pub app: &'static str,
+    pub host: &'static str,
+    pub kind: &'static str,
+    // none if action succeeded
+    pub severity: Option<Severity>,
+    pub ref_id: Option<&'a [String]>,
+    pub error_code: Option<&'static str>,
+    pub error_message: Option<&'a str>,
+    pub context: &'a [(&'static str, Vec<String>)],
+    pub stats: &'a HashMap<Cow<'static, str>, u64>,
+    // key is operation, e.g. http, db or kafka
+    pub perf_stats: &'a HashMap<&'static str, PerformanceStat>,
+    // only present if action has warning or error, or trace is sampled
+    pub trace: Option<&'a [String]>,
+    pub spans: &'a [SpanLog],
+}
+
+// created by span!()
+#[derive(Debug, Clone)]
+pub struct SpanLog {
+    pub name: &'static str,
+    // index of parent span, none if parent is action
+    pub parent: Option<usize>,
+    // offset from action start
+    pub start: Duration,
+    // zero if span was not finished within action
+    pub elapsed: Duration,
+}
+
+// elapsed and count are recorded by span!(), entries and bytes by stats!(), e.g. stats!(db_read_rows = 1)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct PerformanceStat {
+    // in nanoseconds
+    pub total_elapsed: u64,
+    pub count: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub read_entries: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub write_entries: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub read_bytes: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub write_bytes: Option<u64>,
+}
+
+impl PerformanceStat {
+    // e.g. db_elapsed, db_count and db_read_entries, elapsed and count are omitted if recorded without span!()
+    pub(crate) fn flatten(&self, operation: &str) -> Vec<(String, u64)> {
+        let mut stats = vec![];
+        if self.count > 0 {
+            stats.push((format!("{operation}_elapsed"), self.total_elapsed));
+            stats.push((format!("{operation}_count"), self.count));
+        }
+        let entries = [
+            ("read_entries", self.read_entries),
+            ("write_entries", self.write_entries),
+            ("read_bytes", self.read_bytes),
+            ("write_bytes", self.write_bytes),
+        ];
+        for (key, value) in entries {
+            if let Some(value) = value {
+                stats.push((format!("{operation}_{key}"), value));
+            }
+        }
+        stats
+    }
 }
 
 impl Appender {
@@ -30,18 +134,48 @@ impl Appender {
         match self {
             Appender::Console => append_console(action),
             Appender::GoogleCloud => append_gcloud(action, app),
+            Appender::Custom(appender) => {
+                append_console(action);
+                appender.append(&ActionLog::new(action, app));
+            }
+            Appender::JsonConsole => append_json_console(action, app),
         }
     }
 
     pub(crate) fn append_metrics(&self, metrics: &Metrics, app: &'static str) {
         match self {
             Appender::Console => append_metrics_console(metrics),
+            Appender::Custom(appender) => {
+                append_metrics_console(metrics);
+                appender.append_metrics(metrics, app);
+            }
             Appender::GoogleCloud => append_metrics_gcloud(metrics, app),
+            Appender::JsonConsole => append_metrics_json_console(metrics, app),
+        }
+    }
+}
+
+impl<'a> ActionLog<'a> {
+    fn new(action: &'a Action, app: &'static str) -> Self {
+        Self {
+            id: action.id.to_string(),
+            date: action.date,
+            app,
+            host: hostname(),
+            kind: action.kind,
+            severity: action.error.as_ref().map(|e| e.severity),
+            ref_id: action.ref_id.as_deref(),
+            error_code: action.error.as_ref().and_then(|e| e.code),
+            error_message: action.error.as_ref().map(|e| e.message.as_str()),
+            context: &action.context,
+            stats: &action.stats,
+            perf_stats: &action.perf_stats,
+            trace: action.flush_trace().then_some(action.logs.as_slice()),
+            spans: &action.spans,
         }
     }
 }
 
-#[allow(clippy::print_stdout, clippy::print_stderr)]
 fn append_console(action: &Action) {
     let date = action.date.to_rfc3339_opts(chrono::SecondsFormat::Millis, true);
     let severity = severity(action.error.as_ref());
@@ -84,17 +218,31 @@ fn append_console(action: &Action) {
         }
     }
 
-    println!("{log}");
+    for (operation, stat) in &action.perf_stats {
+        for (key, value) in stat.flatten(operation) {
+            if key.ends_with("elapsed") {
+                write_str!(&mut log, " | {key}={:?}", Duration::from_nanos(value));
+            } else {
+                write_str!(&mut log, " | {key}={value}");
+            }
+        }
+    }
+
+    let mut console = console(action.error.as_ref());
+    let _ = writeln!(console, "{log}");
 
     if action.flush_trace() {
-        let mut stdout = stdout().lock();
         for line in &action.logs {
-            let _ = writeln!(stdout, "{line}");
+            let _ = writeln!(console, "{line}");
         }
     }
 }
 
-#[allow(clippy::print_stdout)]
+// WARN/ERROR go to stderr if enabled by log::set_error_to_stderr()
+fn console(error: Option<&Error>) -> Box<dyn Write> {
+    if error.is_some() && error_to_stderr() { Box::new(stderr().lock()) } else { Box::new(stdout().lock()) }
+}
+
 fn append_metrics_console(metrics: &Metrics) {
     let date = metrics.date.to_rfc3339_opts(chrono::SecondsFormat::Millis, true);
     let mut log = format!("METRICS: {date}");
@@ -116,7 +264,7 @@ fn append_metrics_console(metrics: &Metrics) {
         write_str!(&mut log, " | {key}={value}");
     }
 
-    println!("{log}");
+    let _ = writeln!(console(metrics.error.as_ref()), "{log}");
 }
 
 #[allow(clippy::print_stdout)]
@@ -142,6 +290,7 @@ fn append_gcloud(action: &Action, app: &'static str) {
             error_message,
             context: action.context.as_ref(),
             stats: &action.stats,
+            perf_stats: &action.perf_stats,
             label: LogLabel { log: "action" },
             trace_id: id,
         })
@@ -170,6 +319,35 @@ fn append_gcloud(action: &Action, app: &'static str) {
     }
 }
 
+fn append_json_console(action: &Action, app: &'static str) {
+    let error = action.error.as_ref();
+    let action = ActionLog::new(action, app);
+    let _ = writeln!(
+        console(error),
+        "{}",
+        json::to_json(&JsonActionEntry::new(&action)).expect("serialize to json cannot fail")
+    );
+}
+
+fn append_metrics_json_console(metrics: &Metrics, app: &'static str) {
+    let _ = writeln!(
+        console(metrics.error.as_ref()),
+        "{}",
+        json::to_json(&JsonMetricsEntry {
+            id: metrics.id.to_string().as_str(),
+            date: metrics.date,
+            app,
+            host: hostname(),
+            severity: severity(metrics.error.as_ref()),
+            error_code: metrics.error.as_ref().and_then(|e| e.code),
+            error_message: metrics.error.as_ref().map(|e| e.message.as_str()),
+            stats: &metrics.stats,
+            info: &metrics.info,
+        })
+        .expect("serialize to json cannot fail")
+    );
+}
+
 #[allow(clippy::print_stdout)]
 fn append_metrics_gcloud(metrics: &Metrics, app: &'static str) {
     let error_code = metrics.error.as_ref().and_then(|e| e.code);
@@ -204,6 +382,70 @@ const fn severity(error: Option<&Error>) -> &'static str {
     }
 }
 
+// shared by json console and file appender, trace lines are only included if trace is flushed
+#[derive(Debug, Serialize)]
+pub(crate) struct JsonActionEntry<'a> {
+    id: &'a str,
+    date: DateTime<Utc>,
+    app: &'static str,
+    host: &'static str,
+    severity: &'static str,
+    kind: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ref_id: Option<&'a [String]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error_code: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error_message: Option<&'a str>,
+    #[serde(serialize_with = "serialize_key_value_tuple")]
+    context: &'a [(&'static str, Vec<String>)],
+    stats: &'a HashMap<Cow<'static, str>, u64>,
+    perf_stats: &'a HashMap<&'static str, PerformanceStat>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    trace: Option<&'a [String]>,
+}
+
+impl<'a> JsonActionEntry<'a> {
+    pub(crate) fn new(action: &'a ActionLog<'a>) -> Self {
+        Self {
+            id: &action.id,
+            date: action.date,
+            app: action.app,
+            host: action.host,
+            severity: match action.severity {
+                None => "INFO",
+                Some(Severity::Warn) => "WARN",
+                Some(Severity::Error) => "ERROR",
+            },
+            kind: action.kind,
+            ref_id: action.ref_id,
+            error_code: action.error_code,
+            error_message: action.error_message,
+            context: action.context,
+            stats: action.stats,
+            perf_stats: action.perf_stats,
+            trace: action.trace,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct JsonMetricsEntry<'a> {
+    id: &'a str,
+    date: DateTime<Utc>,
+    app: &'static str,
+    host: &'static str,
+    severity: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error_code: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error_message: Option<&'a str>,
+    #[serde(serialize_with = "serialize_key_value_tuple")]
+    stats: &'a [(&'static str, u64)],
+    #[serde(serialize_with = "serialize_key_value_tuple")]
+    info: &'a [(&'static str, String)],
+}
+
 #[derive(Debug, Serialize)]
 struct LogLabel {
     log: &'static str,
@@ -227,6 +469,7 @@ struct ActionEntry<'a> {
     context: &'a [(&'static str, Vec<String>)],
     #[serde(flatten)]
     stats: &'a HashMap<Cow<'static, str>, u64>,
+    perf_stats: &'a HashMap<&'static str, PerformanceStat>,
     #[serde(rename = "logging.googleapis.com/labels")]
     label: LogLabel,
     #[serde(rename = "logging.googleapis.com/trace")]
@@ -295,7 +538,11 @@ mod tests {
     use serde_json::Value;
 
     use super::ActionEntry;
+    use super::ActionLog;
+    use super::JsonActionEntry;
     use super::LogLabel;
+    use super::PerformanceStat;
+    use crate::exception::Severity;
     use crate::json;
 
     #[test]
@@ -316,6 +563,7 @@ mod tests {
             error_message: Some("invalid input"),
             context: &context,
             stats: &stats,
+            perf_stats: &HashMap::new(),
             label: LogLabel { log: "action" },
             trace_id: "action-1",
         };
@@ -338,4 +586,58 @@ mod tests {
         assert_eq!(value["logging.googleapis.com/labels"]["log"], "action");
         assert_eq!(value["logging.googleapis.com/trace"], "action-1");
     }
+
+    #[test]
+    fn serialize_json_action_entry() {
+        let context = vec![("user_id", vec!["u1".to_owned()]), ("tag", vec!["a".to_owned(), "b".to_owned()])];
+        let mut stats = HashMap::new();
+        stats.insert("elapsed".into(), 100);
+        let perf_stats = HashMap::from([(
+            "db",
+            PerformanceStat { total_elapsed: 50, count: 2, read_entries: Some(3), ..PerformanceStat::default() },
+        )]);
+        let trace = vec!["line1".to_owned()];
+
+        let action = ActionLog {
+            id: "action-1".to_owned(),
+            date: DateTime::from_timestamp(1_700_000_000, 0).unwrap(),
+            app: "test-app",
+            host: "host-1",
+            kind: "http",
+            severity: Some(Severity::Warn),
+            ref_id: None,
+            error_code: Some("NOT_FOUND"),
+            error_message: Some("not found"),
+            context: &context,
+            stats: &stats,
+            perf_stats: &perf_stats,
+            trace: Some(&trace),
+            spans: &[],
+        };
+
+        let json = json::to_json(&JsonActionEntry::new(&action)).unwrap();
+        let value: Value = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(value["severity"], "WARN");
+        assert_eq!(value["error_code"], "NOT_FOUND");
+        assert!(value.get("ref_id").is_none());
+        assert_eq!(value["context"]["user_id"], "u1");
+        assert_eq!(value["context"]["tag"][1], "b");
+        assert_eq!(value["stats"]["elapsed"], 100);
+        assert_eq!(value["perf_stats"]["db"]["count"], 2);
+        assert_eq!(value["perf_stats"]["db"]["read_entries"], 3);
+        assert!(value["perf_stats"]["db"].get("write_entries").is_none());
+        assert_eq!(value["trace"][0], "line1");
+    }
+
+    #[test]
+    fn flatten_perf_stat() {
+        let stat = PerformanceStat { total_elapsed: 50, count: 2, write_bytes: Some(10), ..PerformanceStat::default() };
+        assert_eq!(
+            stat.flatten("kafka"),
+            vec![("kafka_elapsed".to_owned(), 50), ("kafka_count".to_owned(), 2), ("kafka_write_bytes".to_owned(), 10)]
+        );
+        let without_span = PerformanceStat { read_entries: Some(1), ..PerformanceStat::default() };
+        assert_eq!(without_span.flatten("db"), vec![("db_read_entries".to_owned(), 1)]);
+    }
 }
@@ -0,0 +1,205 @@
+use std::fs;
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::io;
+use std::io::BufWriter;
+use std::io::Write as _;
+use std::iter;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::mpsc;
+use std::sync::mpsc::Receiver;
+use std::sync::mpsc::SyncSender;
+use std::thread;
+
+use chrono::DateTime;
+use chrono::NaiveDate;
+use chrono::Utc;
+
+use crate::console;
+use crate::json;
+use crate::log::appender::ActionLog;
+use crate::log::appender::ActionLogAppender;
+use crate::log::appender::JsonActionEntry;
+use crate::log::metrics::Metrics;
+
+const MAX_QUEUE_SIZE: usize = 10_000;
+const FILE_PREFIX: &str = "action-";
+const FILE_SUFFIX: &str = ".log";
+
+pub struct FileAppenderConfig {
+    pub dir: PathBuf,
+    // rotate to next file once current file exceeds, files are also rotated daily (UTC)
+    pub max_file_size: u64,
+    // oldest files are deleted on rotation
+    pub max_files: usize,
+}
+
+impl Default for FileAppenderConfig {
+    fn default() -> Self {
+        Self { dir: PathBuf::from("log"), max_file_size: 100 * 1024 * 1024, max_files: 10 }
+    }
+}
+
+// writes one json line per action to {dir}/action-{yyyy-mm-dd}.{index}.log,
+// lines are written by dedicated thread, new actions are dropped if queue is full, queued actions are dropped on process exit
+pub struct FileAppender {
+    sender: SyncSender<(DateTime<Utc>, String)>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl FileAppender {
+    pub fn new(config: FileAppenderConfig) -> Self {
+        fs::create_dir_all(&config.dir).expect("failed to create log dir"); // fail fast on startup
+        let (sender, receiver) = mpsc::sync_channel(MAX_QUEUE_SIZE);
+        let file = RollingFile::new(config);
+        thread::Builder::new()
+            .name("file-appender".to_owned())
+            .spawn(move || file.run(&receiver))
+            .expect("failed to spawn file appender thread");
+        Self { sender, dropped: Arc::new(AtomicU64::new(0)) }
+    }
+
+    // reports dropped actions since last collection, e.g. collector.add(appender.appender_metrics()) before log::init_with_appender(appender, app)
+    pub fn appender_metrics(&self) -> impl Fn(&mut Metrics) + use<> {
+        let dropped = Arc::clone(&self.dropped);
+        move |metrics| {
+            metrics.stats.push(("file_appender_dropped", dropped.swap(0, Ordering::Relaxed)));
+        }
+    }
+}
+
+impl ActionLogAppender for FileAppender {
+    fn append(&self, action: &ActionLog<'_>) {
+        let line = json::to_json(&JsonActionEntry::new(action)).expect("serialize to json cannot fail");
+        if self.sender.try_send((action.date, line)).is_err() {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+struct RollingFile {
+    config: FileAppenderConfig,
+    date: NaiveDate,
+    index: u32,
+    writer: Option<BufWriter<File>>,
+    size: u64,
+}
+
+impl RollingFile {
+    const fn new(config: FileAppenderConfig) -> Self {
+        Self { config, date: NaiveDate::MIN, index: 0, writer: None, size: 0 }
+    }
+
+    fn run(mut self, receiver: &Receiver<(DateTime<Utc>, String)>) {
+        while let Ok(entry) = receiver.recv() {
+            // drain queued lines before flush
+            let result = iter::once(entry)
+                .chain(receiver.try_iter())
+                .try_for_each(|(date, line)| self.write(date, &line))
+                .and_then(|()| self.flush());
+            if let Err(e) = result {
+                console!("WARN failed to write action log file, error={e}");
+                self.writer = None; // reopen on next write
+            }
+        }
+    }
+
+    fn write(&mut self, date: DateTime<Utc>, line: &str) -> io::Result<()> {
+        let date = date.date_naive();
+        if self.writer.is_none() || date != self.date || self.size >= self.config.max_file_size {
+            self.rotate(date)?;
+        }
+        if let Some(writer) = &mut self.writer {
+            writeln!(writer, "{line}")?;
+            self.size += line.len() as u64 + 1;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if let Some(writer) = &mut self.writer {
+            writer.flush()?;
+        }
+        Ok(())
+    }
+
+    fn rotate(&mut self, date: NaiveDate) -> io::Result<()> {
+        self.flush()?;
+        if date != self.date {
+            self.date = date;
+            self.index = 0;
+        } else if self.writer.is_some() {
+            self.index += 1;
+        }
+        // continue with existing file after restart, skip the full ones
+        loop {
+            let path = self.config.dir.join(format!("{FILE_PREFIX}{date}.{:03}{FILE_SUFFIX}", self.index));
+            let file = OpenOptions::new().create(true).append(true).open(path)?;
+            self.size = file.metadata()?.len();
+            if self.size < self.config.max_file_size {
+                self.writer = Some(BufWriter::new(file));
+                break;
+            }
+            self.index += 1;
+        }
+        self.cleanup()
+    }
+
+    fn cleanup(&self) -> io::Result<()> {
+        let mut files = vec![];
+        for entry in fs::read_dir(&self.config.dir)? {
+            let path = entry?.path();
+            if let Some(name) = path.file_name().and_then(|name| name.to_str())
+                && name.starts_with(FILE_PREFIX)
+                && name.ends_with(FILE_SUFFIX)
+            {
+                files.push(path);
+            }
+        }
+        // file names are ordered by date and index
+        files.sort();
+        let expired = files.len().saturating_sub(self.config.max_files);
+        for path in files.iter().take(expired) {
+            fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::process;
+
+    use chrono::DateTime;
+
+    use super::FileAppenderConfig;
+    use super::RollingFile;
+
+    #[test]
+    fn rotate() {
+        let dir = env::temp_dir().join(format!("file-appender-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let mut file = RollingFile::new(FileAppenderConfig { dir: dir.clone(), max_file_size: 10, max_files: 3 });
+
+        let day1 = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let day2 = DateTime::from_timestamp(1_700_100_000, 0).unwrap();
+        file.write(day1, "line-1-of-day1").unwrap();
+        file.write(day1, "line-2").unwrap();
+        file.write(day2, "line-1-of-day2").unwrap();
+        file.write(day2, "line-2").unwrap();
+        file.flush().unwrap();
+
+        let mut files: Vec<String> =
+            fs::read_dir(&dir).unwrap().map(|entry| entry.unwrap().file_name().into_string().unwrap()).collect();
+        files.sort();
+        assert_eq!(files, vec!["action-2023-11-14.001.log", "action-2023-11-16.000.log", "action-2023-11-16.001.log"]);
+        assert_eq!(fs::read_to_string(dir.join("action-2023-11-16.001.log")).unwrap(), "line-2\n");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
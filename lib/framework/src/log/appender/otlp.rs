@@ -0,0 +1,300 @@
+use std::sync::Arc;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+use chrono::DateTime;
+use chrono::Utc;
+use serde::Serialize;
+use tokio::sync::mpsc;
+use tokio::sync::mpsc::Receiver;
+use tokio::sync::mpsc::Sender;
+
+use crate::console;
+use crate::exception::Exception;
+use crate::exception::Severity;
+use crate::http::HttpClient;
+use crate::http::HttpClientConfig;
+use crate::http::HttpRequest;
+use crate::http::Method;
+use crate::json;
+use crate::log::appender::ActionLog;
+use crate::log::appender::ActionLogAppender;
+use crate::log::metrics::Metrics;
+use crate::log::trace_id;
+
+const MAX_QUEUE_SIZE: usize = 10_000;
+const MAX_BATCH_SIZE: usize = 100;
+
+// exports action and its span!() spans to opentelemetry collector via OTLP/HTTP json, e.g. for jaeger or tempo,
+// trace id is same as traceparent sent by HttpClient, new actions are dropped if queue is full
+pub struct OtlpAppender {
+    sender: Sender<ActionSpans>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl OtlpAppender {
+    // e.g. http://otel-collector:4318, must be called within tokio runtime
+    pub fn new(endpoint: &str) -> Self {
+        let (sender, receiver) = mpsc::channel(MAX_QUEUE_SIZE);
+        let url = format!("{endpoint}/v1/traces");
+        tokio::spawn(export(HttpClient::new(HttpClientConfig::default()), url, receiver));
+        Self { sender, dropped: Arc::new(AtomicU64::new(0)) }
+    }
+
+    // reports dropped actions since last collection
+    pub fn appender_metrics(&self) -> impl Fn(&mut Metrics) + use<> {
+        let dropped = Arc::clone(&self.dropped);
+        move |metrics| {
+            metrics.stats.push(("otlp_appender_dropped", dropped.swap(0, Ordering::Relaxed)));
+        }
+    }
+}
+
+impl ActionLogAppender for OtlpAppender {
+    fn append(&self, action: &ActionLog<'_>) {
+        let action_spans = ActionSpans { app: action.app, host: action.host, spans: spans(action) };
+        if self.sender.try_send(action_spans).is_err() {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+// runs outside action, so http client won't log or produce more actions
+async fn export(client: HttpClient, url: String, mut receiver: Receiver<ActionSpans>) {
+    let mut batch = Vec::with_capacity(MAX_BATCH_SIZE);
+    while receiver.recv_many(&mut batch, MAX_BATCH_SIZE).await > 0 {
+        if let Err(e) = send(&client, &url, &batch).await {
+            console!("WARN failed to export spans, url={url}, error={e}");
+        }
+        batch.clear();
+    }
+}
+
+async fn send(client: &HttpClient, url: &str, batch: &[ActionSpans]) -> Result<(), Exception> {
+    let Some(first) = batch.first() else {
+        return Ok(());
+    };
+    let request = ExportTraceServiceRequest {
+        resource_spans: vec![ResourceSpans {
+            resource: Resource {
+                attributes: vec![
+                    KeyValue::string("service.name", first.app.to_owned()),
+                    KeyValue::string("host.name", first.host.to_owned()),
+                ],
+            },
+            scope_spans: vec![ScopeSpans {
+                scope: Scope { name: "framework" },
+                spans: batch.iter().flat_map(|action| &action.spans).collect(),
+            }],
+        }],
+    };
+    let mut http_request = HttpRequest::new(Method::POST, url);
+    http_request.body(json::to_json(&request)?, "application/json");
+    let response = client.execute(http_request).await?;
+    if !(200..300).contains(&response.status) {
+        return Err(exception!(format!("failed to export spans, status={}", response.status)));
+    }
+    Ok(())
+}
+
+fn spans(action: &ActionLog<'_>) -> Vec<OtlpSpan> {
+    let trace_id = trace_id(&action.id, action.context);
+    let root_id = span_id();
+    let elapsed = Duration::from_nanos(action.stats.get("elapsed").copied().unwrap_or_default());
+
+    let mut attributes = vec![KeyValue::string("action.id", action.id.clone())];
+    if let Some(error_code) = action.error_code {
+        attributes.push(KeyValue::string("error_code", error_code.to_owned()));
+    }
+    for (key, values) in action.context {
+        attributes.push(KeyValue::string(key, values.join(",")));
+    }
+    for (key, value) in action.stats {
+        attributes.push(KeyValue { key: key.to_string(), value: AnyValue::IntValue(value.to_string()) });
+    }
+    for (operation, stat) in action.perf_stats {
+        for (key, value) in stat.flatten(operation) {
+            attributes.push(KeyValue { key, value: AnyValue::IntValue(value.to_string()) });
+        }
+    }
+
+    let mut spans = Vec::with_capacity(action.spans.len() + 1);
+    spans.push(OtlpSpan {
+        trace_id: trace_id.clone(),
+        span_id: root_id.clone(),
+        parent_span_id: None,
+        name: action.kind.to_owned(),
+        kind: SPAN_KIND_INTERNAL,
+        start_time_unix_nano: unix_nano(action.date, Duration::ZERO),
+        end_time_unix_nano: unix_nano(action.date, elapsed),
+        attributes,
+        status: match action.severity {
+            Some(Severity::Error) => {
+                Status { code: STATUS_CODE_ERROR, message: action.error_message.map(str::to_owned) }
+            }
+            Some(Severity::Warn) | None => Status { code: STATUS_CODE_UNSET, message: None },
+        },
+    });
+
+    let span_ids: Vec<String> = action.spans.iter().map(|_| span_id()).collect();
+    for (span, span_id) in action.spans.iter().zip(&span_ids) {
+        let parent_id = span.parent.and_then(|parent| span_ids.get(parent)).unwrap_or(&root_id);
+        spans.push(OtlpSpan {
+            trace_id: trace_id.clone(),
+            span_id: span_id.clone(),
+            parent_span_id: Some(parent_id.clone()),
+            name: span.name.to_owned(),
+            kind: SPAN_KIND_INTERNAL,
+            start_time_unix_nano: unix_nano(action.date, span.start),
+            end_time_unix_nano: unix_nano(action.date, span.start + span.elapsed),
+            attributes: vec![],
+            status: Status { code: STATUS_CODE_UNSET, message: None },
+        });
+    }
+    spans
+}
+
+fn span_id() -> String {
+    format!("{:016x}", rand::random::<u64>())
+}
+
+// int64 is encoded as string in OTLP json
+fn unix_nano(date: DateTime<Utc>, offset: Duration) -> String {
+    let start = date.timestamp_nanos_opt().unwrap_or_default() as u128;
+    (start + offset.as_nanos()).to_string()
+}
+
+struct ActionSpans {
+    app: &'static str,
+    host: &'static str,
+    spans: Vec<OtlpSpan>,
+}
+
+const SPAN_KIND_INTERNAL: u8 = 1;
+const STATUS_CODE_UNSET: u8 = 0;
+const STATUS_CODE_ERROR: u8 = 2;
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ExportTraceServiceRequest<'a> {
+    resource_spans: Vec<ResourceSpans<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ResourceSpans<'a> {
+    resource: Resource,
+    scope_spans: Vec<ScopeSpans<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+struct Resource {
+    attributes: Vec<KeyValue>,
+}
+
+#[derive(Debug, Serialize)]
+struct ScopeSpans<'a> {
+    scope: Scope,
+    spans: Vec<&'a OtlpSpan>,
+}
+
+#[derive(Debug, Serialize)]
+struct Scope {
+    name: &'static str,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct OtlpSpan {
+    trace_id: String,
+    span_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    parent_span_id: Option<String>,
+    name: String,
+    kind: u8,
+    start_time_unix_nano: String,
+    end_time_unix_nano: String,
+    attributes: Vec<KeyValue>,
+    status: Status,
+}
+
+#[derive(Debug, Serialize)]
+struct Status {
+    code: u8,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct KeyValue {
+    key: String,
+    value: AnyValue,
+}
+
+impl KeyValue {
+    fn string(key: &str, value: String) -> Self {
+        Self { key: key.to_owned(), value: AnyValue::StringValue(value) }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+enum AnyValue {
+    StringValue(String),
+    IntValue(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    use chrono::DateTime;
+
+    use super::spans;
+    use crate::exception::Severity;
+    use crate::log::appender::ActionLog;
+    use crate::log::appender::SpanLog;
+
+    #[test]
+    fn action_spans() {
+        let stats = HashMap::from([("elapsed".into(), 5_000)]);
+        let span_logs = vec![
+            SpanLog { name: "http", parent: None, start: Duration::from_micros(1), elapsed: Duration::from_micros(3) },
+            SpanLog {
+                name: "db",
+                parent: Some(0),
+                start: Duration::from_micros(2),
+                elapsed: Duration::from_nanos(500),
+            },
+        ];
+        let action = ActionLog {
+            id: "0A1B2C3D4E5F60718293".to_owned(),
+            date: DateTime::from_timestamp(1_700_000_000, 0).unwrap(),
+            app: "test-app",
+            host: "host-1",
+            kind: "http",
+            severity: Some(Severity::Error),
+            ref_id: None,
+            error_code: Some("INTERNAL_ERROR"),
+            error_message: Some("failed"),
+            context: &[],
+            stats: &stats,
+            perf_stats: &HashMap::new(),
+            trace: None,
+            spans: &span_logs,
+        };
+
+        let spans = spans(&action);
+        assert_eq!(spans.len(), 3);
+        assert_eq!(spans[0].trace_id, "0000000000000a1b2c3d4e5f60718293");
+        assert_eq!(spans[0].start_time_unix_nano, "1700000000000000000");
+        assert_eq!(spans[0].end_time_unix_nano, "1700000000000005000");
+        assert_eq!(spans[0].status.code, 2);
+        assert_eq!(spans[1].parent_span_id.as_ref(), Some(&spans[0].span_id));
+        assert_eq!(spans[2].parent_span_id.as_ref(), Some(&spans[1].span_id));
+        assert_eq!(spans[2].start_time_unix_nano, "1700000000000002000");
+        assert_eq!(spans[2].end_time_unix_nano, "1700000000000002500");
+    }
+}
@@ -0,0 +1,115 @@
+use std::borrow::Cow;
+use std::sync::LazyLock;
+use std::sync::OnceLock;
+
+use regex::Regex;
+
+const MASK: &str = "******";
+
+pub struct MaskConfig {
+    // matched case insensitively as part of json field, key=value or header name, e.g. "token" matches access_token
+    pub fields: Vec<&'static str>,
+    // regex, matched text is replaced with ******, e.g. credit card number
+    pub patterns: Vec<&'static str>,
+}
+
+impl Default for MaskConfig {
+    fn default() -> Self {
+        Self {
+            fields: vec!["password", "passwd", "secret", "token", "authorization", "api_key", "api-key"],
+            patterns: vec![],
+        }
+    }
+}
+
+struct Masking {
+    fields: Vec<String>,
+    // (regex, replacement)
+    rules: Vec<(Regex, String)>,
+}
+
+static MASKING: OnceLock<Masking> = OnceLock::new();
+static DEFAULT_MASKING: LazyLock<Masking> = LazyLock::new(|| Masking::new(MaskConfig::default()));
+
+// applies to all log lines and error messages of action, must be called before log::init if customized
+pub fn init(config: MaskConfig) {
+    MASKING.set(Masking::new(config)).unwrap_or_else(|_| panic!("mask can only be initialized once"));
+}
+
+fn masking() -> &'static Masking {
+    MASKING.get().unwrap_or(&DEFAULT_MASKING)
+}
+
+impl Masking {
+    fn new(config: MaskConfig) -> Self {
+        let mut rules = vec![];
+        if !config.fields.is_empty() {
+            let fields = config.fields.iter().map(|field| regex::escape(field)).collect::<Vec<_>>().join("|");
+            // "password": "value"
+            let json = format!(r#"(?i)("[\w-]*(?:{fields})[\w-]*"\s*:\s*)"(?:[^"\\]|\\.)*""#);
+            // password=value, e.g. query string or form body
+            let key_value = format!(r"(?i)\b([\w-]*(?:{fields})[\w-]*=)[^&\s,]+");
+            rules.push((Regex::new(&json).expect("mask field must be valid"), format!(r#"${{1}}"{MASK}""#)));
+            rules.push((Regex::new(&key_value).expect("mask field must be valid"), format!("${{1}}{MASK}")));
+        }
+        for pattern in config.patterns {
+            rules.push((Regex::new(pattern).expect("mask pattern must be valid regex"), MASK.to_owned()));
+        }
+        Self { fields: config.fields.iter().map(|field| field.to_lowercase()).collect(), rules }
+    }
+
+    fn mask<'a>(&self, message: &'a str) -> Cow<'a, str> {
+        let mut message = Cow::Borrowed(message);
+        for (regex, replacement) in &self.rules {
+            if regex.is_match(&message) {
+                message = Cow::Owned(regex.replace_all(&message, replacement.as_str()).into_owned());
+            }
+        }
+        message
+    }
+
+    fn is_masked_field(&self, name: &str) -> bool {
+        let name = name.to_lowercase();
+        self.fields.iter().any(|field| name.contains(field.as_str()))
+    }
+}
+
+pub(crate) fn mask(message: &str) -> Cow<'_, str> {
+    masking().mask(message)
+}
+
+// e.g. header name
+pub(crate) fn is_masked_field(name: &str) -> bool {
+    masking().is_masked_field(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MaskConfig;
+    use super::Masking;
+
+    #[test]
+    fn mask() {
+        let masking = Masking::new(MaskConfig { patterns: vec![r"\b\d{16}\b"], ..MaskConfig::default() });
+        let mask = |message| masking.mask(message);
+
+        assert_eq!(
+            mask(r#"[request] body={"name":"n","password":"p\"1","accessToken" : "t"}"#),
+            r#"[request] body={"name":"n","password":"******","accessToken" : "******"}"#
+        );
+        assert_eq!(
+            mask("[request] url=https://host/path?api_key=k1&name=n&Token=t"),
+            "[request] url=https://host/path?api_key=******&name=n&Token=******"
+        );
+        assert_eq!(mask("card=1234567812345678"), "card=******");
+        assert_eq!(mask("name=none"), "name=none");
+    }
+
+    #[test]
+    fn is_masked_field() {
+        let masking = Masking::new(MaskConfig::default());
+        assert!(masking.is_masked_field("Authorization"));
+        assert!(masking.is_masked_field("x-api-key"));
+        assert!(!masking.is_masked_field("content-type"));
+    }
+}
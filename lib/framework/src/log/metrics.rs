@@ -6,6 +6,7 @@ use std::time::Instant;
 
 use chrono::DateTime;
 use chrono::Utc;
+use tokio::runtime::Handle;
 use tokio::time::sleep;
 use tokio_util::sync::CancellationToken;
 
@@ -16,6 +17,7 @@ use crate::log::Context;
 use crate::log::action::Error;
 use crate::log::id_generator;
 use crate::log::id_generator::LogId;
+use crate::log::prometheus;
 use crate::number::parse_u64;
 
 pub struct Metrics {
@@ -87,6 +89,7 @@ impl MetricsCollector {
                     }
                     () = sleep(Duration::from_secs(5)) => {
                         let metrics = self.collect_metrics();
+                        prometheus::record_metrics(&metrics);
                         appender.append_metrics(&metrics, app);
                     }
                 }
@@ -112,6 +115,9 @@ impl MetricsCollector {
             collect_mem_usage(&mut metrics, mem_stats);
         }
 
+        collect_process_stats(&mut metrics);
+        collect_runtime_stats(&mut metrics);
+
         for collector in &self.collectors {
             collector(&mut metrics);
         }
@@ -209,6 +215,22 @@ fn collect_mem_usage(metrics: &mut Metrics, mem_stats: &MemoryStats) {
     }
 }
 
+fn collect_process_stats(metrics: &mut Metrics) {
+    if let Ok(entries) = fs::read_dir("/proc/self/fd") {
+        metrics.stats.push(("process_open_fds", entries.count() as u64));
+    }
+}
+
+// tokio runtime is the equivalent of java thread pools
+fn collect_runtime_stats(metrics: &mut Metrics) {
+    if let Ok(handle) = Handle::try_current() {
+        let runtime_metrics = handle.metrics();
+        metrics.stats.push(("runtime_workers", runtime_metrics.num_workers() as u64));
+        metrics.stats.push(("runtime_alive_tasks", runtime_metrics.num_alive_tasks() as u64));
+        metrics.stats.push(("runtime_global_queue_depth", runtime_metrics.global_queue_depth() as u64));
+    }
+}
+
 // container working set memory (memory.current - inactive_file) in bytes, cgroup v2
 fn container_mem_used() -> Option<u64> {
     let current = parse_u64(fs::read_to_string("/sys/fs/cgroup/memory.current").ok()?.trim()).ok()?;
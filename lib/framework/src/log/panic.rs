@@ -0,0 +1,59 @@
+use std::any::Any;
+use std::backtrace::Backtrace;
+use std::cell::RefCell;
+use std::panic;
+use std::sync::Once;
+
+use crate::exception::Exception;
+use crate::log::CURRENT_ACTION;
+
+thread_local! {
+    // panic hook runs on panicking thread, before unwinding reaches catch_unwind within log::action()
+    static PANIC_BACKTRACE: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+// keeps default hook which prints to stderr, and captures backtrace if panicked within action
+pub(crate) fn install_hook() {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        let default_hook = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            if CURRENT_ACTION.try_with(|_| ()).is_ok() {
+                let location = info.location().map(ToString::to_string).unwrap_or_default();
+                let backtrace = format!("panicked at {location}\n{}", Backtrace::force_capture());
+                PANIC_BACKTRACE.with(|panic_backtrace| *panic_backtrace.borrow_mut() = Some(backtrace));
+            }
+            default_hook(info);
+        }));
+    });
+}
+
+pub(crate) fn take_backtrace() -> Option<String> {
+    PANIC_BACKTRACE.with(|panic_backtrace| panic_backtrace.borrow_mut().take())
+}
+
+pub(crate) fn panic_exception(payload: &(dyn Any + Send)) -> Exception {
+    let message = if let Some(message) = payload.downcast_ref::<&str>() {
+        message
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.as_str()
+    } else {
+        "unknown panic"
+    };
+    exception!(format!("panic: {message}"), code = "PANIC")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::panic_exception;
+
+    #[test]
+    fn panic_message() {
+        let str_panic = panic_exception(&"invalid state");
+        assert_eq!(str_panic.code, Some("PANIC"));
+        assert_eq!(str_panic.message, "panic: invalid state");
+
+        let string_panic = panic_exception(&"index out of bounds".to_owned());
+        assert_eq!(string_panic.message, "panic: index out of bounds");
+    }
+}
@@ -0,0 +1,153 @@
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+
+use crate::exception::Severity;
+use crate::log::action::Action;
+use crate::log::metrics::Metrics;
+use crate::write_str;
+
+// aggregates finished actions and collected metrics, exposed by http server in prometheus text format
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static REGISTRY: Mutex<Registry> = Mutex::new(Registry::new());
+
+// context keys used as "name" label, values must have bounded cardinality
+pub(crate) const NAME_CONTEXT_KEYS: [&str; 3] = ["matched_path", "topic", "job"];
+
+struct Registry {
+    actions: BTreeMap<(&'static str, String), ActionStats>,
+    gauges: BTreeMap<&'static str, u64>,
+}
+
+#[derive(Default)]
+struct ActionStats {
+    results: BTreeMap<&'static str, u64>,
+    elapsed: u64,
+    stats: BTreeMap<String, u64>,
+}
+
+pub(crate) fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+pub(crate) fn record_action(action: &Action) {
+    if ENABLED.load(Ordering::Relaxed) {
+        REGISTRY.lock().unwrap().record_action(action);
+    }
+}
+
+pub(crate) fn record_metrics(metrics: &Metrics) {
+    if ENABLED.load(Ordering::Relaxed) {
+        REGISTRY.lock().unwrap().gauges.extend(metrics.stats.iter().copied());
+    }
+}
+
+pub(crate) fn render() -> String {
+    REGISTRY.lock().unwrap().render()
+}
+
+impl Registry {
+    const fn new() -> Self {
+        Self { actions: BTreeMap::new(), gauges: BTreeMap::new() }
+    }
+
+    fn record_action(&mut self, action: &Action) {
+        let name = action
+            .context
+            .iter()
+            .find(|(key, _)| NAME_CONTEXT_KEYS.contains(key))
+            .and_then(|(_, values)| values.first())
+            .cloned()
+            .unwrap_or_default();
+        let result = match action.error {
+            Some(ref error) if error.severity == Severity::Error => "ERROR",
+            Some(_) => "WARN",
+            None => "OK",
+        };
+        let stats = self.actions.entry((action.kind, name)).or_default();
+        *stats.results.entry(result).or_default() += 1;
+        for (key, value) in &action.stats {
+            if key == "elapsed" {
+                stats.elapsed += value;
+            } else {
+                *stats.stats.entry(key.to_string()).or_default() += value;
+            }
+        }
+        for (operation, stat) in &action.perf_stats {
+            for (key, value) in stat.flatten(operation) {
+                *stats.stats.entry(key).or_default() += value;
+            }
+        }
+    }
+
+    fn render(&self) -> String {
+        let mut output = String::new();
+        output.push_str("# TYPE action_total counter\n");
+        for ((kind, name), stats) in &self.actions {
+            let name = escape(name);
+            for (result, count) in &stats.results {
+                write_str!(output, "action_total{{kind=\"{kind}\",name=\"{name}\",result=\"{result}\"}} {count}\n");
+            }
+        }
+        output.push_str("# TYPE action_elapsed_seconds summary\n");
+        for ((kind, name), stats) in &self.actions {
+            let name = escape(name);
+            let count: u64 = stats.results.values().sum();
+            let elapsed = stats.elapsed as f64 / 1_000_000_000.0;
+            write_str!(output, "action_elapsed_seconds_sum{{kind=\"{kind}\",name=\"{name}\"}} {elapsed}\n");
+            write_str!(output, "action_elapsed_seconds_count{{kind=\"{kind}\",name=\"{name}\"}} {count}\n");
+        }
+        output.push_str("# TYPE action_stats_total counter\n");
+        for ((kind, name), stats) in &self.actions {
+            let name = escape(name);
+            for (stat, value) in &stats.stats {
+                write_str!(output, "action_stats_total{{kind=\"{kind}\",name=\"{name}\",stat=\"{stat}\"}} {value}\n");
+            }
+        }
+        for (key, value) in &self.gauges {
+            write_str!(output, "# TYPE {key} gauge\n{key} {value}\n");
+        }
+        output
+    }
+}
+
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use super::Registry;
+    use crate::log::action::Action;
+    use crate::log::id_generator;
+
+    #[test]
+    fn render() {
+        let mut action = Action::new(id_generator::next_id(0), "http", None, Utc::now());
+        action.context.push(("matched_path", vec!["/user/{id}".to_owned()]));
+        action.stats.insert("elapsed".into(), 1_500_000_000);
+        action.add_stats("http_read_bytes", 100);
+
+        let mut registry = Registry::new();
+        registry.record_action(&action);
+        registry.record_action(&action);
+        registry.gauges.insert("active_http_requests", 3);
+
+        assert_eq!(
+            registry.render(),
+            r#"# TYPE action_total counter
+action_total{kind="http",name="/user/{id}",result="OK"} 2
+# TYPE action_elapsed_seconds summary
+action_elapsed_seconds_sum{kind="http",name="/user/{id}"} 3
+action_elapsed_seconds_count{kind="http",name="/user/{id}"} 2
+# TYPE action_stats_total counter
+action_stats_total{kind="http",name="/user/{id}",stat="http_read_bytes"} 200
+# TYPE active_http_requests gauge
+active_http_requests 3
+"#
+        );
+    }
+}
@@ -0,0 +1,107 @@
+use std::time::Duration;
+
+use tokio::time;
+
+use crate::exception::Exception;
+use crate::log::__stats;
+
+// retry failed operation within same action, e.g. kafka handler or scheduled job, fails after last attempt
+#[derive(Clone, Copy)]
+pub struct RetryPolicy {
+    // includes first attempt, 1 means no retry
+    pub max_attempts: u32,
+    // doubled after each attempt, capped by max_backoff
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    // e.g. retry on elasticsearch or network error, not on validation error
+    pub retryable: fn(&Exception) -> bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 1,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(30),
+            retryable: |_| true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    fn backoff(&self, attempt: u32) -> Duration {
+        self.initial_backoff.saturating_mul(2_u32.saturating_pow(attempt.saturating_sub(1))).min(self.max_backoff)
+    }
+}
+
+// stats_key counts retries of current action, e.g. "kafka_handler_retries"
+pub async fn with_retry<F, Fut, T>(
+    retry: RetryPolicy,
+    stats_key: &'static str,
+    mut operation: F,
+) -> Result<T, Exception>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, Exception>>,
+{
+    let mut attempt = 1;
+    loop {
+        let result = operation().await;
+        match result {
+            Err(ref error) if attempt < retry.max_attempts && (retry.retryable)(error) => {
+                let backoff = retry.backoff(attempt);
+                log!("failed, retry after {backoff:?}, attempt={attempt}, error={}", error.message);
+                __stats(stats_key, 1, concat!(module_path!(), ":", line!()));
+                time::sleep(backoff).await;
+                attempt += 1;
+            }
+            _ => return result,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::RetryPolicy;
+    use super::with_retry;
+
+    #[test]
+    fn backoff() {
+        let retry = RetryPolicy {
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(5),
+            ..RetryPolicy::default()
+        };
+        assert_eq!(retry.backoff(1), Duration::from_secs(1));
+        assert_eq!(retry.backoff(2), Duration::from_secs(2));
+        assert_eq!(retry.backoff(3), Duration::from_secs(4));
+        assert_eq!(retry.backoff(4), Duration::from_secs(5));
+        assert_eq!(retry.backoff(100), Duration::from_secs(5));
+    }
+
+    #[tokio::test]
+    async fn retry_until_max_attempts() {
+        let retry =
+            RetryPolicy { max_attempts: 3, initial_backoff: Duration::from_millis(1), ..RetryPolicy::default() };
+        let mut attempts = 0;
+        let result: Result<(), _> = with_retry(retry, "test_retries", || {
+            attempts += 1;
+            async { Err(exception!("failed")) }
+        })
+        .await;
+        result.unwrap_err();
+        assert_eq!(attempts, 3);
+
+        let not_retryable = RetryPolicy { max_attempts: 3, retryable: |_| false, ..RetryPolicy::default() };
+        let mut calls = 0;
+        let not_retried: Result<(), _> = with_retry(not_retryable, "test_retries", || {
+            calls += 1;
+            async { Err(exception!("failed")) }
+        })
+        .await;
+        not_retried.unwrap_err();
+        assert_eq!(calls, 1);
+    }
+}
@@ -1,8 +1,11 @@
 use std::any::type_name;
+use std::collections::VecDeque;
 use std::mem;
 use std::pin::Pin;
 use std::sync::Arc;
 use std::sync::Mutex;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
 use std::time::Duration;
 
 use chrono::DateTime;
@@ -10,45 +13,173 @@ use chrono::FixedOffset;
 use chrono::NaiveTime;
 use chrono::SecondsFormat;
 use chrono::Utc;
+use chrono::Weekday;
 use futures::FutureExt as _;
+use serde::Serialize;
+use tokio::sync::Semaphore;
 use tokio::task::JoinSet;
 use tokio::time;
 use tokio_util::sync::CancellationToken;
 
 use crate::exception::Exception;
+use crate::exception::Severity;
 use crate::log;
 use crate::log::current_action_id;
+use crate::log::current_correlation_id;
+use crate::network::hostname;
+use crate::retry::RetryPolicy;
+use crate::retry::with_retry;
+use crate::schedule::cron::Cron;
+use crate::schedule::lock::JobLock;
 use crate::schedule::trigger::Trigger;
 use crate::task::TaskExecutor;
 
 pub mod controller;
+mod cron;
+pub mod lock;
 mod trigger;
 
+// executions kept per job by JobHistory
+const MAX_HISTORY: usize = 20;
+// must be longer than clock skew between replicas
+const JOB_LOCK_TTL: Duration = Duration::from_hours(1);
+
+#[derive(Clone)]
 pub struct JobContext {
     pub name: &'static str,
     pub scheduled_time: DateTime<Utc>,
 }
 
-type Job<S> = Box<dyn Fn(S, JobContext) -> Pin<Box<dyn Future<Output = ()> + Send>> + Send + Sync>;
+// what to do if previous execution of same job is still running, e.g. slow job overlaps its next scheduled time,
+// applies to both scheduled and manually triggered executions
+#[derive(Debug, Clone, Copy, Default)]
+pub enum OverlapPolicy {
+    // runs concurrently
+    #[default]
+    Allow,
+    // skips this execution
+    Skip,
+    // waits until previous execution finished
+    Queue,
+    // cancels previous execution with error_code JOB_CANCELLED, and runs after it stopped
+    CancelPrevious,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct JobExecution {
+    pub action_id: Option<String>,
+    pub scheduled_time: DateTime<Utc>,
+    pub start_time: DateTime<Utc>,
+    pub end_time: DateTime<Utc>,
+    // OK, WARN or ERROR, same as action result
+    pub result: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_message: Option<String>,
+}
+
+type Executions = Arc<Mutex<VecDeque<JobExecution>>>;
+
+// recent executions of all jobs, e.g. to check if last night's job succeeded, remains valid after scheduler started
+#[derive(Clone)]
+pub struct JobHistory {
+    jobs: Vec<(&'static str, Executions)>,
+}
+
+impl JobHistory {
+    // latest first, none if job not found
+    pub fn executions(&self, name: &str) -> Option<Vec<JobExecution>> {
+        let (_, executions) = self.jobs.iter().find(|(job, _)| *job == name)?;
+        Some(executions.lock().unwrap().iter().rev().cloned().collect())
+    }
+}
+
+fn record_execution(executions: &Mutex<VecDeque<JobExecution>>, execution: JobExecution) {
+    let mut executions = executions.lock().unwrap();
+    if executions.len() >= MAX_HISTORY {
+        executions.pop_front();
+    }
+    executions.push_back(execution);
+}
+
+type Job<S> = Box<dyn Fn(S, JobContext, Execution) -> Pin<Box<dyn Future<Output = ()> + Send>> + Send + Sync>;
+
+// applied within job action, so cancelled or timed out job is logged with error
+struct Execution {
+    cancel: CancellationToken,
+    timeout: Option<Duration>,
+    retry: RetryPolicy,
+    history: Executions,
+}
 
 struct Schedule<S> {
     name: &'static str,
     job: Job<S>,
     trigger: Trigger,
+    overlap: OverlapPolicy,
+    timeout: Option<Duration>,
+    retry: RetryPolicy,
+    // paused job is not run by trigger, but still can be triggered manually
+    paused: AtomicBool,
+    history: Executions,
+    running: Arc<Semaphore>,
+    previous: Mutex<CancellationToken>,
+}
+
+impl<S> Schedule<S> {
+    // returns false if skipped by overlap policy
+    fn spawn(&self, executor: &Mutex<TaskExecutor>, task_name: String, state: S, context: JobContext) -> bool {
+        let running = Arc::clone(&self.running);
+        match self.overlap {
+            OverlapPolicy::Allow => {
+                let job = (self.job)(state, context, self.execution(CancellationToken::new()));
+                executor.lock().unwrap().spawn(task_name, job);
+            }
+            OverlapPolicy::Skip => {
+                let Ok(permit) = running.try_acquire_owned() else {
+                    console!("WARN job skipped, previous execution is still running, name={}", self.name);
+                    return false;
+                };
+                let job = (self.job)(state, context, self.execution(CancellationToken::new()));
+                executor.lock().unwrap().spawn(task_name, async move {
+                    job.await;
+                    drop(permit);
+                });
+            }
+            OverlapPolicy::Queue | OverlapPolicy::CancelPrevious => {
+                let cancel = CancellationToken::new();
+                if let OverlapPolicy::CancelPrevious = self.overlap {
+                    mem::replace(&mut *self.previous.lock().unwrap(), cancel.clone()).cancel();
+                }
+                let job = (self.job)(state, context, self.execution(cancel));
+                executor.lock().unwrap().spawn(task_name, async move {
+                    let _permit = running.acquire_owned().await;
+                    job.await;
+                });
+            }
+        }
+        true
+    }
+
+    fn execution(&self, cancel: CancellationToken) -> Execution {
+        Execution { cancel, timeout: self.timeout, retry: self.retry, history: Arc::clone(&self.history) }
+    }
 }
 
 pub struct Scheduler<S> {
     timezone: FixedOffset,
     schedules: Vec<Arc<Schedule<S>>>,
     executor: Arc<Mutex<TaskExecutor>>,
+    lock: Option<Arc<dyn JobLock>>,
 }
 
 impl<S> Scheduler<S>
 where
-    S: Send + Sync + 'static,
+    S: Clone + Send + Sync + 'static,
 {
     pub fn new(timezone: FixedOffset) -> Self {
-        Self { timezone, schedules: Vec::new(), executor: Arc::new(Mutex::new(TaskExecutor::default())) }
+        Self { timezone, schedules: Vec::new(), executor: Arc::new(Mutex::new(TaskExecutor::default())), lock: None }
     }
 
     pub fn schedule_fixed_rate<J, Fut>(&mut self, name: &'static str, job: J, interval: Duration)
@@ -67,19 +198,104 @@ where
         self.add_job(name, job, Trigger::Daily { time_zone: self.timezone, time });
     }
 
-    fn add_job<J, Fut>(&mut self, name: &'static str, job: J, trigger: Trigger)
+    pub fn schedule_weekly<J, Fut>(&mut self, name: &'static str, job: J, weekday: Weekday, time: NaiveTime)
+    where
+        J: Fn(S, JobContext) -> Fut + Copy + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), Exception>> + Send + 'static,
+    {
+        self.add_job(name, job, Trigger::Weekly { time_zone: self.timezone, weekday, time });
+    }
+
+    // day_of_month is 1 to 31, runs on last day of month if month is shorter, e.g. 31 runs on feb 28
+    pub fn schedule_monthly<J, Fut>(&mut self, name: &'static str, job: J, day_of_month: u32, time: NaiveTime)
     where
         J: Fn(S, JobContext) -> Fut + Copy + Send + Sync + 'static,
         Fut: Future<Output = Result<(), Exception>> + Send + 'static,
     {
-        let job = Box::new(move |state: S, context| process_job(job, state, context));
-        self.schedules.push(Arc::new(Schedule { name, job, trigger }));
+        assert!((1..=31).contains(&day_of_month), "day_of_month must be 1 to 31, name={name}");
+        self.add_job(name, job, Trigger::Monthly { time_zone: self.timezone, day_of_month, time });
+    }
+
+    // e.g. "0 30 4 * * MON-FRI" for weekdays at 04:30, evaluated in scheduler timezone, see cron::Cron for syntax,
+    // panics if expression is invalid or never matches
+    pub fn schedule_cron<J, Fut>(&mut self, name: &'static str, job: J, expression: &str)
+    where
+        J: Fn(S, JobContext) -> Fut + Copy + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), Exception>> + Send + 'static,
+    {
+        let cron =
+            Cron::parse(expression).unwrap_or_else(|e| panic!("invalid cron expression, name={name}, error={e}"));
+        assert!(
+            cron.next(Utc::now().with_timezone(&self.timezone)).is_some(),
+            "cron expression never matches, name={name}, expression={expression}"
+        );
+        self.add_job(name, job, Trigger::Cron { time_zone: self.timezone, cron });
+    }
+
+    // must be called before routes() of SystemRoute
+    pub fn overlap_policy(&mut self, name: &'static str, policy: OverlapPolicy) {
+        self.schedule_mut(name).overlap = policy;
+    }
+
+    // job is aborted and its action fails with error_code JOB_TIMEOUT if it runs longer than timeout,
+    // must be called before routes() of SystemRoute
+    pub fn timeout(&mut self, name: &'static str, timeout: Duration) {
+        self.schedule_mut(name).timeout = Some(timeout);
+    }
+
+    // timeout covers all attempts, must be called before routes() of SystemRoute
+    pub fn retry(&mut self, name: &'static str, retry: RetryPolicy) {
+        assert!(retry.max_attempts >= 1, "max_attempts must be at least 1, name={name}");
+        self.schedule_mut(name).retry = retry;
+    }
+
+    // triggered jobs only run if lock of job and scheduled time is acquired, manually triggered jobs are not locked,
+    // fixed rate jobs are not locked either, as they are scheduled from start time of each replica,
+    // pause by /_sys/job only applies to replica received the request, other replicas still acquire lock and run job,
+    // so pause all replicas, or disable job in config instead
+    pub fn job_lock<L>(&mut self, lock: L)
+    where
+        L: JobLock + 'static,
+    {
+        self.lock = Some(Arc::new(lock));
+    }
+
+    pub fn history(&self) -> JobHistory {
+        JobHistory {
+            jobs: self.schedules.iter().map(|schedule| (schedule.name, Arc::clone(&schedule.history))).collect(),
+        }
     }
 
-    pub async fn start(self, state: S, shutdown_signal: CancellationToken)
+    fn schedule_mut(&mut self, name: &'static str) -> &mut Schedule<S> {
+        let schedule = self
+            .schedules
+            .iter_mut()
+            .find(|schedule| schedule.name == name)
+            .unwrap_or_else(|| panic!("job not found, name={name}"));
+        Arc::get_mut(schedule).unwrap_or_else(|| panic!("job must be configured before creating routes, name={name}"))
+    }
+
+    fn add_job<J, Fut>(&mut self, name: &'static str, job: J, trigger: Trigger)
     where
-        S: Clone,
+        J: Fn(S, JobContext) -> Fut + Copy + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), Exception>> + Send + 'static,
     {
+        let job = Box::new(move |state: S, context, execution| process_job(job, state, context, execution));
+        self.schedules.push(Arc::new(Schedule {
+            name,
+            job,
+            trigger,
+            overlap: OverlapPolicy::default(),
+            timeout: None,
+            retry: RetryPolicy::default(),
+            paused: AtomicBool::new(false),
+            history: Arc::new(Mutex::new(VecDeque::with_capacity(MAX_HISTORY))),
+            running: Arc::new(Semaphore::new(1)),
+            previous: Mutex::new(CancellationToken::new()),
+        }));
+    }
+
+    pub async fn start(self, state: S, shutdown_signal: CancellationToken) {
         assert!(!self.schedules.is_empty(), "scheduler does not have any jobs");
 
         let mut handles = JoinSet::new();
@@ -87,6 +303,7 @@ where
             let state = state.clone();
             let shutdown_signal = shutdown_signal.clone();
             let executor = Arc::clone(&self.executor);
+            let lock = self.lock.clone().filter(|_| !matches!(schedule.trigger, Trigger::FixedRate(_)));
             handles.spawn(async move {
                 let mut previous = Utc::now();
                 let mut first = true;
@@ -106,11 +323,16 @@ where
                             return;
                         }
                         () = time::sleep(waiting_time) => {
-                            let state = state.clone();
-                            executor.lock().unwrap().spawn(
-                                format!("job:{name}@{scheduled_time}"),
-                                (schedule.job)(state, context),
-                            );
+                            if schedule.paused.load(Ordering::Relaxed) {
+                                console!("job paused, skip execution, name={name}, scheduled_time={scheduled_time}");
+                                continue;
+                            }
+                            if let Some(ref lock) = lock
+                                && !acquire_lock(lock.as_ref(), name, &scheduled_time).await
+                            {
+                                continue;
+                            }
+                            schedule.spawn(&executor, format!("job:{name}@{scheduled_time}"), state.clone(), context);
                         }
                     }
                 }
@@ -126,13 +348,33 @@ where
     }
 }
 
-fn process_job<S, J, Fut>(job: J, state: S, context: JobContext) -> Pin<Box<dyn Future<Output = ()> + Send>>
+async fn acquire_lock(lock: &dyn JobLock, name: &str, scheduled_time: &str) -> bool {
+    match lock.try_acquire(format!("{name}@{scheduled_time}"), hostname(), JOB_LOCK_TTL).await {
+        Ok(true) => true,
+        Ok(false) => {
+            console!("job is locked by other instance, skip execution, name={name}, scheduled_time={scheduled_time}");
+            false
+        }
+        Err(e) => {
+            console!("WARN failed to acquire job lock, skip execution, name={name}, error={e}");
+            false
+        }
+    }
+}
+
+fn process_job<S, J, Fut>(
+    job: J,
+    state: S,
+    context: JobContext,
+    execution: Execution,
+) -> Pin<Box<dyn Future<Output = ()> + Send>>
 where
-    S: Send + 'static,
+    S: Clone + Send + 'static,
     J: Fn(S, JobContext) -> Fut + Send + 'static,
     Fut: Future<Output = Result<(), Exception>> + Send + 'static,
 {
     let ref_id = current_action_id().map(|id| vec![id]);
+    let correlation_id = current_correlation_id();
     let triggered = ref_id.is_some();
     Box::pin(
         log::action("job", ref_id, async move {
@@ -141,11 +383,177 @@ where
                 scheduled_time = context.scheduled_time.to_rfc3339_opts(SecondsFormat::Millis, true),
                 fn = type_name::<J>()
             );
+            if let Some(correlation_id) = correlation_id {
+                context!(correlation_id = correlation_id);
+            }
             if triggered {
                 warn!(error_code = "MANUAL_OPERATION", "trigger job manually");
             }
-            job(state, context).await
+            let Execution { cancel, timeout, retry, history } = execution;
+            let scheduled_time = context.scheduled_time;
+            let start_time = Utc::now();
+            let run = async move {
+                tokio::select! {
+                    result = with_retry(retry, "job_retries", move || job(state.clone(), context.clone())) => result,
+                    () = cancel.cancelled() => Err(exception!(
+                        "job cancelled by next execution",
+                        severity = Severity::Warn,
+                        code = "JOB_CANCELLED"
+                    )),
+                }
+            };
+            let result = match timeout {
+                Some(timeout) => time::timeout(timeout, run).await.unwrap_or_else(|_| {
+                    Err(exception!(format!("job timed out, timeout={timeout:?}"), code = "JOB_TIMEOUT"))
+                }),
+                None => run.await,
+            };
+            let error = result.as_ref().err();
+            record_execution(
+                &history,
+                JobExecution {
+                    action_id: current_action_id(),
+                    scheduled_time,
+                    start_time,
+                    end_time: Utc::now(),
+                    result: match error.map(|e| e.severity) {
+                        None => "OK",
+                        Some(Severity::Warn) => "WARN",
+                        Some(Severity::Error) => "ERROR",
+                    },
+                    error_code: error.and_then(|e| e.code),
+                    error_message: error.map(|e| e.message.clone()),
+                },
+            );
+            result
         })
         .map(drop), // start_action handled error with logging
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::Mutex;
+    use std::sync::atomic::AtomicU32;
+    use std::sync::atomic::Ordering;
+    use std::time::Duration;
+
+    use chrono::FixedOffset;
+    use chrono::Utc;
+    use tokio::time;
+
+    use super::JobContext;
+    use super::MAX_HISTORY;
+    use super::OverlapPolicy;
+    use super::Scheduler;
+    use super::acquire_lock;
+    use super::lock::MemoryJobLock;
+    use crate::exception::Exception;
+    use crate::retry::RetryPolicy;
+    use crate::task::TaskExecutor;
+
+    async fn slow_job(finished: Arc<AtomicU32>, _context: JobContext) -> Result<(), Exception> {
+        time::sleep(Duration::from_millis(50)).await;
+        finished.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    async fn hung_job(_state: Arc<AtomicU32>, _context: JobContext) -> Result<(), Exception> {
+        time::sleep(Duration::from_hours(1)).await;
+        Ok(())
+    }
+
+    async fn flaky_job(attempts: Arc<AtomicU32>, _context: JobContext) -> Result<(), Exception> {
+        if attempts.fetch_add(1, Ordering::Relaxed) == 0 {
+            return Err(exception!("elasticsearch is unavailable"));
+        }
+        Ok(())
+    }
+
+    async fn run_twice(policy: OverlapPolicy) -> (Vec<bool>, u32) {
+        let mut scheduler = Scheduler::new(FixedOffset::east_opt(0).unwrap());
+        scheduler.schedule_fixed_rate("slow_job", slow_job, Duration::from_mins(1));
+        scheduler.overlap_policy("slow_job", policy);
+        let finished = Arc::new(AtomicU32::new(0));
+        let executor = Mutex::new(TaskExecutor::default());
+        let schedule = &scheduler.schedules[0];
+        let spawn = || {
+            let context = JobContext { name: schedule.name, scheduled_time: Utc::now() };
+            schedule.spawn(&executor, "job:slow_job".to_owned(), Arc::clone(&finished), context)
+        };
+        let spawned = vec![spawn(), spawn()];
+        executor.into_inner().unwrap().shutdown(Duration::from_secs(1)).await;
+        (spawned, finished.load(Ordering::Relaxed))
+    }
+
+    #[tokio::test]
+    async fn overlap_policy() {
+        assert_eq!(run_twice(OverlapPolicy::Allow).await, (vec![true, true], 2));
+        assert_eq!(run_twice(OverlapPolicy::Skip).await, (vec![true, false], 1));
+        assert_eq!(run_twice(OverlapPolicy::Queue).await, (vec![true, true], 2));
+        assert_eq!(run_twice(OverlapPolicy::CancelPrevious).await, (vec![true, true], 1));
+    }
+
+    #[tokio::test]
+    async fn timeout() {
+        let mut scheduler = Scheduler::new(FixedOffset::east_opt(0).unwrap());
+        scheduler.schedule_fixed_rate("hung_job", hung_job, Duration::from_mins(1));
+        scheduler.timeout("hung_job", Duration::from_millis(10));
+        let executor = Mutex::new(TaskExecutor::default());
+        let schedule = &scheduler.schedules[0];
+        let context = JobContext { name: schedule.name, scheduled_time: Utc::now() };
+        schedule.spawn(&executor, "job:hung_job".to_owned(), Arc::new(AtomicU32::new(0)), context);
+        assert_eq!(executor.into_inner().unwrap().shutdown(Duration::from_secs(1)).await, None);
+
+        let executions = scheduler.history().executions("hung_job").unwrap();
+        assert_eq!(executions.len(), 1);
+        assert_eq!(executions[0].result, "ERROR");
+        assert_eq!(executions[0].error_code, Some("JOB_TIMEOUT"));
+    }
+
+    #[tokio::test]
+    async fn retry() {
+        let mut scheduler = Scheduler::new(FixedOffset::east_opt(0).unwrap());
+        scheduler.schedule_fixed_rate("flaky_job", flaky_job, Duration::from_mins(1));
+        let policy =
+            RetryPolicy { max_attempts: 3, initial_backoff: Duration::from_millis(1), ..RetryPolicy::default() };
+        scheduler.retry("flaky_job", policy);
+        let attempts = Arc::new(AtomicU32::new(0));
+        let executor = Mutex::new(TaskExecutor::default());
+        let schedule = &scheduler.schedules[0];
+        let context = JobContext { name: schedule.name, scheduled_time: Utc::now() };
+        schedule.spawn(&executor, "job:flaky_job".to_owned(), Arc::clone(&attempts), context);
+        executor.into_inner().unwrap().shutdown(Duration::from_secs(1)).await;
+        assert_eq!(attempts.load(Ordering::Relaxed), 2);
+    }
+
+    #[tokio::test]
+    async fn history() {
+        let mut scheduler = Scheduler::new(FixedOffset::east_opt(0).unwrap());
+        scheduler.schedule_fixed_rate("flaky_job", flaky_job, Duration::from_mins(1));
+        let history = scheduler.history();
+        let attempts = Arc::new(AtomicU32::new(0));
+        let executor = Mutex::new(TaskExecutor::default());
+        let schedule = &scheduler.schedules[0];
+        for _ in 0..=MAX_HISTORY {
+            let context = JobContext { name: schedule.name, scheduled_time: Utc::now() };
+            schedule.spawn(&executor, "job:flaky_job".to_owned(), Arc::clone(&attempts), context);
+        }
+        executor.into_inner().unwrap().shutdown(Duration::from_secs(1)).await;
+
+        let executions = history.executions("flaky_job").unwrap();
+        assert_eq!(executions.len(), MAX_HISTORY);
+        // first failed execution is evicted
+        assert!(executions.iter().all(|execution| execution.result == "OK"));
+        assert!(history.executions("not_found").is_none());
+    }
+
+    #[tokio::test]
+    async fn lock_scheduled_time() {
+        let lock = MemoryJobLock::default();
+        assert!(acquire_lock(&lock, "daily_job", "2026-05-13T01:00:00Z").await);
+        assert!(!acquire_lock(&lock, "daily_job", "2026-05-13T01:00:00Z").await);
+        assert!(acquire_lock(&lock, "daily_job", "2026-05-14T01:00:00Z").await);
+    }
+}
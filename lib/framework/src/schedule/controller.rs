@@ -1,22 +1,28 @@
 use std::collections::HashMap;
 use std::sync::Arc;
 use std::sync::Mutex;
+use std::sync::atomic::Ordering;
 
 use axum::Router;
 use axum::extract::Path;
 use axum::extract::State;
-use axum::routing::put;
+use axum::routing::get;
 use chrono::SecondsFormat;
 use chrono::Utc;
 use http::StatusCode;
+use serde::Deserialize;
+use serde::Serialize;
 
+use crate::exception::Exception;
 use crate::exception::Severity;
 use crate::exception::error_code;
 use crate::schedule::JobContext;
+use crate::schedule::JobExecution;
 use crate::schedule::Schedule;
 use crate::schedule::Scheduler;
 use crate::task::TaskExecutor;
 use crate::web::SystemRoute;
+use crate::web::body::Json;
 use crate::web::error::HttpResult;
 
 #[derive(Clone)]
@@ -26,21 +32,109 @@ struct JobState<S> {
     executor: Arc<Mutex<TaskExecutor>>,
 }
 
+#[derive(Debug, Serialize)]
+struct JobView {
+    name: &'static str,
+    trigger: String,
+    paused: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct JobRequest {
+    operation: JobOperation,
+}
+
+#[derive(Debug, Deserialize)]
+enum JobOperation {
+    #[serde(rename = "TRIGGER")]
+    Trigger,
+    #[serde(rename = "PAUSE")]
+    Pause,
+    #[serde(rename = "RESUME")]
+    Resume,
+}
+
+async fn list_jobs<S>(State(state): State<JobState<S>>) -> Json<Vec<JobView>> {
+    let mut jobs: Vec<JobView> = state
+        .schedules
+        .values()
+        .map(|schedule| JobView {
+            name: schedule.name,
+            trigger: schedule.trigger.to_string(),
+            paused: schedule.paused.load(Ordering::Relaxed),
+        })
+        .collect();
+    jobs.sort_by_key(|job| job.name);
+    Json(jobs)
+}
+
+// latest first
+async fn job_history<S>(
+    State(state): State<JobState<S>>,
+    Path(job): Path<String>,
+) -> HttpResult<Json<Vec<JobExecution>>> {
+    let schedule = schedule(&state, &job)?;
+    Ok(Json(schedule.history.lock().unwrap().iter().rev().cloned().collect()))
+}
+
+// e.g. {"operation": "PAUSE"}, paused job can still be triggered manually,
+// pause state is local to this instance, with multiple replicas and Scheduler::job_lock(), other replicas still run job
+async fn update_job<S>(
+    State(state): State<JobState<S>>,
+    Path(job): Path<String>,
+    Json(request): Json<JobRequest>,
+) -> HttpResult<StatusCode>
+where
+    S: Clone,
+{
+    match request.operation {
+        JobOperation::Trigger => trigger_job(&state, &job)?,
+        JobOperation::Pause => {
+            schedule(&state, &job)?.paused.store(true, Ordering::Relaxed);
+            warn!(error_code = "MANUAL_OPERATION", "pause job, name={job}");
+        }
+        JobOperation::Resume => {
+            schedule(&state, &job)?.paused.store(false, Ordering::Relaxed);
+            warn!(error_code = "MANUAL_OPERATION", "resume job, name={job}");
+        }
+    }
+    Ok(StatusCode::ACCEPTED)
+}
+
 async fn run_job<S>(State(state): State<JobState<S>>, Path(job): Path<String>) -> HttpResult<StatusCode>
 where
     S: Clone,
 {
-    let schedule = state.schedules.get(job.as_str()).ok_or_else(|| {
-        exception!(format!("job not found, name={job}"), severity = Severity::Warn, code = error_code::NOT_FOUND)
-    })?;
-    let context = JobContext { name: schedule.name, scheduled_time: Utc::now() };
-    state.executor.lock().unwrap().spawn(
-        format!("job:{job}@{}", context.scheduled_time.to_rfc3339_opts(SecondsFormat::Millis, true)),
-        (schedule.job)(state.state.clone(), context),
-    );
+    trigger_job(&state, &job)?;
     Ok(StatusCode::ACCEPTED)
 }
 
+fn trigger_job<S>(state: &JobState<S>, job: &str) -> Result<(), Exception>
+where
+    S: Clone,
+{
+    let schedule = schedule(state, job)?;
+    let context = JobContext { name: schedule.name, scheduled_time: Utc::now() };
+    let task_name = format!("job:{job}@{}", context.scheduled_time.to_rfc3339_opts(SecondsFormat::Millis, true));
+    if !schedule.spawn(&state.executor, task_name, state.state.clone(), context) {
+        return Err(exception!(
+            format!("job is still running, name={job}"),
+            severity = Severity::Warn,
+            code = error_code::CONFLICT
+        ));
+    }
+    Ok(())
+}
+
+fn schedule<'a, S>(state: &'a JobState<S>, job: &str) -> Result<&'a Schedule<S>, Exception> {
+    state.schedules.get(job).map(AsRef::as_ref).ok_or_else(|| {
+        exception!(format!("job not found, name={job}"), severity = Severity::Warn, code = error_code::NOT_FOUND)
+    })
+}
+
+// GET /_sys/job lists jobs, GET /_sys/job/{job} lists recent executions,
+// POST /_sys/job/{job} triggers, pauses or resumes job, PUT /_sys/job/{job} triggers job,
+// restrict with ip_access for internal use
 impl<S> SystemRoute<S> for Scheduler<S>
 where
     S: Clone + Send + Sync + 'static,
@@ -48,10 +142,9 @@ where
     fn routes(&self, state: S) -> Router {
         let jobs: HashMap<&'static str, Arc<Schedule<S>>> =
             self.schedules.iter().map(|schedule| (schedule.name, Arc::clone(schedule))).collect();
-        Router::new().route("/_sys/job/{job}", put(run_job)).with_state(JobState {
-            state,
-            schedules: Arc::new(jobs),
-            executor: Arc::clone(&self.executor),
-        })
+        Router::new()
+            .route("/_sys/job", get(list_jobs))
+            .route("/_sys/job/{job}", get(job_history).put(run_job).post(update_job))
+            .with_state(JobState { state, schedules: Arc::new(jobs), executor: Arc::clone(&self.executor) })
     }
 }
@@ -0,0 +1,203 @@
+use chrono::DateTime;
+use chrono::Datelike as _;
+use chrono::FixedOffset;
+use chrono::NaiveDate;
+use chrono::NaiveTime;
+use chrono::TimeDelta;
+use chrono::TimeZone as _;
+use chrono::Timelike as _;
+
+use crate::exception::Exception;
+
+const MONTHS: [&str; 12] = ["JAN", "FEB", "MAR", "APR", "MAY", "JUN", "JUL", "AUG", "SEP", "OCT", "NOV", "DEC"];
+const WEEKDAYS: [&str; 7] = ["SUN", "MON", "TUE", "WED", "THU", "FRI", "SAT"];
+// covers leap day, e.g. "0 0 0 29 2 *"
+const MAX_SEARCH_DAYS: u32 = 366 * 8;
+
+// "second minute hour day_of_month month day_of_week", e.g. "0 30 4 * * MON-FRI", "0 */15 9-18 * * *",
+// each field supports *, ?, list (1,15), range (9-18) and step (*/15, 0-30/10), month and weekday accept names,
+// if both day_of_month and day_of_week are restricted, either matches, same as standard cron
+#[derive(Debug)]
+pub(super) struct Cron {
+    pub(super) expression: String,
+    seconds: u64,
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    day_restricted: bool,
+    weekday_restricted: bool,
+}
+
+impl Cron {
+    pub(super) fn parse(expression: &str) -> Result<Self, Exception> {
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        let [seconds, minutes, hours, days, months, weekdays] = fields.as_slice() else {
+            return Err(exception!(format!("cron expression must have 6 fields, expression={expression}")));
+        };
+        let parse = |field: &str, min: u32, max: u32, names: &[&str]| {
+            parse_field(field, min, max, names)
+                .ok_or_else(|| exception!(format!("invalid cron field, field={field}, expression={expression}")))
+        };
+        let mut weekdays_bits = parse(weekdays, 0, 7, &WEEKDAYS)?;
+        // both 0 and 7 are sunday
+        if weekdays_bits & (1 << 7) != 0 {
+            weekdays_bits |= 1;
+        }
+        Ok(Self {
+            expression: expression.to_owned(),
+            seconds: parse(seconds, 0, 59, &[])?,
+            minutes: parse(minutes, 0, 59, &[])?,
+            hours: parse(hours, 0, 23, &[])?,
+            days: parse(days, 1, 31, &[])?,
+            months: parse(months, 1, 12, &MONTHS)?,
+            weekdays: weekdays_bits,
+            day_restricted: !matches!(*days, "*" | "?"),
+            weekday_restricted: !matches!(*weekdays, "*" | "?"),
+        })
+    }
+
+    // returns none if expression never matches, e.g. "0 0 0 31 2 *"
+    pub(super) fn next(&self, previous: DateTime<FixedOffset>) -> Option<DateTime<FixedOffset>> {
+        let start = previous.naive_local().with_nanosecond(0)? + TimeDelta::seconds(1);
+        let mut date = start.date();
+        for _ in 0..MAX_SEARCH_DAYS {
+            if self.matches_date(date) {
+                let from = if date == start.date() { start.time() } else { NaiveTime::MIN };
+                if let Some(time) = self.next_time(from) {
+                    return previous.timezone().from_local_datetime(&date.and_time(time)).single();
+                }
+            }
+            date = date.succ_opt()?;
+        }
+        None
+    }
+
+    fn matches_date(&self, date: NaiveDate) -> bool {
+        if !contains(self.months, date.month()) {
+            return false;
+        }
+        let day = contains(self.days, date.day());
+        let weekday = contains(self.weekdays, date.weekday().num_days_from_sunday());
+        match (self.day_restricted, self.weekday_restricted) {
+            (true, true) => day || weekday,
+            (true, false) => day,
+            (false, true) => weekday,
+            (false, false) => true,
+        }
+    }
+
+    fn next_time(&self, from: NaiveTime) -> Option<NaiveTime> {
+        for hour in (from.hour()..24).filter(|hour| contains(self.hours, *hour)) {
+            let from_minute = if hour == from.hour() { from.minute() } else { 0 };
+            for minute in (from_minute..60).filter(|minute| contains(self.minutes, *minute)) {
+                let from_second = if hour == from.hour() && minute == from.minute() { from.second() } else { 0 };
+                if let Some(second) = (from_second..60).find(|second| contains(self.seconds, *second)) {
+                    return NaiveTime::from_hms_opt(hour, minute, second);
+                }
+            }
+        }
+        None
+    }
+}
+
+const fn contains(bits: u64, value: u32) -> bool {
+    bits & (1 << value) != 0
+}
+
+fn parse_field(field: &str, min: u32, max: u32, names: &[&str]) -> Option<u64> {
+    let mut bits = 0;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().ok().filter(|step| *step > 0)?),
+            None => (part, 1),
+        };
+        let (start, end) = if range == "*" || range == "?" {
+            (min, max)
+        } else if let Some((start, end)) = range.split_once('-') {
+            (parse_value(start, min, names)?, parse_value(end, min, names)?)
+        } else {
+            let start = parse_value(range, min, names)?;
+            // e.g. 5/10 means from 5 to max every 10
+            (start, if part.contains('/') { max } else { start })
+        };
+        if start < min || end > max || start > end {
+            return None;
+        }
+        for value in (start..=end).step_by(step as usize) {
+            bits |= 1 << value;
+        }
+    }
+    Some(bits)
+}
+
+fn parse_value(value: &str, min: u32, names: &[&str]) -> Option<u32> {
+    if let Some(index) = names.iter().position(|name| name.eq_ignore_ascii_case(value)) {
+        return u32::try_from(index).ok().map(|index| index + min);
+    }
+    value.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::DateTime;
+    use chrono::FixedOffset;
+    use chrono::TimeZone as _;
+
+    use super::Cron;
+
+    fn time(value: &str) -> DateTime<FixedOffset> {
+        DateTime::parse_from_rfc3339(value).unwrap()
+    }
+
+    #[test]
+    fn weekdays() {
+        let cron = Cron::parse("0 30 4 * * MON-FRI").unwrap();
+        // 2026-05-15 is friday
+        assert_eq!(cron.next(time("2026-05-15T04:00:00+08:00")), Some(time("2026-05-15T04:30:00+08:00")));
+        assert_eq!(cron.next(time("2026-05-15T04:30:00+08:00")), Some(time("2026-05-18T04:30:00+08:00")));
+    }
+
+    #[test]
+    fn step_within_hours() {
+        let cron = Cron::parse("0 */15 9-18 * * *").unwrap();
+        assert_eq!(cron.next(time("2026-05-13T08:59:59Z")), Some(time("2026-05-13T09:00:00Z")));
+        assert_eq!(cron.next(time("2026-05-13T09:00:00Z")), Some(time("2026-05-13T09:15:00Z")));
+        assert_eq!(cron.next(time("2026-05-13T18:45:00Z")), Some(time("2026-05-14T09:00:00Z")));
+    }
+
+    #[test]
+    fn day_of_month_or_weekday() {
+        // 1st of month or sunday
+        let cron = Cron::parse("0 0 0 1 * 0").unwrap();
+        // 2026-05-13 is wednesday
+        assert_eq!(cron.next(time("2026-05-13T00:00:00Z")), Some(time("2026-05-17T00:00:00Z")));
+        assert_eq!(cron.next(time("2026-05-31T00:00:00Z")), Some(time("2026-06-01T00:00:00Z")));
+
+        let sunday = Cron::parse("0 0 12 ? * 7").unwrap();
+        assert_eq!(sunday.next(time("2026-05-13T00:00:00Z")), Some(time("2026-05-17T12:00:00Z")));
+    }
+
+    #[test]
+    fn lists_and_names() {
+        let cron = Cron::parse("30 5,35 1 15 jan,Jul *").unwrap();
+        assert_eq!(cron.next(time("2026-05-13T00:00:00Z")), Some(time("2026-07-15T01:05:30Z")));
+        assert_eq!(cron.next(time("2026-07-15T01:05:30Z")), Some(time("2026-07-15T01:35:30Z")));
+    }
+
+    #[test]
+    fn never_matches() {
+        let cron = Cron::parse("0 0 0 31 2 *").unwrap();
+        assert_eq!(cron.next(FixedOffset::east_opt(0).unwrap().with_ymd_and_hms(2026, 1, 1, 0, 0, 0).unwrap()), None);
+    }
+
+    #[test]
+    fn invalid_expression() {
+        Cron::parse("0 30 4 * *").unwrap_err();
+        Cron::parse("0 60 4 * * *").unwrap_err();
+        Cron::parse("0 30 4 * * MON-XYZ").unwrap_err();
+        Cron::parse("0 */0 4 * * *").unwrap_err();
+        Cron::parse("0 30 18-9 * * *").unwrap_err();
+    }
+}
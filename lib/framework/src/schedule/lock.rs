@@ -0,0 +1,66 @@
+use std::collections::HashMap;
+use std::future;
+use std::pin::Pin;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
+
+use crate::exception::Exception;
+
+// acquired by Scheduler before running triggered job, so same app deployed with multiple replicas runs job once,
+// key is unique per job and scheduled time, e.g. "cleanup_job@2026-05-13T01:00:00Z",
+// implementation must be atomic across replicas, e.g. redis "SET key owner NX PX ttl" or insert into unique keyed table,
+// lock is never released, it only needs to outlive clock skew between replicas before it expires
+pub trait JobLock: Send + Sync {
+    // returns false if acquired by other owner
+    fn try_acquire(
+        &self,
+        key: String,
+        owner: &'static str,
+        ttl: Duration,
+    ) -> Pin<Box<dyn Future<Output = Result<bool, Exception>> + Send>>;
+}
+
+// for single instance or tests
+#[derive(Default)]
+pub struct MemoryJobLock {
+    locks: Mutex<HashMap<String, Instant>>,
+}
+
+impl JobLock for MemoryJobLock {
+    fn try_acquire(
+        &self,
+        key: String,
+        _owner: &'static str,
+        ttl: Duration,
+    ) -> Pin<Box<dyn Future<Output = Result<bool, Exception>> + Send>> {
+        let now = Instant::now();
+        let mut locks = self.locks.lock().unwrap();
+        locks.retain(|_, expiration| *expiration > now);
+        let acquired = !locks.contains_key(&key);
+        if acquired {
+            locks.insert(key, now + ttl);
+        }
+        Box::pin(future::ready(Ok(acquired)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::JobLock as _;
+    use super::MemoryJobLock;
+
+    #[tokio::test]
+    async fn acquire_once() {
+        let lock = MemoryJobLock::default();
+        let ttl = Duration::from_mins(10);
+        assert!(lock.try_acquire("job@2026-05-13T01:00:00Z".to_owned(), "host-1", ttl).await.unwrap());
+        assert!(!lock.try_acquire("job@2026-05-13T01:00:00Z".to_owned(), "host-2", ttl).await.unwrap());
+        assert!(lock.try_acquire("job@2026-05-14T01:00:00Z".to_owned(), "host-2", ttl).await.unwrap());
+
+        assert!(lock.try_acquire("expired".to_owned(), "host-1", Duration::ZERO).await.unwrap());
+        assert!(lock.try_acquire("expired".to_owned(), "host-2", ttl).await.unwrap());
+    }
+}
@@ -1,14 +1,26 @@
+use std::fmt;
 use std::time::Duration;
 
 use chrono::DateTime;
+use chrono::Datelike as _;
 use chrono::FixedOffset;
+use chrono::Months;
+use chrono::NaiveDate;
 use chrono::NaiveTime;
 use chrono::TimeDelta;
+use chrono::TimeZone as _;
 use chrono::Utc;
+use chrono::Weekday;
+
+use crate::schedule::cron::Cron;
 
 pub(super) enum Trigger {
     FixedRate(Duration),
     Daily { time_zone: FixedOffset, time: NaiveTime },
+    Weekly { time_zone: FixedOffset, weekday: Weekday, time: NaiveTime },
+    // day_of_month is clamped to last day of shorter months, e.g. 31 runs on 30th of april
+    Monthly { time_zone: FixedOffset, day_of_month: u32, time: NaiveTime },
+    Cron { time_zone: FixedOffset, cron: Cron },
 }
 
 const INITIAL_DELAY: chrono::Duration = chrono::Duration::seconds(3);
@@ -31,10 +43,54 @@ impl Trigger {
                     next_time.checked_add_signed(TimeDelta::days(1)).expect("result cannot be out of range").to_utc()
                 }
             }
+            Self::Weekly { time_zone, weekday, time } => {
+                let local = previous.with_timezone(time_zone);
+                let days = (7 + weekday.num_days_from_monday() - local.weekday().num_days_from_monday()) % 7;
+                let date = local.date_naive() + TimeDelta::days(i64::from(days));
+                let next_time = at(*time_zone, date, *time);
+                if next_time > previous { next_time } else { at(*time_zone, date + TimeDelta::days(7), *time) }
+            }
+            Self::Monthly { time_zone, day_of_month, time } => {
+                let local = previous.with_timezone(time_zone);
+                let month = local.date_naive().with_day(1).expect("first day of month must be valid");
+                let next_time = at(*time_zone, day_in_month(month, *day_of_month), *time);
+                if next_time > previous {
+                    next_time
+                } else {
+                    at(*time_zone, day_in_month(month + Months::new(1), *day_of_month), *time)
+                }
+            }
+            Self::Cron { time_zone, cron } => cron
+                .next(previous.with_timezone(time_zone))
+                .expect("cron expression was validated on schedule")
+                .to_utc(),
+        }
+    }
+}
+
+// e.g. "daily@01:00:00", shown by GET /_sys/job
+impl fmt::Display for Trigger {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::FixedRate(interval) => write!(f, "fixed_rate@{}s", interval.as_secs()),
+            Self::Daily { time, .. } => write!(f, "daily@{time}"),
+            Self::Weekly { weekday, time, .. } => write!(f, "weekly@{weekday}/{time}"),
+            Self::Monthly { day_of_month, time, .. } => write!(f, "monthly@{day_of_month}/{time}"),
+            Self::Cron { cron, .. } => write!(f, "cron@{}", cron.expression),
         }
     }
 }
 
+fn at(time_zone: FixedOffset, date: NaiveDate, time: NaiveTime) -> DateTime<Utc> {
+    time_zone.from_local_datetime(&date.and_time(time)).unwrap().to_utc()
+}
+
+// month is first day of month
+fn day_in_month(month: NaiveDate, day_of_month: u32) -> NaiveDate {
+    let day = day_of_month.min(u32::from(month.num_days_in_month()));
+    month.with_day(day).expect("day must be within month")
+}
+
 #[cfg(test)]
 mod tests {
     use std::time::Duration;
@@ -43,9 +99,11 @@ mod tests {
     use chrono::NaiveTime;
     use chrono::TimeZone as _;
     use chrono::Utc;
+    use chrono::Weekday;
 
     use super::INITIAL_DELAY;
     use super::Trigger;
+    use crate::schedule::cron::Cron;
 
     #[test]
     fn fixed_rate_first_returns_previous() {
@@ -95,4 +153,59 @@ mod tests {
         let expected = Utc.with_ymd_and_hms(2026, 5, 13, 1, 0, 0).unwrap();
         assert_eq!(trigger.next(previous, false), expected);
     }
+
+    #[test]
+    fn cron_respects_timezone() {
+        // 04:30 in +08:00 = 20:30 UTC of previous day
+        let trigger = Trigger::Cron {
+            time_zone: FixedOffset::east_opt(8 * 3600).unwrap(),
+            cron: Cron::parse("0 30 4 * * *").unwrap(),
+        };
+        let previous = Utc.with_ymd_and_hms(2026, 5, 13, 10, 0, 0).unwrap();
+        let expected = Utc.with_ymd_and_hms(2026, 5, 13, 20, 30, 0).unwrap();
+        assert_eq!(trigger.next(previous, false), expected);
+    }
+
+    #[test]
+    fn weekly() {
+        let trigger = Trigger::Weekly {
+            time_zone: FixedOffset::east_opt(0).unwrap(),
+            weekday: Weekday::Mon,
+            time: NaiveTime::from_hms_opt(9, 0, 0).unwrap(),
+        };
+        // 2026-05-13 is wednesday
+        let wednesday = Utc.with_ymd_and_hms(2026, 5, 13, 10, 0, 0).unwrap();
+        assert_eq!(trigger.next(wednesday, false), Utc.with_ymd_and_hms(2026, 5, 18, 9, 0, 0).unwrap());
+        let monday_before = Utc.with_ymd_and_hms(2026, 5, 18, 8, 0, 0).unwrap();
+        assert_eq!(trigger.next(monday_before, false), Utc.with_ymd_and_hms(2026, 5, 18, 9, 0, 0).unwrap());
+        let monday_at = Utc.with_ymd_and_hms(2026, 5, 18, 9, 0, 0).unwrap();
+        assert_eq!(trigger.next(monday_at, false), Utc.with_ymd_and_hms(2026, 5, 25, 9, 0, 0).unwrap());
+    }
+
+    #[test]
+    fn monthly_clamps_to_end_of_month() {
+        let trigger = Trigger::Monthly {
+            time_zone: FixedOffset::east_opt(0).unwrap(),
+            day_of_month: 31,
+            time: NaiveTime::from_hms_opt(2, 0, 0).unwrap(),
+        };
+        let january = Utc.with_ymd_and_hms(2026, 1, 31, 2, 0, 0).unwrap();
+        assert_eq!(trigger.next(january, false), Utc.with_ymd_and_hms(2026, 2, 28, 2, 0, 0).unwrap());
+        let february = Utc.with_ymd_and_hms(2026, 2, 28, 2, 0, 0).unwrap();
+        assert_eq!(trigger.next(february, false), Utc.with_ymd_and_hms(2026, 3, 31, 2, 0, 0).unwrap());
+        let april = Utc.with_ymd_and_hms(2026, 4, 15, 0, 0, 0).unwrap();
+        assert_eq!(trigger.next(april, false), Utc.with_ymd_and_hms(2026, 4, 30, 2, 0, 0).unwrap());
+    }
+
+    #[test]
+    fn display() {
+        let time_zone = FixedOffset::east_opt(0).unwrap();
+        let time = NaiveTime::from_hms_opt(1, 0, 0).unwrap();
+        assert_eq!(Trigger::FixedRate(Duration::from_mins(1)).to_string(), "fixed_rate@60s");
+        assert_eq!(Trigger::Daily { time_zone, time }.to_string(), "daily@01:00:00");
+        assert_eq!(Trigger::Weekly { time_zone, weekday: Weekday::Mon, time }.to_string(), "weekly@Mon/01:00:00");
+        assert_eq!(Trigger::Monthly { time_zone, day_of_month: 31, time }.to_string(), "monthly@31/01:00:00");
+        let cron = Cron::parse("0 30 4 * * MON-FRI").unwrap();
+        assert_eq!(Trigger::Cron { time_zone, cron }.to_string(), "cron@0 30 4 * * MON-FRI");
+    }
 }
@@ -4,7 +4,7 @@ use crate::exception::Exception;
 
 pub async fn run(command: &str) -> Result<String, Exception> {
     let _span = span!("shell");
-    let output = Command::new("sh").arg("-c").arg(command).output().await?;
+    let output = Command::new("sh").arg("-c").arg(command).kill_on_drop(true).output().await?;
     log!("status = {:?}", output.status.code());
     let stdout = String::from_utf8_lossy(&output.stdout);
     log!("stdout = {}", stdout);
@@ -13,6 +13,7 @@ use tokio::time;
 use crate::exception::Exception;
 use crate::log;
 use crate::log::current_action_id;
+use crate::log::current_correlation_id;
 use crate::log::metrics::Counter;
 use crate::log::metrics::Metrics;
 
@@ -79,6 +80,7 @@ where
 {
     let task_name = format!("task:{name}@{location}");
     let ref_id = current_action_id().map(|id| vec![id]);
+    let correlation_id = current_correlation_id();
 
     EXECUTOR.lock().unwrap().spawn(task_name, async move {
         let _counter = TASK_COUNTER.get().map(Counter::increase);
@@ -86,6 +88,9 @@ where
         // start_action logs the Exception on failure, so the Result can be discarded here
         let _result = log::action("task", ref_id, async {
             context!(task = name, location = location);
+            if let Some(correlation_id) = correlation_id {
+                context!(correlation_id = correlation_id);
+            }
             task.await
         })
         .await;
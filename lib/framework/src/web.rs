@@ -2,13 +2,29 @@ use axum::Router;
 use http::HeaderName;
 
 pub mod api;
+pub mod api_key;
 pub mod body;
 pub mod client_info;
+pub mod compression;
+pub mod cors;
+pub mod diagnostic;
 pub mod error;
+pub mod extract;
+pub mod health_check;
+pub mod ip_access;
+pub mod jwt;
+pub mod rate_limit;
 pub mod server;
+pub mod session;
+pub mod sse;
+pub mod static_files;
+pub mod websocket;
 
-const REF_ID: HeaderName = HeaderName::from_static("ref-id");
+pub(crate) const REF_ID: HeaderName = HeaderName::from_static("ref-id");
+pub(crate) const CORRELATION_ID: HeaderName = HeaderName::from_static("correlation-id");
 const CLIENT: HeaderName = HeaderName::from_static("client");
+pub(crate) const X_REQUEST_ID: HeaderName = HeaderName::from_static("x-request-id");
+pub(crate) const TRACEPARENT: HeaderName = HeaderName::from_static("traceparent");
 
 pub trait SystemRoute<S> {
     fn routes(&self, state: S) -> Router;
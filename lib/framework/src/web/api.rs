@@ -16,10 +16,8 @@ use crate::http::HttpClient;
 use crate::http::HttpRequest;
 use crate::http::HttpResponse;
 use crate::json;
-use crate::log::current_action_id;
 use crate::string::intern;
 use crate::web::CLIENT;
-use crate::web::REF_ID;
 use crate::web::body::Json;
 use crate::web::error::HttpError;
 
@@ -99,9 +97,6 @@ impl ApiClient {
     }
 
     fn link_context(&self, http_request: &mut HttpRequest) -> Result<(), Exception> {
-        if let Some(action_id) = current_action_id() {
-            http_request.header(REF_ID, &action_id)?;
-        }
         http_request.header(CLIENT, self.client)?;
         Ok(())
     }
@@ -117,11 +112,12 @@ where
             // SAFETY: We've verified Res is () via TypeId, so this transmute is sound.
             Ok(unsafe { transmute_copy(&()) })
         } else {
-            json::from_json(&response.body)
+            json::from_json(response.text()?)
         }
     } else if let Some(content_type) = response.headers.get(&header::CONTENT_TYPE)
         && content_type == "application/json"
-        && let Ok(error) = json::from_json::<ErrorResponse>(&response.body)
+        && let Ok(body) = response.text()
+        && let Ok(error) = json::from_json::<ErrorResponse>(body)
     {
         if let Some(ref code) = error.code {
             Err(exception!(
@@ -136,6 +132,9 @@ where
             ))
         }
     } else {
-        Err(exception!(format!("failed to call api, status={status}, body={}", response.body)))
+        Err(exception!(format!(
+            "failed to call api, status={status}, body={}",
+            String::from_utf8_lossy(&response.body)
+        )))
     }
 }
@@ -0,0 +1,44 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use axum::Router;
+use axum::extract::Request;
+use axum::extract::State;
+use axum::http::HeaderName;
+use axum::middleware;
+use axum::middleware::Next;
+use axum::response::IntoResponse as _;
+use axum::response::Response;
+
+use crate::exception::Severity;
+use crate::exception::error_code;
+use crate::web::error::HttpError;
+
+pub(crate) const X_API_KEY: HeaderName = HeaderName::from_static("x-api-key");
+
+pub struct ApiKeyConfig {
+    // api key -> identifier, identifier is recorded in action context, never log the key itself
+    pub keys: HashMap<String, String>,
+}
+
+pub fn api_key_auth<S>(router: Router<S>, config: ApiKeyConfig) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    router.layer(middleware::from_fn_with_state(Arc::new(config), api_key_layer))
+}
+
+async fn api_key_layer(State(config): State<Arc<ApiKeyConfig>>, request: Request, next: Next) -> Response {
+    let identifier =
+        request.headers().get(X_API_KEY).and_then(|value| value.to_str().ok()).and_then(|key| config.keys.get(key));
+    let Some(identifier) = identifier else {
+        return HttpError::from(exception!(
+            "invalid api key",
+            severity = Severity::Warn,
+            code = error_code::UNAUTHORIZED
+        ))
+        .into_response();
+    };
+    context!(api_key = identifier);
+    next.run(request).await
+}
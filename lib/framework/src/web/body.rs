@@ -1,15 +1,14 @@
 use std::fmt::Debug;
 use std::ops::Deref;
 
-use axum::extract;
 use axum::extract::FromRequest;
-use axum::extract::FromRequestParts;
 use axum::extract::Request;
+use axum::extract::rejection::StringRejection;
 use axum::http::HeaderValue;
+use axum::http::StatusCode;
 use axum::http::header;
 use axum::response::IntoResponse;
 use axum::response::Response;
-use http::request::Parts;
 use serde::Serialize;
 use serde::de::DeserializeOwned;
 
@@ -17,7 +16,11 @@ use crate::exception::Exception;
 use crate::exception::Severity;
 use crate::exception::error_code;
 use crate::json;
+use crate::validate::Validator;
 use crate::web::error::HttpError;
+// moved to web::extract, kept for existing imports
+pub use crate::web::extract::Query;
+pub use crate::web::extract::ValidatedQuery;
 
 pub struct TextBody(pub String);
 
@@ -42,15 +45,7 @@ where
                 log!("[request] body={body}");
                 Ok(TextBody(body))
             }
-            Err(rejection) => {
-                let error_message = rejection.body_text();
-                Err(exception!(
-                    format!("failed to read body, error={error_message}"),
-                    severity = Severity::Warn,
-                    code = error_code::BAD_REQUEST
-                )
-                .into())
-            }
+            Err(rejection) => Err(body_rejection(&rejection).into()),
         }
     }
 }
@@ -81,15 +76,7 @@ where
                     .into()),
                 }
             }
-            Err(rejection) => {
-                let error_message = rejection.body_text();
-                Err(exception!(
-                    format!("failed to read body, error={error_message}"),
-                    severity = Severity::Warn,
-                    code = error_code::BAD_REQUEST
-                )
-                .into())
-            }
+            Err(rejection) => Err(body_rejection(&rejection).into()),
         }
     }
 }
@@ -104,7 +91,6 @@ where
             Ok(body) => {
                 log!("[response] body={body}");
                 let length = body.len();
-                stats!(response_content_length = length);
                 (
                     [
                         (header::CONTENT_TYPE, HeaderValue::from_static("application/json")),
@@ -122,27 +108,30 @@ where
     }
 }
 
-pub struct Query<T>(pub T);
+// chunked body exceeding HttpServerConfig.max_body_size is rejected when reading
+fn body_rejection(rejection: &StringRejection) -> Exception {
+    let error_message = rejection.body_text();
+    let code = if rejection.status() == StatusCode::PAYLOAD_TOO_LARGE {
+        error_code::PAYLOAD_TOO_LARGE
+    } else {
+        error_code::BAD_REQUEST
+    };
+    exception!(format!("failed to read body, error={error_message}"), severity = Severity::Warn, code = code)
+}
+
+// parses json body and calls validate(), validation error is responded as 400
+pub struct ValidatedJson<T>(pub T);
 
-impl<T, S> FromRequestParts<S> for Query<T>
+impl<S, T> FromRequest<S> for ValidatedJson<T>
 where
-    T: DeserializeOwned,
     S: Send + Sync,
+    T: DeserializeOwned + Validator,
 {
     type Rejection = HttpError;
 
-    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
-        log!("[request] query={}", &parts.uri.query().unwrap_or_default());
-        let result = extract::Query::<T>::try_from_uri(&parts.uri);
-        match result {
-            Ok(extract::Query(query)) => Ok(Query(query)),
-            Err(rejection) => Err(exception!(
-                format!("failed to parse query"),
-                severity = Severity::Warn,
-                code = error_code::BAD_REQUEST,
-                source = rejection
-            )
-            .into()),
-        }
+    async fn from_request(request: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let Json(value) = Json::<T>::from_request(request, state).await?;
+        value.validate()?;
+        Ok(Self(value))
     }
 }
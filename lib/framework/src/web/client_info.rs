@@ -1,3 +1,4 @@
+use std::net::IpAddr;
 use std::net::SocketAddr;
 
 use axum::extract::ConnectInfo;
@@ -5,6 +6,8 @@ use axum::extract::Request;
 use axum::http::HeaderName;
 use axum::http::header;
 
+use crate::web::ip_access::Cidr;
+
 const X_FORWARDED_FOR: HeaderName = HeaderName::from_static("x-forwarded-for");
 
 #[derive(Debug)]
@@ -13,17 +16,29 @@ pub struct ClientInfo {
     pub user_agent: Option<String>,
 }
 
-pub(crate) fn client_info(request: &Request, max_forwarded_ips: usize) -> ClientInfo {
+// with trusted_proxies, x-forwarded-for is only honored if peer is trusted proxy, and trusted hops are skipped
+pub(crate) fn client_info(request: &Request, max_forwarded_ips: usize, trusted_proxies: &[Cidr]) -> ClientInfo {
     let user_agent =
         request.headers().get(header::USER_AGENT).map(|value| value.to_str().unwrap_or_default().to_owned());
 
+    let peer_ip = request.extensions().get::<ConnectInfo<SocketAddr>>().map(|connect_info| connect_info.0.ip());
+    let trust_forwarded =
+        trusted_proxies.is_empty() || peer_ip.is_none_or(|ip| trusted_proxies.iter().any(|cidr| cidr.contains(ip)));
+
     let mut client_ip: Option<String> = None;
     if max_forwarded_ips > 0
+        && trust_forwarded
         && let Some(x_forwarded_for) = request.headers().get(X_FORWARDED_FOR)
     {
-        client_ip = extract_client_ip(x_forwarded_for.to_str().unwrap_or_default(), max_forwarded_ips);
+        let x_forwarded_for = x_forwarded_for.to_str().unwrap_or_default();
+        client_ip = if trusted_proxies.is_empty() {
+            extract_client_ip(x_forwarded_for, max_forwarded_ips)
+        } else {
+            extract_untrusted_ip(x_forwarded_for, max_forwarded_ips, trusted_proxies)
+        };
     }
 
+    // connect info is not available for unix socket connections
     if client_ip.is_none()
         && let Some(connect_info) = request.extensions().get::<ConnectInfo<SocketAddr>>()
     {
@@ -63,6 +78,25 @@ fn extract_client_ip(x_forwarded_for: &str, max_forwarded_ips: usize) -> Option<
     extract_ip(node)
 }
 
+// from right to left, returns first ip not in trusted proxies, or the last checked one if all are trusted
+fn extract_untrusted_ip(x_forwarded_for: &str, max_forwarded_ips: usize, trusted_proxies: &[Cidr]) -> Option<String> {
+    let mut client_ip = None;
+    for node in x_forwarded_for.rsplit(',').take(max_forwarded_ips) {
+        let node = node.trim();
+        if node.is_empty() {
+            break;
+        }
+        let ip = extract_ip(node)?;
+        let trusted =
+            ip.parse::<IpAddr>().is_ok_and(|address| trusted_proxies.iter().any(|cidr| cidr.contains(address)));
+        if !trusted {
+            return Some(ip);
+        }
+        client_ip = Some(ip);
+    }
+    client_ip
+}
+
 // Check loosely to avoid unnecessary overhead, especially x-forwarded-for is extracted from right to left, where values are from trusted LB
 // ipv4 must have 3 dots and 1 optional colon, with hex chars
 // ipv6 must have only colons with hex chars
@@ -128,6 +162,15 @@ mod tests {
         assert_eq!(extract_client_ip("108.0.0.1, 10.10.10.10", 2), Some("108.0.0.1".to_owned()));
     }
 
+    #[test]
+    fn extract_untrusted_ip_with_trusted_proxies() {
+        let trusted: Vec<Cidr> = vec!["10.0.0.0/8".parse().unwrap()];
+        assert_eq!(extract_untrusted_ip("1.1.1.1, 108.0.0.1, 10.0.0.2", 3, &trusted), Some("108.0.0.1".to_owned()));
+        assert_eq!(extract_untrusted_ip("1.1.1.1, 10.0.0.3, 10.0.0.2", 3, &trusted), Some("1.1.1.1".to_owned()));
+        assert_eq!(extract_untrusted_ip("1.1.1.1, 10.0.0.3, 10.0.0.2", 2, &trusted), Some("10.0.0.3".to_owned()));
+        assert_eq!(extract_untrusted_ip("", 2, &trusted), None);
+    }
+
     #[test]
     fn extract_client_ip_with_more_than_limits() {
         assert_eq!(extract_client_ip("108.0.0.2, 108.0.0.1, 10.10.10.10", 2), Some("108.0.0.1".to_owned()));
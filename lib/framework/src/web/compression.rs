@@ -0,0 +1,166 @@
+use std::io::Write as _;
+use std::sync::Arc;
+
+use axum::Router;
+use axum::body;
+use axum::body::Body;
+use axum::body::HttpBody as _;
+use axum::extract::Request;
+use axum::extract::State;
+use axum::http::HeaderMap;
+use axum::http::HeaderValue;
+use axum::http::header;
+use axum::middleware;
+use axum::middleware::Next;
+use axum::response::IntoResponse as _;
+use axum::response::Response;
+use brotli::CompressorWriter;
+use flate2::Compression;
+use flate2::write::GzEncoder;
+
+use crate::exception::Exception;
+use crate::web::error::HttpError;
+
+pub struct CompressionConfig {
+    // responses smaller than min_size are sent as is
+    pub min_size: usize,
+    // content type prefixes to compress, e.g. "application/json", "text/"
+    pub content_types: Vec<&'static str>,
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        CompressionConfig { min_size: 1024, content_types: vec!["application/json", "text/"] }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Encoding {
+    Gzip,
+    Brotli,
+}
+
+impl Encoding {
+    const fn as_str(self) -> &'static str {
+        match self {
+            Encoding::Gzip => "gzip",
+            Encoding::Brotli => "br",
+        }
+    }
+}
+
+// only compresses responses with known size, streaming responses (e.g. sse) are not buffered
+pub fn compression<S>(router: Router<S>, config: CompressionConfig) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    router.layer(middleware::from_fn_with_state(Arc::new(config), compression_layer))
+}
+
+async fn compression_layer(State(config): State<Arc<CompressionConfig>>, request: Request, next: Next) -> Response {
+    let encoding = accept_encoding(request.headers());
+    let response = next.run(request).await;
+    let Some(encoding) = encoding else {
+        return response;
+    };
+    if !compressible(&config, &response) {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let result = async {
+        let body = body::to_bytes(body, usize::MAX).await?;
+        compress(encoding, &body)
+    }
+    .await;
+    match result {
+        Ok(compressed) => {
+            log!("[response] content_encoding={}, compressed_length={}", encoding.as_str(), compressed.len());
+            parts.headers.insert(header::CONTENT_ENCODING, HeaderValue::from_static(encoding.as_str()));
+            parts.headers.insert(header::CONTENT_LENGTH, HeaderValue::from(compressed.len()));
+            parts.headers.append(header::VARY, HeaderValue::from_static("accept-encoding"));
+            Response::from_parts(parts, Body::from(compressed))
+        }
+        Err(e) => HttpError::from(e).into_response(),
+    }
+}
+
+fn compressible(config: &CompressionConfig, response: &Response) -> bool {
+    if response.headers().contains_key(header::CONTENT_ENCODING) {
+        return false;
+    }
+    let content_type = response.headers().get(header::CONTENT_TYPE).and_then(|value| value.to_str().ok());
+    let Some(content_type) = content_type else {
+        return false;
+    };
+    if !config.content_types.iter().any(|prefix| content_type.starts_with(prefix)) {
+        return false;
+    }
+    response
+        .body()
+        .size_hint()
+        .exact()
+        .and_then(|size| usize::try_from(size).ok())
+        .is_some_and(|size| size >= config.min_size)
+}
+
+// prefers br over gzip, ignores encodings with q=0
+fn accept_encoding(headers: &HeaderMap) -> Option<Encoding> {
+    let value = headers.get(header::ACCEPT_ENCODING)?.to_str().ok()?;
+    let mut result = None;
+    for item in value.split(',') {
+        let mut params = item.split(';').map(str::trim);
+        let name = params.next().unwrap_or_default();
+        if params.any(|param| param.strip_prefix("q=").and_then(|q| q.parse::<f32>().ok()) == Some(0.0)) {
+            continue;
+        }
+        match name {
+            "br" => return Some(Encoding::Brotli),
+            "gzip" => result = Some(Encoding::Gzip),
+            _ => {}
+        }
+    }
+    result
+}
+
+fn compress(encoding: Encoding, body: &[u8]) -> Result<Vec<u8>, Exception> {
+    match encoding {
+        Encoding::Gzip => {
+            let mut encoder = GzEncoder::new(vec![], Compression::default());
+            encoder.write_all(body)?;
+            Ok(encoder.finish()?)
+        }
+        Encoding::Brotli => {
+            let mut compressed = vec![];
+            {
+                let mut writer = CompressorWriter::new(&mut compressed, 4096, 5, 22);
+                writer.write_all(body)?;
+            }
+            Ok(compressed)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::HeaderMap;
+    use axum::http::HeaderValue;
+    use axum::http::header;
+
+    use super::Encoding;
+
+    #[test]
+    fn accept_encoding() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(super::accept_encoding(&headers), None);
+
+        headers.insert(header::ACCEPT_ENCODING, HeaderValue::from_static("gzip, deflate, br"));
+        assert_eq!(super::accept_encoding(&headers), Some(Encoding::Brotli));
+
+        headers.insert(header::ACCEPT_ENCODING, HeaderValue::from_static("br;q=0, gzip;q=0.8"));
+        assert_eq!(super::accept_encoding(&headers), Some(Encoding::Gzip));
+
+        headers.insert(header::ACCEPT_ENCODING, HeaderValue::from_static("identity"));
+        assert_eq!(super::accept_encoding(&headers), None);
+    }
+}
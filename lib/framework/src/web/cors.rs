@@ -0,0 +1,154 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::Router;
+use axum::extract::Request;
+use axum::extract::State;
+use axum::http::HeaderMap;
+use axum::http::HeaderName;
+use axum::http::HeaderValue;
+use axum::http::Method;
+use axum::http::StatusCode;
+use axum::http::header;
+use axum::middleware;
+use axum::middleware::Next;
+use axum::response::IntoResponse as _;
+use axum::response::Response;
+
+use crate::exception::Severity;
+use crate::exception::error_code;
+use crate::web::error::HttpError;
+
+pub struct CorsConfig {
+    // exact origins, e.g. https://example.com, or "*" to allow any origin, "*" cannot be used with allow_credentials
+    pub allowed_origins: Vec<String>,
+    pub allowed_methods: Vec<Method>,
+    pub allowed_headers: Vec<HeaderName>,
+    pub allow_credentials: bool,
+    // how long browser can cache preflight result
+    pub max_age: Option<Duration>,
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        Self {
+            allowed_origins: vec![],
+            allowed_methods: vec![Method::GET, Method::POST, Method::PUT, Method::DELETE],
+            allowed_headers: vec![header::ACCEPT, header::CONTENT_TYPE],
+            allow_credentials: false,
+            max_age: None,
+        }
+    }
+}
+
+impl CorsConfig {
+    fn allow_origin(&self, origin: &HeaderValue) -> Option<HeaderValue> {
+        let value = origin.to_str().ok()?;
+        if self.allowed_origins.iter().any(|allowed| allowed == value) {
+            return Some(origin.clone());
+        }
+        if self.allows_any_origin() {
+            return Some(HeaderValue::from_static("*"));
+        }
+        None
+    }
+
+    fn allows_any_origin(&self) -> bool {
+        self.allowed_origins.iter().any(|allowed| allowed == "*")
+    }
+}
+
+pub fn cors<S>(router: Router<S>, config: CorsConfig) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    // echoing any origin with credentials allows every site to send requests with user's cookies
+    assert!(
+        !(config.allow_credentials && config.allows_any_origin()),
+        "allowed_origins must be explicit if allow_credentials is true"
+    ); // fail fast on startup
+    router.layer(middleware::from_fn_with_state(Arc::new(config), cors_layer))
+}
+
+async fn cors_layer(State(config): State<Arc<CorsConfig>>, request: Request, next: Next) -> Response {
+    let Some(origin) = request.headers().get(header::ORIGIN).cloned() else {
+        return next.run(request).await;
+    };
+    let allow_origin = config.allow_origin(&origin);
+    let preflight =
+        request.method() == Method::OPTIONS && request.headers().contains_key(header::ACCESS_CONTROL_REQUEST_METHOD);
+
+    if preflight {
+        let Some(allow_origin) = allow_origin else {
+            return HttpError::from(exception!(
+                format!("origin is not allowed, origin={origin:?}"),
+                severity = Severity::Warn,
+                code = error_code::FORBIDDEN
+            ))
+            .into_response();
+        };
+        let mut headers = HeaderMap::new();
+        insert_origin_headers(&mut headers, allow_origin, &config);
+        if let Ok(methods) = HeaderValue::from_str(&join(config.allowed_methods.iter().map(Method::as_str))) {
+            headers.insert(header::ACCESS_CONTROL_ALLOW_METHODS, methods);
+        }
+        if let Ok(allowed_headers) = HeaderValue::from_str(&join(config.allowed_headers.iter().map(HeaderName::as_str)))
+        {
+            headers.insert(header::ACCESS_CONTROL_ALLOW_HEADERS, allowed_headers);
+        }
+        if let Some(max_age) = config.max_age {
+            headers.insert(header::ACCESS_CONTROL_MAX_AGE, HeaderValue::from(max_age.as_secs()));
+        }
+        return (StatusCode::NO_CONTENT, headers).into_response();
+    }
+
+    let mut response = next.run(request).await;
+    if let Some(allow_origin) = allow_origin {
+        let headers = response.headers_mut();
+        insert_origin_headers(headers, allow_origin, &config);
+        // allow browser js to read action id for error reporting
+        headers.insert(header::ACCESS_CONTROL_EXPOSE_HEADERS, HeaderValue::from_static("x-request-id"));
+    }
+    response
+}
+
+fn insert_origin_headers(headers: &mut HeaderMap, allow_origin: HeaderValue, config: &CorsConfig) {
+    headers.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, allow_origin);
+    if config.allow_credentials {
+        headers.insert(header::ACCESS_CONTROL_ALLOW_CREDENTIALS, HeaderValue::from_static("true"));
+    }
+    headers.append(header::VARY, HeaderValue::from_static("origin"));
+}
+
+fn join<'a>(values: impl Iterator<Item = &'a str>) -> String {
+    values.collect::<Vec<_>>().join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::Router;
+    use axum::http::HeaderValue;
+
+    use super::CorsConfig;
+    use super::cors;
+
+    #[test]
+    fn allow_origin() {
+        let origin = HeaderValue::from_static("https://example.com");
+
+        let exact = CorsConfig { allowed_origins: vec!["https://example.com".to_owned()], ..CorsConfig::default() };
+        assert_eq!(exact.allow_origin(&origin), Some(origin.clone()));
+        assert_eq!(exact.allow_origin(&HeaderValue::from_static("https://other.com")), None);
+
+        let wildcard = CorsConfig { allowed_origins: vec!["*".to_owned()], ..CorsConfig::default() };
+        assert_eq!(wildcard.allow_origin(&origin), Some(HeaderValue::from_static("*")));
+    }
+
+    #[test]
+    #[should_panic(expected = "allowed_origins must be explicit")]
+    fn reject_wildcard_with_credentials() {
+        let config =
+            CorsConfig { allowed_origins: vec!["*".to_owned()], allow_credentials: true, ..CorsConfig::default() };
+        let _router: Router = cors(Router::new(), config);
+    }
+}
@@ -0,0 +1,16 @@
+use axum::Router;
+use axum::routing::get;
+
+use crate::log::active;
+use crate::log::active::ActiveActionView;
+use crate::web::body::Json;
+
+// GET /_sys/action lists actions not finished yet, longest running first, restrict with ip_access for internal use
+pub fn routes() -> Router {
+    active::enable();
+    Router::new().route("/_sys/action", get(active_actions))
+}
+
+async fn active_actions() -> Json<Vec<ActiveActionView>> {
+    Json(active::active_actions())
+}
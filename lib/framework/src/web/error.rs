@@ -1,6 +1,15 @@
+use std::sync::Arc;
+
+use axum::extract::Request;
+use axum::extract::State;
 use axum::http::StatusCode;
+use axum::http::header;
+use axum::middleware::Next;
 use axum::response::IntoResponse;
 use axum::response::Response;
+use serde_json::Map;
+use serde_json::Value;
+use serde_json::json;
 
 use crate::api::ErrorResponse;
 use crate::exception::Exception;
@@ -15,12 +24,73 @@ pub struct HttpError {
     body: ErrorResponse,
 }
 
+// customizes error response body, full error is always logged in action log
+pub struct ErrorResponsePolicy {
+    // replace message of 5xx responses, e.g. to hide internal details in production
+    pub hide_internal_message: bool,
+    pub include_error_code: bool,
+    // custom body e.g. to rename fields, overrides other options
+    pub format: Option<ErrorResponseFormat>,
+}
+
+pub type ErrorResponseFormat = Box<dyn Fn(StatusCode, &ErrorResponse) -> Value + Send + Sync>;
+
+impl Default for ErrorResponsePolicy {
+    fn default() -> Self {
+        Self { hide_internal_message: false, include_error_code: true, format: None }
+    }
+}
+
+impl ErrorResponsePolicy {
+    fn body(&self, status_code: StatusCode, error: &ErrorResponse) -> Value {
+        if let Some(ref format) = self.format {
+            return format(status_code, error);
+        }
+        let message = if self.hide_internal_message && status_code.is_server_error() {
+            "internal server error"
+        } else {
+            error.message.as_str()
+        };
+        let mut body = Map::new();
+        body.insert("severity".to_owned(), json!(error.severity));
+        if self.include_error_code
+            && let Some(ref code) = error.code
+        {
+            body.insert("code".to_owned(), Value::from(code.as_str()));
+        }
+        body.insert("message".to_owned(), Value::from(message));
+        Value::Object(body)
+    }
+}
+
+// attached to error response, so error_response_layer can rewrite body
+#[derive(Clone)]
+struct ErrorResponseExtension(ErrorResponse);
+
 impl IntoResponse for HttpError {
     fn into_response(self) -> Response {
-        (self.status_code, Json(self.body)).into_response()
+        let extension = ErrorResponseExtension(self.body.clone());
+        let mut response = (self.status_code, Json(self.body)).into_response();
+        response.extensions_mut().insert(extension);
+        response
     }
 }
 
+pub(crate) async fn error_response_layer(
+    State(policy): State<Arc<ErrorResponsePolicy>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let response = next.run(request).await;
+    let Some(ErrorResponseExtension(error)) = response.extensions().get::<ErrorResponseExtension>().cloned() else {
+        return response;
+    };
+    let (mut parts, _) = response.into_parts();
+    parts.headers.remove(header::CONTENT_LENGTH);
+    let body = Json(policy.body(parts.status, &error)).into_response();
+    (parts, body).into_response()
+}
+
 impl<E> From<E> for HttpError
 where
     E: Into<Exception>,
@@ -32,7 +102,12 @@ where
         let status_code = e.code.map_or(StatusCode::INTERNAL_SERVER_ERROR, |code| match code {
             error_code::BAD_REQUEST | error_code::VALIDATION_ERROR => StatusCode::BAD_REQUEST,
             error_code::NOT_FOUND => StatusCode::NOT_FOUND,
+            error_code::UNAUTHORIZED => StatusCode::UNAUTHORIZED,
             error_code::FORBIDDEN => StatusCode::FORBIDDEN,
+            error_code::CONFLICT => StatusCode::CONFLICT,
+            error_code::PAYLOAD_TOO_LARGE => StatusCode::PAYLOAD_TOO_LARGE,
+            error_code::TOO_MANY_REQUESTS => StatusCode::TOO_MANY_REQUESTS,
+            error_code::REQUEST_TIMEOUT => StatusCode::SERVICE_UNAVAILABLE,
             _ => StatusCode::INTERNAL_SERVER_ERROR,
         });
 
@@ -42,3 +117,44 @@ where
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use axum::http::StatusCode;
+    use serde_json::json;
+
+    use super::ErrorResponsePolicy;
+    use crate::api::ErrorResponse;
+    use crate::exception::Severity;
+
+    #[test]
+    fn policy_body() {
+        let error = ErrorResponse {
+            severity: Severity::Error,
+            code: Some("DB_ERROR".to_owned()),
+            message: "sql failed".to_owned(),
+        };
+
+        let policy = ErrorResponsePolicy::default();
+        assert_eq!(
+            policy.body(StatusCode::INTERNAL_SERVER_ERROR, &error),
+            json!({"severity": "ERROR", "code": "DB_ERROR", "message": "sql failed"})
+        );
+
+        let production = ErrorResponsePolicy { hide_internal_message: true, include_error_code: false, format: None };
+        assert_eq!(
+            production.body(StatusCode::INTERNAL_SERVER_ERROR, &error),
+            json!({"severity": "ERROR", "message": "internal server error"})
+        );
+        assert_eq!(production.body(StatusCode::BAD_REQUEST, &error)["message"], "sql failed");
+
+        let custom = ErrorResponsePolicy {
+            format: Some(Box::new(|status, response| json!({"status": status.as_u16(), "error": response.message}))),
+            ..ErrorResponsePolicy::default()
+        };
+        assert_eq!(
+            custom.body(StatusCode::INTERNAL_SERVER_ERROR, &error),
+            json!({"status": 500, "error": "sql failed"})
+        );
+    }
+}
@@ -0,0 +1,80 @@
+use axum::extract;
+use axum::extract::FromRequestParts;
+use axum::extract::RawPathParams;
+use http::request::Parts;
+use serde::de::DeserializeOwned;
+
+use crate::exception::Severity;
+use crate::exception::error_code;
+use crate::validate::Validator;
+use crate::web::error::HttpError;
+
+pub struct Query<T>(pub T);
+
+impl<T, S> FromRequestParts<S> for Query<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = HttpError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        log!("[request] query={}", &parts.uri.query().unwrap_or_default());
+        let result = extract::Query::<T>::try_from_uri(&parts.uri);
+        match result {
+            Ok(extract::Query(query)) => Ok(Query(query)),
+            Err(rejection) => Err(exception!(
+                format!("failed to parse query, error={}", rejection.body_text()),
+                severity = Severity::Warn,
+                code = error_code::BAD_REQUEST,
+                source = rejection
+            )
+            .into()),
+        }
+    }
+}
+
+pub struct ValidatedQuery<T>(pub T);
+
+impl<T, S> FromRequestParts<S> for ValidatedQuery<T>
+where
+    T: DeserializeOwned + Validator,
+    S: Send + Sync,
+{
+    type Rejection = HttpError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Query(value) = Query::<T>::from_request_parts(parts, state).await?;
+        value.validate()?;
+        Ok(Self(value))
+    }
+}
+
+pub struct Path<T>(pub T);
+
+impl<T, S> FromRequestParts<S> for Path<T>
+where
+    T: DeserializeOwned + Send,
+    S: Send + Sync,
+{
+    type Rejection = HttpError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        if let Ok(params) = RawPathParams::from_request_parts(parts, state).await {
+            for (key, value) in &params {
+                log!("[request] path_param {key}={value}");
+            }
+        }
+        let result = extract::Path::<T>::from_request_parts(parts, state).await;
+        match result {
+            Ok(extract::Path(path)) => Ok(Path(path)),
+            Err(rejection) => Err(exception!(
+                format!("failed to parse path, error={}", rejection.body_text()),
+                severity = Severity::Warn,
+                code = error_code::BAD_REQUEST,
+                source = rejection
+            )
+            .into()),
+        }
+    }
+}
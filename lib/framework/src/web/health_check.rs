@@ -0,0 +1,118 @@
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::extract::Request;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::middleware::Next;
+use axum::response::IntoResponse as _;
+use axum::response::Response;
+use futures::future::join_all;
+use tokio::time;
+use tokio_util::sync::CancellationToken;
+
+use crate::exception::Exception;
+
+// hung probe fails, so health check responds before lb health check timeout
+const PROBE_TIMEOUT: Duration = Duration::from_secs(3);
+
+type Probe = Box<dyn Fn() -> Pin<Box<dyn Future<Output = Result<(), Exception>> + Send>> + Send + Sync>;
+
+// GET /health-check is readiness (used by lb to route traffic), GET /health-check/live is liveness
+#[derive(Default)]
+pub struct HealthCheck {
+    liveness: Vec<(&'static str, Probe)>,
+    readiness: Vec<(&'static str, Probe)>,
+}
+
+impl HealthCheck {
+    // failing liveness probe means process should be restarted, e.g. deadlock, keep it cheap
+    pub fn add_liveness<F, Fut>(&mut self, name: &'static str, probe: F)
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), Exception>> + Send + 'static,
+    {
+        self.liveness.push((name, Box::new(move || Box::pin(probe()))));
+    }
+
+    // failing readiness probe takes instance out of lb, e.g. kafka or elasticsearch is not reachable
+    pub fn add_readiness<F, Fut>(&mut self, name: &'static str, probe: F)
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), Exception>> + Send + 'static,
+    {
+        self.readiness.push((name, Box::new(move || Box::pin(probe()))));
+    }
+}
+
+pub(crate) struct HealthCheckState {
+    pub(crate) health_check: HealthCheck,
+    pub(crate) shutdown_signal: CancellationToken,
+}
+
+// skip log for health check
+pub(crate) async fn health_check_layer(
+    State(state): State<Arc<HealthCheckState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    match request.uri().path() {
+        "/health-check" => {
+            // fail readiness during shutdown grace period, so lb stops routing new requests
+            if state.shutdown_signal.is_cancelled() {
+                return (StatusCode::SERVICE_UNAVAILABLE, "shutting down").into_response();
+            }
+            check(&state.health_check.readiness, PROBE_TIMEOUT).await
+        }
+        "/health-check/live" => check(&state.health_check.liveness, PROBE_TIMEOUT).await,
+        _ => next.run(request).await,
+    }
+}
+
+async fn check(probes: &[(&'static str, Probe)], timeout: Duration) -> Response {
+    let results = join_all(probes.iter().map(|(name, probe)| async move {
+        let result = time::timeout(timeout, probe())
+            .await
+            .unwrap_or_else(|_| Err(exception!(format!("probe timed out, timeout={timeout:?}"))));
+        (*name, result)
+    }))
+    .await;
+    let mut failed = vec![];
+    for (name, result) in results {
+        if let Err(e) = result {
+            console!("WARN health check failed, probe={name}, error={e}");
+            failed.push(name);
+        }
+    }
+    if failed.is_empty() {
+        StatusCode::OK.into_response() // gce lb health check requires to return 200
+    } else {
+        (StatusCode::SERVICE_UNAVAILABLE, format!("failed probes: {}", failed.join(", "))).into_response()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use axum::http::StatusCode;
+    use tokio::time;
+
+    use super::HealthCheck;
+    use super::check;
+
+    #[tokio::test]
+    async fn hung_probe_fails() {
+        let mut health_check = HealthCheck::default();
+        health_check.add_readiness("db", || async { Ok(()) });
+        assert_eq!(check(&health_check.readiness, Duration::from_millis(10)).await.status(), StatusCode::OK);
+
+        health_check.add_readiness("kafka", || async {
+            time::sleep(Duration::from_hours(1)).await;
+            Ok(())
+        });
+        let response = check(&health_check.readiness, Duration::from_millis(10)).await;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    }
+}
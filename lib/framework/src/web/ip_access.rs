@@ -0,0 +1,155 @@
+use std::net::IpAddr;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use axum::Router;
+use axum::extract::Request;
+use axum::extract::State;
+use axum::middleware;
+use axum::middleware::Next;
+use axum::response::IntoResponse as _;
+use axum::response::Response;
+
+use crate::exception::Exception;
+use crate::exception::Severity;
+use crate::exception::error_code;
+use crate::web::client_info::ClientInfo;
+use crate::web::error::HttpError;
+
+// e.g. "10.0.0.0/8", "192.168.1.1", "fd00::/8"
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Cidr {
+    network: IpAddr,
+    prefix: u8,
+}
+
+impl FromStr for Cidr {
+    type Err = Exception;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (address, prefix) =
+            value.split_once('/').map_or((value, None), |(address, prefix)| (address, Some(prefix)));
+        let network: IpAddr =
+            address.parse().map_err(|err| exception!(format!("invalid cidr, value={value}"), source = err))?;
+        let max_prefix = if network.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(prefix) => {
+                prefix.parse().map_err(|err| exception!(format!("invalid cidr, value={value}"), source = err))?
+            }
+            None => max_prefix,
+        };
+        if prefix > max_prefix {
+            return Err(exception!(format!("invalid cidr prefix, value={value}")));
+        }
+        Ok(Cidr { network, prefix })
+    }
+}
+
+impl Cidr {
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.network, ip.to_canonical()) {
+            (IpAddr::V4(network), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - u32::from(self.prefix)).unwrap_or(0);
+                u32::from(network) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(network), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - u32::from(self.prefix)).unwrap_or(0);
+                u128::from(network) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+pub struct IpAccessConfig {
+    // path prefixes to restrict, e.g. "/_sys/", empty means all paths
+    pub paths: Vec<&'static str>,
+    // if not empty, only client ips in allow list are permitted
+    pub allow: Vec<Cidr>,
+    // checked before allow list
+    pub deny: Vec<Cidr>,
+}
+
+impl IpAccessConfig {
+    fn permitted(&self, path: &str, client_ip: &str) -> bool {
+        if !self.paths.is_empty() && !self.paths.iter().any(|prefix| path.starts_with(prefix)) {
+            return true;
+        }
+        let Ok(ip) = client_ip.parse::<IpAddr>() else {
+            return self.allow.is_empty() && self.deny.is_empty();
+        };
+        if self.deny.iter().any(|cidr| cidr.contains(ip)) {
+            return false;
+        }
+        self.allow.is_empty() || self.allow.iter().any(|cidr| cidr.contains(ip))
+    }
+}
+
+pub fn ip_access<S>(router: Router<S>, config: IpAccessConfig) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    router.layer(middleware::from_fn_with_state(Arc::new(config), ip_access_layer))
+}
+
+pub(crate) async fn ip_access_layer(
+    State(config): State<Arc<IpAccessConfig>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    // client info is populated by http_server_layer
+    if let Some(client_info) = request.extensions().get::<Arc<ClientInfo>>()
+        && !config.permitted(request.uri().path(), &client_info.client_ip)
+    {
+        return HttpError::from(exception!(
+            format!("access denied, client_ip={}", client_info.client_ip),
+            severity = Severity::Warn,
+            code = error_code::FORBIDDEN
+        ))
+        .into_response();
+    }
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::IpAddr;
+
+    use super::Cidr;
+    use super::IpAccessConfig;
+
+    #[test]
+    fn cidr_contains() {
+        let cidr: Cidr = "10.0.0.0/8".parse().unwrap();
+        assert!(cidr.contains("10.1.2.3".parse().unwrap()));
+        assert!(!cidr.contains("11.0.0.1".parse().unwrap()));
+        assert!(cidr.contains("::ffff:10.0.0.1".parse().unwrap()));
+
+        let single: Cidr = "192.168.1.1".parse().unwrap();
+        assert!(single.contains("192.168.1.1".parse().unwrap()));
+        assert!(!single.contains("192.168.1.2".parse().unwrap()));
+
+        let any: Cidr = "0.0.0.0/0".parse().unwrap();
+        assert!(any.contains("8.8.8.8".parse().unwrap()));
+
+        let ipv6: Cidr = "fd00::/8".parse().unwrap();
+        assert!(ipv6.contains("fd12::1".parse::<IpAddr>().unwrap()));
+
+        "10.0.0.0/33".parse::<Cidr>().unwrap_err();
+        "invalid".parse::<Cidr>().unwrap_err();
+    }
+
+    #[test]
+    fn permitted() {
+        let config = IpAccessConfig {
+            paths: vec!["/_sys/"],
+            allow: vec!["10.0.0.0/8".parse().unwrap()],
+            deny: vec!["10.0.0.1".parse().unwrap()],
+        };
+        assert!(config.permitted("/user", "8.8.8.8"));
+        assert!(config.permitted("/_sys/job", "10.0.0.2"));
+        assert!(!config.permitted("/_sys/job", "10.0.0.1"));
+        assert!(!config.permitted("/_sys/job", "8.8.8.8"));
+        assert!(!config.permitted("/_sys/job", "unknown"));
+    }
+}
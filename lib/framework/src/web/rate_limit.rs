@@ -0,0 +1,154 @@
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use axum::Router;
+use axum::extract::Request;
+use axum::extract::State;
+use axum::http::HeaderValue;
+use axum::http::header;
+use axum::middleware;
+use axum::middleware::Next;
+use axum::response::IntoResponse as _;
+use axum::response::Response;
+
+use crate::exception::Severity;
+use crate::exception::error_code;
+use crate::web::client_info::ClientInfo;
+use crate::web::error::HttpError;
+
+#[derive(Clone, Copy)]
+pub struct RateLimitConfig {
+    // tokens refilled per second per client ip, must be positive
+    pub rate: f64,
+    // max tokens, allows short bursts above rate
+    pub burst: u32,
+}
+
+struct RateLimiter {
+    config: RateLimitConfig,
+    buckets: Mutex<Buckets>,
+}
+
+#[derive(Default)]
+struct Buckets {
+    buckets: HashMap<String, Bucket>,
+    // (updated_time, client_ip), ordered to find least recently updated bucket
+    updates: BTreeSet<(Instant, String)>,
+}
+
+struct Bucket {
+    tokens: f64,
+    updated_time: Instant,
+}
+
+impl RateLimiter {
+    // evict least recently updated bucket when tracking too many ips, to bound memory
+    const MAX_BUCKETS: usize = 10_000;
+
+    fn new(config: RateLimitConfig) -> Self {
+        assert!(config.rate > 0.0, "rate must be positive, rate={}", config.rate); // fail fast on startup
+        Self { config, buckets: Mutex::new(Buckets::default()) }
+    }
+
+    // takes one token, returns seconds to wait if rejected
+    fn acquire(&self, client_ip: &str, now: Instant) -> Option<u64> {
+        let RateLimitConfig { rate, burst } = self.config;
+        let burst = f64::from(burst);
+        let mut guard = self.buckets.lock().unwrap();
+        let Buckets { ref mut buckets, ref mut updates } = *guard;
+        if !buckets.contains_key(client_ip)
+            && buckets.len() >= Self::MAX_BUCKETS
+            && let Some((_, evicted_ip)) = updates.pop_first()
+        {
+            buckets.remove(&evicted_ip);
+        }
+        let bucket = buckets.entry(client_ip.to_owned()).or_insert(Bucket { tokens: burst, updated_time: now });
+        updates.remove(&(bucket.updated_time, client_ip.to_owned()));
+        updates.insert((now, client_ip.to_owned()));
+        bucket.tokens = (bucket.tokens + now.duration_since(bucket.updated_time).as_secs_f64() * rate).min(burst);
+        bucket.updated_time = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            None
+        } else {
+            #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+            let retry_after = ((1.0 - bucket.tokens) / rate).ceil() as u64;
+            Some(retry_after.max(1))
+        }
+    }
+}
+
+pub fn rate_limit<S>(router: Router<S>, config: RateLimitConfig) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    router.layer(middleware::from_fn_with_state(Arc::new(RateLimiter::new(config)), rate_limit_layer))
+}
+
+async fn rate_limit_layer(State(limiter): State<Arc<RateLimiter>>, request: Request, next: Next) -> Response {
+    // client info is populated by http_server_layer
+    if let Some(client_info) = request.extensions().get::<Arc<ClientInfo>>()
+        && let Some(retry_after) = limiter.acquire(&client_info.client_ip, Instant::now())
+    {
+        let mut response = HttpError::from(exception!(
+            format!("too many requests, client_ip={}, retry_after={retry_after}s", client_info.client_ip),
+            severity = Severity::Warn,
+            code = error_code::TOO_MANY_REQUESTS
+        ))
+        .into_response();
+        response.headers_mut().insert(header::RETRY_AFTER, HeaderValue::from(retry_after));
+        return response;
+    }
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use std::time::Instant;
+
+    use super::RateLimitConfig;
+    use super::RateLimiter;
+
+    #[test]
+    fn acquire() {
+        let limiter = RateLimiter::new(RateLimitConfig { rate: 0.5, burst: 2 });
+        let now = Instant::now();
+
+        assert_eq!(limiter.acquire("10.0.0.1", now), None);
+        assert_eq!(limiter.acquire("10.0.0.1", now), None);
+        assert_eq!(limiter.acquire("10.0.0.1", now), Some(2));
+        assert_eq!(limiter.acquire("10.0.0.2", now), None);
+
+        assert_eq!(limiter.acquire("10.0.0.1", now + Duration::from_secs(2)), None);
+        assert_eq!(limiter.acquire("10.0.0.1", now + Duration::from_secs(2)), Some(2));
+    }
+
+    #[test]
+    fn evict_least_recently_updated() {
+        let limiter = RateLimiter::new(RateLimitConfig { rate: 0.5, burst: 1 });
+        let now = Instant::now();
+        assert_eq!(limiter.acquire("10.0.0.1", now), None);
+        for i in 1..RateLimiter::MAX_BUCKETS {
+            limiter.acquire(&format!("ip-{i}"), now + Duration::from_millis(1));
+        }
+        assert_eq!(limiter.acquire("10.0.0.1", now + Duration::from_millis(2)), Some(2));
+
+        // 10.0.0.1 is updated last, ip-1 is evicted
+        assert_eq!(limiter.acquire("10.0.0.2", now + Duration::from_millis(3)), None);
+        let buckets = limiter.buckets.lock().unwrap();
+        assert_eq!(buckets.buckets.len(), RateLimiter::MAX_BUCKETS);
+        assert_eq!(buckets.updates.len(), RateLimiter::MAX_BUCKETS);
+        assert!(buckets.buckets.contains_key("10.0.0.1"));
+        assert!(!buckets.buckets.contains_key("ip-1"));
+    }
+
+    #[test]
+    #[should_panic(expected = "rate must be positive")]
+    fn reject_zero_rate() {
+        RateLimiter::new(RateLimitConfig { rate: 0.0, burst: 1 });
+    }
+}
@@ -1,66 +1,406 @@
+use std::collections::HashMap;
+use std::future::IntoFuture as _;
+use std::mem;
 use std::net::SocketAddr;
+use std::path::PathBuf;
 use std::sync::Arc;
 use std::sync::OnceLock;
 use std::time::Duration;
+use std::time::SystemTime;
 
 use axum::Router;
+use axum::body::HttpBody as _;
+use axum::extract::DefaultBodyLimit;
 use axum::extract::MatchedPath;
 use axum::extract::Request;
+use axum::extract::State;
+use axum::extract::connect_info::IntoMakeServiceWithConnectInfo;
+use axum::http::HeaderValue;
 use axum::http::StatusCode;
 use axum::http::header;
 use axum::middleware;
 use axum::middleware::Next;
 use axum::response::IntoResponse as _;
 use axum::response::Response;
+use axum::routing::get;
 use axum_extra::extract::CookieJar;
+use axum_server::Handle;
+use axum_server::tls_rustls::RustlsConfig;
+use rustls::ServerConfig;
+use rustls::crypto::aws_lc_rs;
+use rustls::pki_types::CertificateDer;
+use rustls::pki_types::PrivateKeyDer;
+use rustls::pki_types::pem::PemObject as _;
+use tokio::fs;
 use tokio::net::TcpListener;
+use tokio::net::UnixListener;
+use tokio::task::JoinSet;
+use tokio::time;
 use tokio::time::sleep;
 use tokio_util::sync::CancellationToken;
 pub use tower_http::services::ServeDir;
 pub use tower_http::services::ServeFile;
 
+use crate::exception::Exception;
+use crate::exception::Severity;
+use crate::exception::error_code;
 use crate::log;
+use crate::log::current_action_id;
+use crate::log::mask::is_masked_field;
 use crate::log::metrics::Counter;
 use crate::log::metrics::Metrics;
+use crate::log::prometheus;
 use crate::web::CLIENT;
+use crate::web::CORRELATION_ID;
 use crate::web::REF_ID;
+use crate::web::TRACEPARENT;
+use crate::web::X_REQUEST_ID;
+use crate::web::api_key::X_API_KEY;
 use crate::web::client_info::client_info;
+use crate::web::error::ErrorResponsePolicy;
+use crate::web::error::HttpError;
+use crate::web::error::error_response_layer;
+use crate::web::health_check::HealthCheck;
+use crate::web::health_check::HealthCheckState;
+use crate::web::health_check::health_check_layer;
+use crate::web::ip_access::Cidr;
+use crate::web::ip_access::IpAccessConfig;
+use crate::web::ip_access::ip_access_layer;
 
 pub struct HttpServerConfig {
+    // tcp address, empty to only listen on unix socket
     pub bind_address: String,
+    // listen on unix socket, in addition to bind_address, e.g. behind local reverse proxy
+    pub unix_socket_path: Option<PathBuf>,
+    // additional plain http listeners with own router, e.g. admin router on 127.0.0.1:9090
+    pub listeners: Vec<ListenerConfig>,
+    // max number of x-forwarded-for entries to check from right, usually number of lb/proxy hops
     pub max_forwarded_ips: usize,
+    // if set, x-forwarded-for is only trusted if sent from these ranges, and their entries are skipped
+    pub trusted_proxies: Vec<Cidr>,
+    // path prefixes not recorded in action log, e.g. "/static/", "/favicon.ico"
+    pub log_excluded_paths: Vec<&'static str>,
     pub shutdown_grace_period: Duration,
+    // max time to wait for in-flight requests after grace period
+    pub drain_timeout: Duration,
+    // requests with larger body are rejected with 413 before handlers run
+    pub max_body_size: usize,
+    // requests not responded within timeout are aborted with 503, disabled if none (default)
+    pub request_timeout: Option<Duration>,
+    // matched path -> timeout, e.g. "/job/{job}" => 10min, overrides request_timeout
+    pub route_timeouts: HashMap<&'static str, Duration>,
+    // expose GET /metrics in prometheus format on bind_address, aggregated from action stats and MetricsCollector,
+    // if bind_address is public, restrict with ip_access or use ListenerConfig.metrics to expose on internal listener only
+    pub metrics: bool,
+    pub health_check: HealthCheck,
+    // restrict endpoints by client ip, e.g. admin endpoints to internal network
+    pub ip_access: Vec<IpAccessConfig>,
+    // default format is ErrorResponse, which is expected by ApiClient
+    pub error_response: Option<ErrorResponsePolicy>,
+    // serve https directly if set
+    pub tls: Option<TlsConfig>,
+}
+
+pub struct ListenerConfig {
+    pub bind_address: String,
+    pub router: Router,
+    // expose GET /metrics on this listener
+    pub metrics: bool,
+}
+
+pub struct TlsConfig {
+    pub cert_path: PathBuf,
+    pub key_path: PathBuf,
+    // check modified time of cert/key files in interval and reload on rotation, disabled if none
+    pub reload_interval: Option<Duration>,
 }
 
 impl Default for HttpServerConfig {
     fn default() -> Self {
         HttpServerConfig {
             bind_address: "0.0.0.0:8080".to_owned(),
+            unix_socket_path: None,
+            listeners: vec![],
             max_forwarded_ips: 2,
+            trusted_proxies: vec![],
+            log_excluded_paths: vec!["/favicon.ico"],
             shutdown_grace_period: Duration::ZERO,
+            drain_timeout: Duration::from_secs(30),
+            max_body_size: 2 * 1024 * 1024,
+            request_timeout: None,
+            route_timeouts: HashMap::new(),
+            metrics: false,
+            health_check: HealthCheck::default(),
+            ip_access: vec![],
+            error_response: None,
+            tls: None,
         }
     }
 }
 
-pub async fn start_http_server(router: Router, shutdown_signal: CancellationToken, config: HttpServerConfig) {
-    let app = Router::new();
-    let app = app.merge(router);
-    let app = app.layer(middleware::from_fn(http_server_layer));
-    let app = app.into_make_service_with_connect_info::<SocketAddr>();
-    let listener = TcpListener::bind(&config.bind_address).await.expect("failed to bind address");
-    console!("http server stated, bind={}", config.bind_address);
-    axum::serve(listener, app)
-        .with_graceful_shutdown(async move {
+pub async fn start_http_server(router: Router, shutdown_signal: CancellationToken, mut config: HttpServerConfig) {
+    // after grace period, stop accepting connections and reject new requests on existing connections
+    let draining = CancellationToken::new();
+    let metrics_enabled = config.metrics || config.listeners.iter().any(|listener| listener.metrics);
+    if metrics_enabled {
+        prometheus::enable();
+        // skip log for metrics scraping
+        config.log_excluded_paths.push(METRICS_PATH);
+    }
+    let layers = ServerLayers {
+        server_layer: Arc::new(HttpServerLayerConfig {
+            max_forwarded_ips: config.max_forwarded_ips,
+            trusted_proxies: mem::take(&mut config.trusted_proxies),
+            log_excluded_paths: mem::take(&mut config.log_excluded_paths),
+        }),
+        max_body_size: config.max_body_size,
+        timeouts: Arc::new(RequestTimeouts {
+            default: config.request_timeout,
+            routes: mem::take(&mut config.route_timeouts),
+        }),
+        ip_access: mem::take(&mut config.ip_access).into_iter().map(Arc::new).collect(),
+        error_response: config.error_response.take().map(Arc::new),
+        health_check: Arc::new(HealthCheckState {
+            health_check: mem::take(&mut config.health_check),
+            shutdown_signal: shutdown_signal.clone(),
+        }),
+        draining: draining.clone(),
+    };
+    let app = layers.apply(router, config.metrics);
+
+    let period = config.shutdown_grace_period;
+    tokio::spawn({
+        let draining = draining.clone();
+        async move {
             shutdown_signal.cancelled().await;
-            let period = config.shutdown_grace_period;
             if !period.is_zero() {
                 console!("http server shutdown in {period:?}");
                 sleep(period).await;
             }
-        })
+            draining.cancel();
+        }
+    });
+
+    let mut servers = JoinSet::new();
+    for listener in mem::take(&mut config.listeners) {
+        let listener_app =
+            layers.apply(listener.router, listener.metrics).into_make_service_with_connect_info::<SocketAddr>();
+        servers.spawn(start_tcp_server(listener_app, draining.clone(), listener.bind_address, config.drain_timeout));
+    }
+    if let Some(path) = config.unix_socket_path.clone() {
+        servers.spawn(start_unix_server(app.clone(), path, draining.clone(), config.drain_timeout));
+    }
+    if !config.bind_address.is_empty() {
+        let app = app.into_make_service_with_connect_info::<SocketAddr>();
+        if let Some(ref tls) = config.tls {
+            start_https_server(app, draining, &config, tls).await;
+        } else {
+            start_tcp_server(app, draining, config.bind_address.clone(), config.drain_timeout).await;
+        }
+    }
+    servers.join_all().await;
+}
+
+// middlewares shared by all listeners
+struct ServerLayers {
+    server_layer: Arc<HttpServerLayerConfig>,
+    max_body_size: usize,
+    timeouts: Arc<RequestTimeouts>,
+    ip_access: Vec<Arc<IpAccessConfig>>,
+    error_response: Option<Arc<ErrorResponsePolicy>>,
+    health_check: Arc<HealthCheckState>,
+    draining: CancellationToken,
+}
+
+impl ServerLayers {
+    // metrics is added as route, so it's restricted by ip_access, and conflicts with app route fail on startup
+    fn apply(&self, router: Router, metrics: bool) -> Router {
+        let app = Router::new();
+        let app = app.merge(router);
+        let app = if metrics { app.route(METRICS_PATH, get(prometheus_metrics)) } else { app };
+        let app = app.layer(DefaultBodyLimit::max(self.max_body_size));
+        let app = app.layer(middleware::from_fn_with_state(self.max_body_size, body_limit_layer));
+        let app = app.layer(middleware::from_fn_with_state(Arc::clone(&self.timeouts), timeout_layer));
+        let app = self.ip_access.iter().fold(app, |app, ip_access| {
+            app.layer(middleware::from_fn_with_state(Arc::clone(ip_access), ip_access_layer))
+        });
+        let app = if let Some(ref policy) = self.error_response {
+            app.layer(middleware::from_fn_with_state(Arc::clone(policy), error_response_layer))
+        } else {
+            app
+        };
+        let app = app.layer(middleware::from_fn_with_state(Arc::clone(&self.server_layer), http_server_layer));
+        let app = app.layer(middleware::from_fn_with_state(Arc::clone(&self.health_check), health_check_layer));
+        app.layer(middleware::from_fn_with_state(self.draining.clone(), drain_layer))
+    }
+}
+
+async fn start_tcp_server(
+    app: IntoMakeServiceWithConnectInfo<Router, SocketAddr>,
+    draining: CancellationToken,
+    bind_address: String,
+    drain_timeout: Duration,
+) {
+    let listener = TcpListener::bind(&bind_address).await.expect("failed to bind address");
+    console!("http server stated, bind={bind_address}");
+    let server = axum::serve(listener, app).with_graceful_shutdown(draining.clone().cancelled_owned());
+    tokio::select! {
+        result = server.into_future() => result.expect("failed to start http server"),
+        () = async {
+            draining.cancelled().await;
+            sleep(drain_timeout).await;
+        } => console!("WARN http server drain timed out, timeout={drain_timeout:?}"),
+    }
+    console!("http server stopped, bind={bind_address}");
+}
+
+// connections over unix socket have no peer address, client ip is taken from x-forwarded-for set by local proxy
+async fn start_unix_server(app: Router, path: PathBuf, draining: CancellationToken, drain_timeout: Duration) {
+    // remove stale socket file left by previous process
+    let _result = fs::remove_file(&path).await;
+    let listener = UnixListener::bind(&path).expect("failed to bind unix socket");
+    console!("http server stated, unix_socket={}", path.display());
+    let server = axum::serve(listener, app).with_graceful_shutdown(draining.clone().cancelled_owned());
+    tokio::select! {
+        result = server.into_future() => result.expect("failed to start http server"),
+        () = async {
+            draining.cancelled().await;
+            sleep(drain_timeout).await;
+        } => console!("WARN http server drain timed out, timeout={drain_timeout:?}"),
+    }
+    if let Err(e) = fs::remove_file(&path).await {
+        console!("WARN failed to remove unix socket, path={}, error={e}", path.display());
+    }
+    console!("http server stopped, unix_socket={}", path.display());
+}
+
+async fn start_https_server(
+    app: IntoMakeServiceWithConnectInfo<Router, SocketAddr>,
+    draining: CancellationToken,
+    config: &HttpServerConfig,
+    tls: &TlsConfig,
+) {
+    let address: SocketAddr = config.bind_address.parse().expect("invalid bind address");
+    let rustls_config = RustlsConfig::from_config(load_tls_config(tls).await.expect("failed to load tls config"));
+    if let Some(interval) = tls.reload_interval {
+        tokio::spawn(reload_tls_config(
+            rustls_config.clone(),
+            tls.cert_path.clone(),
+            tls.key_path.clone(),
+            interval,
+            draining.clone(),
+        ));
+    }
+
+    let handle = Handle::new();
+    let shutdown_handle = handle.clone();
+    let drain_timeout = config.drain_timeout;
+    tokio::spawn(async move {
+        draining.cancelled().await;
+        shutdown_handle.graceful_shutdown(Some(drain_timeout));
+    });
+
+    console!("https server stated, bind={}", config.bind_address);
+    axum_server::bind_rustls(address, rustls_config)
+        .handle(handle)
+        .serve(app)
         .await
-        .expect("failed to start http server");
-    console!("http server stopped");
+        .expect("failed to start https server");
+    console!("https server stopped");
+}
+
+async fn drain_layer(State(draining): State<CancellationToken>, request: Request, next: Next) -> Response {
+    if draining.is_cancelled() {
+        return (StatusCode::SERVICE_UNAVAILABLE, [(header::CONNECTION, "close")]).into_response();
+    }
+    next.run(request).await
+}
+
+async fn body_limit_layer(State(max_body_size): State<usize>, request: Request, next: Next) -> Response {
+    let content_length = request
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| str::parse::<usize>(v).ok());
+    if let Some(length) = content_length
+        && length > max_body_size
+    {
+        return HttpError::from(exception!(
+            format!("request body too large, content_length={length}, max_body_size={max_body_size}"),
+            severity = Severity::Warn,
+            code = error_code::PAYLOAD_TOO_LARGE
+        ))
+        .into_response();
+    }
+    next.run(request).await
+}
+
+struct RequestTimeouts {
+    default: Option<Duration>,
+    routes: HashMap<&'static str, Duration>,
+}
+
+async fn timeout_layer(State(timeouts): State<Arc<RequestTimeouts>>, request: Request, next: Next) -> Response {
+    let matched_path = request.extensions().get::<MatchedPath>().map(MatchedPath::as_str);
+    let timeout = matched_path.and_then(|path| timeouts.routes.get(path).copied()).or(timeouts.default);
+    let Some(timeout) = timeout else {
+        return next.run(request).await;
+    };
+    match time::timeout(timeout, next.run(request)).await {
+        Ok(response) => response,
+        Err(_) => HttpError::from(exception!(
+            format!("request timed out, timeout={timeout:?}"),
+            code = error_code::REQUEST_TIMEOUT
+        ))
+        .into_response(),
+    }
+}
+
+async fn load_tls_config(tls: &TlsConfig) -> Result<Arc<ServerConfig>, Exception> {
+    let certs = CertificateDer::pem_slice_iter(&fs::read(&tls.cert_path).await?).collect::<Result<Vec<_>, _>>()?;
+    let key = PrivateKeyDer::from_pem_slice(&fs::read(&tls.key_path).await?)?;
+    // select provider explicitly, multiple rustls providers are linked by dependencies
+    let mut config = ServerConfig::builder_with_provider(Arc::new(aws_lc_rs::default_provider()))
+        .with_safe_default_protocol_versions()?
+        .with_no_client_auth()
+        .with_single_cert(certs, key)?;
+    config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    Ok(Arc::new(config))
+}
+
+async fn reload_tls_config(
+    rustls_config: RustlsConfig,
+    cert_path: PathBuf,
+    key_path: PathBuf,
+    interval: Duration,
+    shutdown_signal: CancellationToken,
+) {
+    let tls = TlsConfig { cert_path, key_path, reload_interval: None };
+    let mut last_modified = modified_time(&tls).await;
+    loop {
+        tokio::select! {
+            () = shutdown_signal.cancelled() => return,
+            () = sleep(interval) => {}
+        }
+        let modified = modified_time(&tls).await;
+        if modified == last_modified {
+            continue;
+        }
+        match load_tls_config(&tls).await {
+            Ok(config) => {
+                rustls_config.reload_from_config(config);
+                last_modified = modified;
+                console!("tls cert reloaded, cert={}", tls.cert_path.display());
+            }
+            Err(e) => console!("ERROR failed to reload tls cert, error={e:?}"),
+        }
+    }
+}
+
+async fn modified_time(tls: &TlsConfig) -> Option<(SystemTime, SystemTime)> {
+    let cert = fs::metadata(&tls.cert_path).await.and_then(|metadata| metadata.modified()).ok()?;
+    let key = fs::metadata(&tls.key_path).await.and_then(|metadata| metadata.modified()).ok()?;
+    Some((cert, key))
 }
 
 static REQUEST_COUNTER: OnceLock<Counter> = OnceLock::new();
@@ -75,30 +415,72 @@ pub fn http_server_metrics() -> impl Fn(&mut Metrics) {
     }
 }
 
-async fn http_server_layer(mut request: Request, next: Next) -> Response {
-    // skip log for health check
-    if request.uri().path() == "/health-check" {
-        return StatusCode::OK.into_response(); // gce lb health check requires to return 200
+const METRICS_PATH: &str = "/metrics";
+
+async fn prometheus_metrics() -> Response {
+    ([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], prometheus::render()).into_response()
+}
+
+struct HttpServerLayerConfig {
+    max_forwarded_ips: usize,
+    trusted_proxies: Vec<Cidr>,
+    log_excluded_paths: Vec<&'static str>,
+}
+
+async fn http_server_layer(
+    State(config): State<Arc<HttpServerLayerConfig>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let path = request.uri().path();
+    if config.log_excluded_paths.iter().any(|prefix| path.starts_with(prefix)) {
+        let client_info = client_info(&request, config.max_forwarded_ips, &config.trusted_proxies);
+        request.extensions_mut().insert(Arc::new(client_info));
+        return next.run(request).await;
     }
 
-    let ref_id = request.headers().get(REF_ID).and_then(|v| v.to_str().ok()).map(|id| vec![id.to_owned()]);
+    // ref-id is set by internal clients, x-request-id by browser or external callers
+    let ref_id = request
+        .headers()
+        .get(REF_ID)
+        .or_else(|| request.headers().get(X_REQUEST_ID))
+        .and_then(|v| v.to_str().ok())
+        .map(|id| vec![id.to_owned()]);
 
     let _counter = REQUEST_COUNTER.get().map(Counter::increase);
 
     let response = log::action("http", ref_id, async {
         context!(uri = request.uri().to_string(), method = request.method().as_str());
+        // accept correlation id from internal clients, otherwise this action starts the flow
+        if let Some(correlation_id) = request.headers().get(CORRELATION_ID).and_then(|value| value.to_str().ok()) {
+            context!(correlation_id = correlation_id);
+        }
+        // accept trace id from upstream service, or start new trace
+        let trace_id = request
+            .headers()
+            .get(TRACEPARENT)
+            .and_then(|value| value.to_str().ok())
+            .and_then(log::parse_traceparent)
+            .map(str::to_owned)
+            .or_else(log::current_trace_id);
+        if let Some(trace_id) = trace_id {
+            context!(trace_id = trace_id);
+        }
 
         for (name, value) in request.headers() {
-            if name != header::COOKIE {
+            if name == header::AUTHORIZATION || name == X_API_KEY || is_masked_field(name.as_str()) {
+                log!("[header] {name}=******");
+            } else if name != header::COOKIE {
                 log!("[header] {name}={value:?}");
             }
         }
         let cookies = CookieJar::from_headers(request.headers());
+        // cookie values are often credentials, e.g. session id
         for cookie in cookies.iter() {
-            log!("[cookie] {}={}", cookie.name(), cookie.value());
+            log!("[cookie] {}=******", cookie.name());
         }
 
-        let client_info = client_info(&request, 2);
+        let client_info = client_info(&request, config.max_forwarded_ips, &config.trusted_proxies);
         context!(client_ip = &client_info.client_ip);
         if let Some(ref user_agent) = client_info.user_agent {
             context!(user_agent = user_agent);
@@ -123,7 +505,17 @@ async fn http_server_layer(mut request: Request, next: Next) -> Response {
             stats!(request_content_length = length);
         }
 
-        let http_response = next.run(request).await;
+        let mut http_response = next.run(request).await;
+        if let Some(action_id) = current_action_id()
+            && let Ok(value) = HeaderValue::from_str(&action_id)
+        {
+            http_response.headers_mut().insert(X_REQUEST_ID, value);
+        }
+
+        // after compression, reflects actual bytes sent
+        if let Some(length) = http_response.body().size_hint().exact() {
+            stats!(response_content_length = length);
+        }
 
         let status = http_response.status().as_u16();
         context!(response_status = status.to_string());
@@ -0,0 +1,138 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use axum::Router;
+use axum::extract::FromRequestParts;
+use axum::extract::Request;
+use axum::extract::State;
+use axum::middleware;
+use axum::middleware::Next;
+use axum::response::IntoResponse as _;
+use axum::response::Response;
+use axum_extra::extract::cookie::Cookie;
+pub use axum_extra::extract::cookie::Key;
+use axum_extra::extract::cookie::PrivateCookieJar;
+pub use axum_extra::extract::cookie::SameSite;
+use chrono::Utc;
+use http::request::Parts;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::json;
+use crate::web::error::HttpError;
+
+pub struct SessionConfig {
+    // cookie is encrypted and signed with key, use Key::generate() for local env only, otherwise session is lost on restart
+    pub key: Key,
+    pub cookie_name: &'static str,
+    pub ttl: Duration,
+    pub same_site: SameSite,
+    pub secure: bool,
+}
+
+// values are stored in cookie, keep it small, browser limits cookie size to 4k
+#[derive(Clone, Default)]
+pub struct Session(Arc<Mutex<SessionState>>);
+
+#[derive(Default)]
+struct SessionState {
+    values: HashMap<String, String>,
+    changed: bool,
+    invalidated: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct SessionCookie {
+    expires: i64,
+    values: HashMap<String, String>,
+}
+
+impl Session {
+    pub fn get(&self, key: &str) -> Option<String> {
+        self.0.lock().unwrap().values.get(key).cloned()
+    }
+
+    pub fn set(&self, key: impl Into<String>, value: impl Into<String>) {
+        let mut state = self.0.lock().unwrap();
+        state.values.insert(key.into(), value.into());
+        state.changed = true;
+    }
+
+    pub fn remove(&self, key: &str) {
+        let mut state = self.0.lock().unwrap();
+        if state.values.remove(key).is_some() {
+            state.changed = true;
+        }
+    }
+
+    // e.g. logout, removes session cookie, values set afterwards are written to new cookie, e.g. re-login
+    pub fn invalidate(&self) {
+        let mut state = self.0.lock().unwrap();
+        state.values.clear();
+        state.changed = false;
+        state.invalidated = true;
+    }
+}
+
+pub fn session<S>(router: Router<S>, config: SessionConfig) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    router.layer(middleware::from_fn_with_state(Arc::new(config), session_layer))
+}
+
+async fn session_layer(State(config): State<Arc<SessionConfig>>, mut request: Request, next: Next) -> Response {
+    let jar = PrivateCookieJar::from_headers(request.headers(), config.key.clone());
+    let now = Utc::now().timestamp();
+    let values = jar
+        .get(config.cookie_name)
+        .and_then(|cookie| json::from_json::<SessionCookie>(cookie.value()).ok())
+        .filter(|cookie| cookie.expires > now)
+        .map(|cookie| cookie.values)
+        .unwrap_or_default();
+    let session = Session(Arc::new(Mutex::new(SessionState { values, changed: false, invalidated: false })));
+    request.extensions_mut().insert(session.clone());
+
+    let response = next.run(request).await;
+
+    let (invalidated, changed_value) = {
+        let state = session.0.lock().unwrap();
+        let value = state.changed.then(|| SessionCookie {
+            expires: now.saturating_add(i64::try_from(config.ttl.as_secs()).unwrap_or(i64::MAX)),
+            values: state.values.clone(),
+        });
+        (state.invalidated, value)
+    };
+    if invalidated && changed_value.is_none() {
+        return (jar.remove(Cookie::build(config.cookie_name).path("/")), response).into_response();
+    }
+    if let Some(value) = changed_value
+        && let Ok(value) = json::to_json(&value)
+    {
+        let cookie = Cookie::build((config.cookie_name, value))
+            .path("/")
+            .http_only(true)
+            .secure(config.secure)
+            .same_site(config.same_site)
+            .max_age(config.ttl.try_into().unwrap_or_default());
+        return (jar.add(cookie), response).into_response();
+    }
+    response
+}
+
+impl<S> FromRequestParts<S> for Session
+where
+    S: Send + Sync,
+{
+    type Rejection = HttpError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(parts
+            .extensions
+            .get::<Session>()
+            .cloned()
+            .ok_or_else(|| exception!("session is not configured, router must be wrapped by session()"))?)
+    }
+}
@@ -0,0 +1,138 @@
+use std::convert::Infallible;
+use std::fmt::Write as _;
+use std::pin::Pin;
+use std::task::Context;
+use std::task::Poll;
+use std::time::Duration;
+
+use axum::body::Body;
+use axum::http::HeaderValue;
+use axum::http::header;
+use axum::response::IntoResponse;
+use axum::response::Response;
+use bytes::Bytes;
+use futures::Stream;
+use tokio::sync::mpsc;
+use tokio::time;
+use tokio::time::Instant;
+use tokio::time::Interval;
+use tokio::time::MissedTickBehavior;
+use tokio_stream::wrappers::ReceiverStream;
+
+use crate::exception::Exception;
+pub use crate::http::Event;
+
+// server side of EventSource, events are pulled from stream only when client is ready to receive
+pub struct SseResponse {
+    events: Pin<Box<dyn Stream<Item = Event> + Send>>,
+    keep_alive: Option<Duration>,
+}
+
+// sender side created by SseResponse::channel(), send() waits when buffer is full
+pub struct SseSender(mpsc::Sender<Event>);
+
+impl SseResponse {
+    pub fn new(events: impl Stream<Item = Event> + Send + 'static) -> Self {
+        Self { events: Box::pin(events), keep_alive: Some(Duration::from_secs(15)) }
+    }
+
+    pub fn channel(buffer: usize) -> (SseSender, SseResponse) {
+        let (sender, receiver) = mpsc::channel(buffer);
+        (SseSender(sender), SseResponse::new(ReceiverStream::new(receiver)))
+    }
+
+    // sends comment when idle, to keep proxy/load balancer from closing connection, None to disable
+    #[must_use]
+    pub const fn keep_alive(mut self, interval: Option<Duration>) -> Self {
+        self.keep_alive = interval;
+        self
+    }
+}
+
+impl SseSender {
+    // returns error if client disconnected
+    pub async fn send(&self, event: Event) -> Result<(), Exception> {
+        self.0.send(event).await.map_err(|err| exception!("sse client disconnected", source = err))
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.0.is_closed()
+    }
+}
+
+impl IntoResponse for SseResponse {
+    fn into_response(self) -> Response {
+        let keep_alive = self.keep_alive.map(|interval| {
+            let mut interval = time::interval_at(Instant::now() + interval, interval);
+            interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            interval
+        });
+        let body = Body::from_stream(SseBody { events: self.events, keep_alive });
+        let mut response = body.into_response();
+        let headers = response.headers_mut();
+        headers.insert(header::CONTENT_TYPE, HeaderValue::from_static("text/event-stream"));
+        headers.insert(header::CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+        // disable nginx response buffering
+        headers.insert("x-accel-buffering", HeaderValue::from_static("no"));
+        response
+    }
+}
+
+struct SseBody {
+    events: Pin<Box<dyn Stream<Item = Event> + Send>>,
+    keep_alive: Option<Interval>,
+}
+
+impl Stream for SseBody {
+    type Item = Result<Bytes, Infallible>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        match self.events.as_mut().poll_next(cx) {
+            Poll::Ready(Some(event)) => {
+                if let Some(ref mut keep_alive) = self.keep_alive {
+                    keep_alive.reset();
+                }
+                Poll::Ready(Some(Ok(Bytes::from(encode_event(&event)))))
+            }
+            Poll::Ready(None) => Poll::Ready(None),
+            Poll::Pending => {
+                if let Some(ref mut keep_alive) = self.keep_alive
+                    && keep_alive.poll_tick(cx).is_ready()
+                {
+                    return Poll::Ready(Some(Ok(Bytes::from_static(b":\n\n"))));
+                }
+                Poll::Pending
+            }
+        }
+    }
+}
+
+fn encode_event(event: &Event) -> String {
+    let mut buffer = String::new();
+    if let Some(ref id) = event.id {
+        let _result = writeln!(buffer, "id: {id}");
+    }
+    if let Some(ref event_type) = event.r#type {
+        let _result = writeln!(buffer, "event: {event_type}");
+    }
+    // multi-line data is split into multiple data fields, client joins them with \n
+    for line in event.data.split('\n') {
+        let _result = writeln!(buffer, "data: {line}");
+    }
+    buffer.push('\n');
+    buffer
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Event;
+
+    #[test]
+    fn encode_event() {
+        let event = Event { id: Some("1".to_owned()), r#type: Some("log".to_owned()), data: "line1\nline2".to_owned() };
+        assert_eq!(super::encode_event(&event), "id: 1\nevent: log\ndata: line1\ndata: line2\n\n");
+
+        let data_only = Event { id: None, r#type: None, data: "message".to_owned() };
+        assert_eq!(super::encode_event(&data_only), "data: message\n\n");
+    }
+}
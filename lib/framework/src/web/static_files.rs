@@ -0,0 +1,159 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use axum::Router;
+use axum::extract::Request;
+use axum::extract::State;
+use axum::http::HeaderValue;
+use axum::http::StatusCode;
+use axum::http::Uri;
+use axum::http::header;
+use axum::middleware;
+use axum::middleware::Next;
+use axum::response::IntoResponse as _;
+use axum::response::Response;
+use sha2::Digest as _;
+use sha2::Sha256;
+use tower_http::services::ServeDir;
+
+use crate::exception::Exception;
+use crate::write_str;
+
+const IMMUTABLE: &str = "public, max-age=31536000, immutable";
+
+pub struct StaticFilesConfig {
+    // url path maps to file under dir, e.g. /static/css/index.css => {dir}/static/css/index.css
+    pub dir: PathBuf,
+    // by file extension, e.g. "css" => "public, max-age=3600"
+    pub cache_control: HashMap<&'static str, &'static str>,
+    // serve /static/css/index.{hash}.css with immutable cache control, use asset_url() to get fingerprinted url
+    pub fingerprint: bool,
+}
+
+// files are hashed on startup for etag and fingerprint, changes after startup are not reflected
+pub struct StaticFiles {
+    config: StaticFilesConfig,
+    assets: HashMap<String, Asset>,
+    // fingerprinted path -> path
+    fingerprints: HashMap<String, String>,
+}
+
+struct Asset {
+    etag: HeaderValue,
+    fingerprinted_path: String,
+}
+
+impl StaticFiles {
+    pub fn new(config: StaticFilesConfig) -> Result<Self, Exception> {
+        let mut files = vec![];
+        list_files(&config.dir, &mut files)?;
+        let mut assets = HashMap::new();
+        let mut fingerprints = HashMap::new();
+        for file in files {
+            let Some(path) = file.strip_prefix(&config.dir)?.to_str().map(|path| format!("/{path}")) else {
+                continue;
+            };
+            let mut hash = String::with_capacity(16);
+            for byte in Sha256::digest(fs::read(&file)?).iter().take(8) {
+                write_str!(hash, "{byte:02x}");
+            }
+            let fingerprinted_path = fingerprinted_path(&path, &hash);
+            fingerprints.insert(fingerprinted_path.clone(), path.clone());
+            assets.insert(path, Asset { etag: HeaderValue::from_str(&format!("\"{hash}\""))?, fingerprinted_path });
+        }
+        Ok(Self { config, assets, fingerprints })
+    }
+
+    // returns fingerprinted url if enabled, e.g. "/static/css/index.css" => "/static/css/index.3f2a1b2c4d5e6f70.css"
+    pub fn asset_url<'a>(&'a self, path: &'a str) -> &'a str {
+        if self.config.fingerprint
+            && let Some(asset) = self.assets.get(path)
+        {
+            return &asset.fingerprinted_path;
+        }
+        path
+    }
+
+    // e.g. router.route_service("/static/{*path}", static_files.into_service())
+    pub fn into_service(self) -> Router {
+        let serve_dir = ServeDir::new(&self.config.dir);
+        Router::new()
+            .fallback_service(serve_dir)
+            .layer(middleware::from_fn_with_state(Arc::new(self), static_files_layer))
+    }
+
+    fn cache_control(&self, path: &str, fingerprinted: bool) -> Option<&'static str> {
+        if fingerprinted {
+            return Some(IMMUTABLE);
+        }
+        let extension = Path::new(path).extension().and_then(|extension| extension.to_str())?;
+        self.config.cache_control.get(extension).copied()
+    }
+}
+
+async fn static_files_layer(State(files): State<Arc<StaticFiles>>, mut request: Request, next: Next) -> Response {
+    let mut path = request.uri().path().to_owned();
+    let mut fingerprinted = false;
+    if files.config.fingerprint
+        && let Some(original_path) = files.fingerprints.get(&path)
+        && let Ok(uri) = original_path.parse::<Uri>()
+    {
+        *request.uri_mut() = uri;
+        path.clone_from(original_path);
+        fingerprinted = true;
+    }
+    let cache_control = files.cache_control(&path, fingerprinted);
+    let etag = files.assets.get(&path).map(|asset| asset.etag.clone());
+
+    let mut response = if let Some(ref etag) = etag
+        && request.headers().get(header::IF_NONE_MATCH) == Some(etag)
+    {
+        StatusCode::NOT_MODIFIED.into_response()
+    } else {
+        next.run(request).await
+    };
+    if response.status() == StatusCode::OK || response.status() == StatusCode::NOT_MODIFIED {
+        let headers = response.headers_mut();
+        if let Some(etag) = etag {
+            headers.insert(header::ETAG, etag);
+        }
+        if let Some(cache_control) = cache_control {
+            headers.insert(header::CACHE_CONTROL, HeaderValue::from_static(cache_control));
+        }
+    }
+    response
+}
+
+fn list_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<(), Exception> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            list_files(&path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn fingerprinted_path(path: &str, hash: &str) -> String {
+    let (directory, file) = path.rsplit_once('/').unwrap_or(("", path));
+    match file.rsplit_once('.') {
+        Some((name, extension)) if !name.is_empty() => format!("{directory}/{name}.{hash}.{extension}"),
+        _ => format!("{directory}/{file}.{hash}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn fingerprinted_path() {
+        assert_eq!(super::fingerprinted_path("/static/css/index.css", "abcd"), "/static/css/index.abcd.css");
+        assert_eq!(super::fingerprinted_path("/static/js/app.min.js", "abcd"), "/static/js/app.min.abcd.js");
+        assert_eq!(super::fingerprinted_path("/static/LICENSE", "abcd"), "/static/LICENSE.abcd");
+        assert_eq!(super::fingerprinted_path("/.htaccess", "abcd"), "/.htaccess.abcd");
+    }
+}
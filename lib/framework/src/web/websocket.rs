@@ -0,0 +1,117 @@
+use std::time::Instant;
+
+use axum::extract::ws;
+use axum::extract::ws::CloseFrame;
+pub use axum::extract::ws::WebSocketUpgrade;
+use axum::extract::ws::close_code;
+use axum::response::Response;
+use tokio_util::sync::CancellationToken;
+
+use crate::exception::Exception;
+pub use crate::http::websocket::Message;
+use crate::log;
+use crate::log::current_action_id;
+use crate::log::current_correlation_id;
+
+// server side socket, closed with "going away" when shutdown signal fires
+pub struct WebSocket {
+    socket: ws::WebSocket,
+    shutdown_signal: CancellationToken,
+
+    start_time: Instant,
+    read_bytes: usize,
+    read_entries: usize,
+    write_bytes: usize,
+    write_entries: usize,
+}
+
+// each connection runs in its own "ws" action, ref_id links to http action of upgrade request
+pub fn upgrade<F, Fut>(upgrade: WebSocketUpgrade, shutdown_signal: CancellationToken, handler: F) -> Response
+where
+    F: FnOnce(WebSocket) -> Fut + Send + 'static,
+    Fut: Future<Output = Result<(), Exception>> + Send,
+{
+    let ref_id = current_action_id().map(|id| vec![id]);
+    let correlation_id = current_correlation_id();
+    upgrade.on_upgrade(move |socket| async move {
+        let _result = log::action("ws", ref_id, async move {
+            if let Some(correlation_id) = correlation_id {
+                context!(correlation_id = correlation_id);
+            }
+            let socket = WebSocket {
+                socket,
+                shutdown_signal,
+                start_time: Instant::now(),
+                read_bytes: 0,
+                read_entries: 0,
+                write_bytes: 0,
+                write_entries: 0,
+            };
+            handler(socket).await
+        })
+        .await;
+    })
+}
+
+impl WebSocket {
+    // returns none if client closed connection or shutdown signal fired
+    pub async fn receive(&mut self) -> Option<Result<Message, Exception>> {
+        loop {
+            let message = tokio::select! {
+                message = self.socket.recv() => message?,
+                () = self.shutdown_signal.cancelled() => {
+                    log!("[ws] shutdown, close socket");
+                    let close = CloseFrame { code: close_code::AWAY, reason: "server shutdown".into() };
+                    let _result = self.socket.send(ws::Message::Close(Some(close))).await;
+                    return None;
+                }
+            };
+            match message {
+                Ok(ws::Message::Text(text)) => {
+                    log!("[ws] read, text={}", text.as_str());
+                    self.read_bytes += text.len();
+                    self.read_entries += 1;
+                    return Some(Ok(Message::Text(text.as_str().to_owned())));
+                }
+                Ok(ws::Message::Binary(bytes)) => {
+                    log!("[ws] read, binary_length={}", bytes.len());
+                    self.read_bytes += bytes.len();
+                    self.read_entries += 1;
+                    return Some(Ok(Message::Binary(bytes)));
+                }
+                Ok(ws::Message::Close(_)) => return None,
+                Ok(ws::Message::Ping(_) | ws::Message::Pong(_)) => {}
+                Err(err) => return Some(Err(exception!("failed to receive websocket message", source = err))),
+            }
+        }
+    }
+
+    pub async fn send(&mut self, message: Message) -> Result<(), Exception> {
+        let message = match message {
+            Message::Text(text) => {
+                log!("[ws] write, text={text}");
+                self.write_bytes += text.len();
+                ws::Message::Text(text.into())
+            }
+            Message::Binary(bytes) => {
+                log!("[ws] write, binary_length={}", bytes.len());
+                self.write_bytes += bytes.len();
+                ws::Message::Binary(bytes)
+            }
+        };
+        self.write_entries += 1;
+        self.socket.send(message).await.map_err(|err| exception!("failed to send websocket message", source = err))
+    }
+}
+
+impl Drop for WebSocket {
+    fn drop(&mut self) {
+        stats!(
+            ws_read_entries = self.read_entries,
+            ws_read_bytes = self.read_bytes,
+            ws_write_entries = self.write_entries,
+            ws_write_bytes = self.write_bytes,
+            ws_elapsed = self.start_time.elapsed().as_nanos()
+        );
+    }
+}
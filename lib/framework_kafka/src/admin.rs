@@ -0,0 +1,95 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use framework::exception;
+use framework::exception::Exception;
+use framework::log;
+use rdkafka::ClientConfig;
+use rdkafka::admin::AdminClient;
+use rdkafka::admin::AdminOptions;
+use rdkafka::admin::NewTopic;
+use rdkafka::admin::ResourceSpecifier;
+use rdkafka::admin::TopicReplication;
+use rdkafka::client::DefaultClientContext;
+use rdkafka::types::RDKafkaErrorCode;
+use tokio::task;
+
+use crate::security::SecurityConfig;
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+pub struct TopicConfig {
+    pub name: &'static str,
+    pub partitions: i32,
+    pub replication_factor: i32,
+    // retention.ms, use broker default if not set
+    pub retention: Option<Duration>,
+}
+
+// e.g. create topics on startup, rather than relying on auto.create.topics.enable of broker
+pub struct Admin {
+    // shared with blocking metadata request
+    client: Arc<AdminClient<DefaultClientContext>>,
+}
+
+impl Admin {
+    pub fn new(bootstrap_servers: &str, security: &SecurityConfig) -> Self {
+        let mut config = ClientConfig::new();
+        config.set("bootstrap.servers", bootstrap_servers);
+        security.apply(&mut config);
+        let client = config.create().expect("failed to create admin client"); // fail fast on startup
+        Self { client: Arc::new(client) }
+    }
+
+    // returns false if topic already exists, existing topic is not altered
+    pub async fn create_topic(&self, topic: &TopicConfig) -> Result<bool, Exception> {
+        let retention = topic.retention.map(|retention| retention.as_millis().to_string());
+        let mut new_topic =
+            NewTopic::new(topic.name, topic.partitions, TopicReplication::Fixed(topic.replication_factor));
+        if let Some(ref retention) = retention {
+            new_topic = new_topic.set("retention.ms", retention);
+        }
+        let options = AdminOptions::new().operation_timeout(Some(TIMEOUT));
+        let results = self.client.create_topics([&new_topic], &options).await?;
+        // one result per requested topic
+        match results.into_iter().next() {
+            Some(Ok(name)) => {
+                log!("topic created, topic={name}, partitions={}", topic.partitions);
+                Ok(true)
+            }
+            Some(Err((_, RDKafkaErrorCode::TopicAlreadyExists))) | None => Ok(false),
+            Some(Err((name, code))) => Err(exception!(format!("failed to create topic, topic={name}"), source = code)),
+        }
+    }
+
+    pub async fn list_topics(&self) -> Result<Vec<String>, Exception> {
+        let client = Arc::clone(&self.client);
+        // fetch_metadata blocks until broker responds or timeout
+        let metadata = task::spawn_blocking(move || client.inner().fetch_metadata(None, TIMEOUT)).await??;
+        Ok(metadata
+            .topics()
+            .iter()
+            .map(|topic| topic.name().to_owned())
+            .filter(|name| !name.starts_with("__"))
+            .collect())
+    }
+
+    // returns non default configs, e.g. retention.ms, cleanup.policy
+    pub async fn describe_config(&self, topic: &str) -> Result<BTreeMap<String, String>, Exception> {
+        let results = self.client.describe_configs([&ResourceSpecifier::Topic(topic)], &AdminOptions::new()).await?;
+        let mut configs = BTreeMap::new();
+        for result in results {
+            let resource = result
+                .map_err(|code| exception!(format!("failed to describe config, topic={topic}"), source = code))?;
+            for entry in resource.entries {
+                if !entry.is_default
+                    && let Some(value) = entry.value
+                {
+                    configs.insert(entry.name, value);
+                }
+            }
+        }
+        Ok(configs)
+    }
+}
@@ -0,0 +1,323 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+
+use chrono::DateTime;
+use chrono::Utc;
+use framework::console;
+use framework::exception::Severity;
+use framework::log::appender::ActionLog;
+use framework::log::appender::ActionLogAppender;
+use framework::log::appender::PerformanceStat;
+use framework::log::metrics::Metrics;
+use framework::network::hostname;
+use framework::string::StringExt as _;
+use serde::Serialize;
+use tokio::sync::mpsc;
+use tokio::sync::mpsc::Receiver;
+use tokio::sync::mpsc::Sender;
+
+use crate::Topic;
+use crate::producer::Producer;
+
+const ACTION_LOG_TOPIC: &str = "action-log-v2";
+const STAT_TOPIC: &str = "stat";
+const MAX_QUEUE_SIZE: usize = 10_000;
+const MAX_BATCH_SIZE: usize = 500;
+// keep message under default max_request_size
+const MAX_TRACE_LOG_LEN: usize = 900_000;
+
+// action log message schema from java core-ng framework, consumed by log_processor
+#[derive(Debug, Serialize)]
+struct ActionLogMessage {
+    id: String,
+    date: DateTime<Utc>,
+    app: &'static str,
+    host: &'static str,
+    result: &'static str,
+    action: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    correlation_ids: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    clients: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ref_ids: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error_code: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error_message: Option<String>,
+    elapsed: u64,
+    context: HashMap<&'static str, Vec<String>>,
+    stats: HashMap<String, f64>,
+    perf_stats: HashMap<String, PerformanceStatMessage>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    trace_log: Option<String>,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+struct PerformanceStatMessage {
+    total_elapsed: u64,
+    count: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    read_entries: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    write_entries: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    read_bytes: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    write_bytes: Option<u64>,
+}
+
+impl From<&PerformanceStat> for PerformanceStatMessage {
+    fn from(stat: &PerformanceStat) -> Self {
+        Self {
+            total_elapsed: stat.total_elapsed,
+            count: stat.count,
+            read_entries: stat.read_entries,
+            write_entries: stat.write_entries,
+            read_bytes: stat.read_bytes,
+            write_bytes: stat.write_bytes,
+        }
+    }
+}
+
+// stat message schema from java core-ng framework, consumed by log_processor
+#[derive(Debug, Serialize)]
+struct StatMessage {
+    id: String,
+    date: DateTime<Utc>,
+    app: &'static str,
+    host: &'static str,
+    result: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error_code: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error_message: Option<String>,
+    stats: HashMap<&'static str, f64>,
+    info: HashMap<&'static str, String>,
+}
+
+enum LogMessage {
+    Action(ActionLogMessage),
+    Stat(StatMessage),
+}
+
+// actions and metrics are queued and sent in batches by background task, queued messages are dropped if full or on process exit
+pub struct KafkaAppender {
+    sender: Sender<LogMessage>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl KafkaAppender {
+    // must be called within tokio runtime, producer is dedicated to action logs
+    pub fn new(producer: Producer) -> Self {
+        let (sender, receiver) = mpsc::channel(MAX_QUEUE_SIZE);
+        tokio::spawn(send_messages(producer, receiver));
+        Self { sender, dropped: Arc::new(AtomicU64::new(0)) }
+    }
+
+    // reports dropped messages since last collection
+    pub fn appender_metrics(&self) -> impl Fn(&mut Metrics) + use<> {
+        let dropped = Arc::clone(&self.dropped);
+        move |metrics| {
+            metrics.stats.push(("kafka_appender_dropped", dropped.swap(0, Ordering::Relaxed)));
+        }
+    }
+
+    fn send(&self, message: LogMessage) {
+        if self.sender.try_send(message).is_err() {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+impl ActionLogAppender for KafkaAppender {
+    fn append(&self, action: &ActionLog<'_>) {
+        self.send(LogMessage::Action(action_log_message(action)));
+    }
+
+    // metrics are published as stat message, same as java apps, so they appear in existing stat dashboards
+    fn append_metrics(&self, metrics: &Metrics, app: &'static str) {
+        self.send(LogMessage::Stat(stat_message(metrics, app)));
+    }
+}
+
+// runs outside action, log!() and stats!() within producer are no-op, so sending won't produce more actions
+async fn send_messages(producer: Producer, mut receiver: Receiver<LogMessage>) {
+    let action_log_topic = Topic::new(ACTION_LOG_TOPIC);
+    let stat_topic = Topic::new(STAT_TOPIC);
+    let mut messages = Vec::with_capacity(MAX_BATCH_SIZE);
+    while receiver.recv_many(&mut messages, MAX_BATCH_SIZE).await > 0 {
+        let mut action_logs = vec![];
+        let mut stats = vec![];
+        for message in &messages {
+            match message {
+                LogMessage::Action(action_log) => action_logs.push((Some(action_log.id.clone()), action_log)),
+                LogMessage::Stat(stat) => stats.push((Some(stat.id.clone()), stat)),
+            }
+        }
+        let action_log_count = action_logs.len();
+        if action_log_count > 0
+            && let Err(e) = producer.send_batch(&action_log_topic, action_logs).await
+        {
+            console!("WARN failed to send action logs, count={action_log_count}, error={e}");
+        }
+        let stat_count = stats.len();
+        if stat_count > 0
+            && let Err(e) = producer.send_batch(&stat_topic, stats).await
+        {
+            console!("WARN failed to send stats, count={stat_count}, error={e}");
+        }
+        messages.clear();
+    }
+}
+
+const fn result(severity: Option<Severity>) -> &'static str {
+    match severity {
+        None => "OK",
+        Some(Severity::Warn) => "WARN",
+        Some(Severity::Error) => "ERROR",
+    }
+}
+
+fn stat_message(metrics: &Metrics, app: &'static str) -> StatMessage {
+    StatMessage {
+        id: metrics.id.to_string(),
+        date: metrics.date,
+        app,
+        host: hostname(),
+        result: result(metrics.error.as_ref().map(|error| error.severity)),
+        error_code: metrics.error.as_ref().and_then(|error| error.code),
+        error_message: metrics.error.as_ref().map(|error| error.message.clone()),
+        stats: metrics.stats.iter().map(|(key, value)| (*key, *value as f64)).collect(),
+        info: metrics.info.iter().cloned().collect(),
+    }
+}
+
+fn action_log_message(action: &ActionLog<'_>) -> ActionLogMessage {
+    let result = result(action.severity);
+
+    let mut context: HashMap<&'static str, Vec<String>> = HashMap::new();
+    for (key, values) in action.context {
+        context.entry(key).or_default().extend(values.iter().cloned());
+    }
+    // context!(correlation_id = ...) and context!(client = ...) are top level fields in java schema
+    let correlation_ids = context.remove("correlation_id");
+    let clients = context.remove("client");
+
+    let stats = action
+        .stats
+        .iter()
+        .filter(|(key, _)| *key != "elapsed")
+        .map(|(key, value)| (key.to_string(), *value as f64))
+        .collect();
+    let perf_stats = action
+        .perf_stats
+        .iter()
+        .map(|(operation, stat)| ((*operation).to_owned(), PerformanceStatMessage::from(stat)))
+        .collect();
+
+    ActionLogMessage {
+        id: action.id.clone(),
+        date: action.date,
+        app: action.app,
+        host: action.host,
+        result,
+        action: action.kind,
+        correlation_ids,
+        clients,
+        ref_ids: action.ref_id.map(<[String]>::to_vec),
+        error_code: action.error_code,
+        error_message: action.error_message.map(str::to_owned),
+        elapsed: action.stats.get("elapsed").copied().unwrap_or_default(),
+        context,
+        stats,
+        perf_stats,
+        trace_log: action.trace.map(trace_log),
+    }
+}
+
+fn trace_log(logs: &[String]) -> String {
+    let mut trace = String::new();
+    for line in logs {
+        if trace.len() + line.len() >= MAX_TRACE_LOG_LEN {
+            trace.push_str(line.truncate_to_max(MAX_TRACE_LOG_LEN - trace.len()));
+            trace.push_str("...(truncated)");
+            break;
+        }
+        trace.push_str(line);
+        trace.push('\n');
+    }
+    trace
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use chrono::Utc;
+    use framework::log::appender::ActionLog;
+    use framework::log::id_generator::next_id;
+    use framework::log::metrics::Metrics;
+
+    use super::action_log_message;
+    use super::stat_message;
+    use super::trace_log;
+
+    #[test]
+    fn action_to_action_log_message() {
+        let context = vec![
+            ("topic", vec!["topic1".to_owned()]),
+            ("client", vec!["producer-app".to_owned()]),
+            ("correlation_id", vec!["id1".to_owned(), "id2".to_owned()]),
+        ];
+        let action = ActionLog {
+            id: "action-1".to_owned(),
+            date: Utc::now(),
+            app: "test-app",
+            host: "host-1",
+            kind: "message",
+            severity: None,
+            ref_id: None,
+            error_code: None,
+            error_message: None,
+            context: &context,
+            stats: &HashMap::new(),
+            perf_stats: &HashMap::new(),
+            trace: None,
+            spans: &[],
+        };
+
+        let message = action_log_message(&action);
+        assert_eq!(message.clients, Some(vec!["producer-app".to_owned()]));
+        assert_eq!(message.correlation_ids, Some(vec!["id1".to_owned(), "id2".to_owned()]));
+        assert_eq!(message.context, HashMap::from([("topic", vec!["topic1".to_owned()])]));
+    }
+
+    #[test]
+    fn metrics_to_stat_message() {
+        let date = Utc::now();
+        let metrics = Metrics {
+            id: next_id(date.timestamp_millis()),
+            date,
+            error: None,
+            stats: vec![("process_cpu_usage", 100), ("process_open_fds", 20)],
+            info: vec![("runtime", "tokio".to_owned())],
+        };
+
+        let message = stat_message(&metrics, "test-app");
+        assert_eq!(message.result, "OK");
+        assert_eq!(message.stats, HashMap::from([("process_cpu_usage", 100.0), ("process_open_fds", 20.0)]));
+        assert_eq!(message.info, HashMap::from([("runtime", "tokio".to_owned())]));
+    }
+
+    #[test]
+    fn truncate_trace_log() {
+        assert_eq!(trace_log(&["line1".to_owned(), "line2".to_owned()]), "line1\nline2\n");
+
+        let trace = trace_log(&["a".repeat(600_000), "b".repeat(600_000)]);
+        assert_eq!(trace.len(), super::MAX_TRACE_LOG_LEN + "...(truncated)".len());
+        assert!(trace.ends_with("b...(truncated)"));
+    }
+}
@@ -0,0 +1,117 @@
+use std::borrow::Cow;
+use std::fmt::Debug;
+
+use bytes::Bytes;
+use framework::exception::Exception;
+use framework::json::from_json_slice;
+use framework::json::to_json;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+pub mod avro;
+pub mod protobuf;
+
+// encodes message for producer, payload is sent as is
+pub trait Encoder<T>: Send + Sync {
+    fn encode(&self, topic: &str, message: &T) -> impl Future<Output = Result<Vec<u8>, Exception>> + Send;
+
+    // for logging
+    fn display<'a>(&self, message: &T, payload: &'a [u8]) -> Cow<'a, str>;
+}
+
+// decodes raw payload for consumer
+pub trait Decoder<T>: Clone + Send + Sync + 'static {
+    // e.g. fetch writer schema, called once before decode(), decode() may be called again on retry
+    fn prepare(&self, _payload: &[u8]) -> impl Future<Output = Result<(), Exception>> + Send {
+        async { Ok(()) }
+    }
+
+    fn decode(&self, payload: &[u8]) -> Result<T, Exception>;
+
+    // called by consumer, override to keep payload without copy
+    fn decode_bytes(&self, payload: &Bytes) -> Result<T, Exception> {
+        self.decode(payload)
+    }
+
+    // for logging, message is decoded result, none if failed to decode
+    fn display<'a>(&self, message: Option<&T>, payload: &'a [u8]) -> Cow<'a, str>;
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JsonCodec;
+
+impl<T> Encoder<T> for JsonCodec
+where
+    T: Serialize + Debug,
+{
+    fn encode(&self, _topic: &str, message: &T) -> impl Future<Output = Result<Vec<u8>, Exception>> + Send {
+        let payload = to_json(message).map(String::into_bytes);
+        async { payload }
+    }
+
+    fn display<'a>(&self, _message: &T, payload: &'a [u8]) -> Cow<'a, str> {
+        String::from_utf8_lossy(payload)
+    }
+}
+
+impl<T> Decoder<T> for JsonCodec
+where
+    T: DeserializeOwned,
+{
+    fn decode(&self, payload: &[u8]) -> Result<T, Exception> {
+        from_json_slice(payload)
+    }
+
+    fn display<'a>(&self, _message: Option<&T>, payload: &'a [u8]) -> Cow<'a, str> {
+        String::from_utf8_lossy(payload)
+    }
+}
+
+// payload is passed through as is, e.g. for handlers forwarding or storing messages without deserialization
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RawCodec;
+
+impl Encoder<Bytes> for RawCodec {
+    fn encode(&self, _topic: &str, message: &Bytes) -> impl Future<Output = Result<Vec<u8>, Exception>> + Send {
+        let payload = message.to_vec();
+        async { Ok(payload) }
+    }
+
+    fn display<'a>(&self, _message: &Bytes, payload: &'a [u8]) -> Cow<'a, str> {
+        String::from_utf8_lossy(payload)
+    }
+}
+
+impl Decoder<Bytes> for RawCodec {
+    fn decode(&self, payload: &[u8]) -> Result<Bytes, Exception> {
+        Ok(Bytes::copy_from_slice(payload))
+    }
+
+    fn decode_bytes(&self, payload: &Bytes) -> Result<Bytes, Exception> {
+        Ok(payload.clone())
+    }
+
+    fn display<'a>(&self, _message: Option<&Bytes>, payload: &'a [u8]) -> Cow<'a, str> {
+        String::from_utf8_lossy(payload)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+
+    use super::Decoder;
+    use super::Encoder as _;
+    use super::RawCodec;
+
+    #[tokio::test]
+    async fn raw_codec() {
+        let payload = Bytes::from_static(b"payload");
+        let decoded = RawCodec.decode_bytes(&payload).unwrap();
+        // shares same buffer
+        assert_eq!(decoded.as_ptr(), payload.as_ptr());
+        assert_eq!(RawCodec.decode(b"payload").unwrap(), payload);
+        assert_eq!(RawCodec.encode("topic", &payload).await.unwrap(), b"payload");
+        assert_eq!(Decoder::display(&RawCodec, Some(&decoded), b"payload"), "payload");
+    }
+}
@@ -0,0 +1,227 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use apache_avro::Schema;
+use apache_avro::from_value;
+use apache_avro::reader::datum::GenericDatumReader;
+use apache_avro::to_value;
+use apache_avro::types::Value;
+use apache_avro::writer::datum::GenericDatumWriter;
+use framework::exception;
+use framework::exception::Exception;
+use framework::http::HttpClient;
+use framework::http::HttpClientConfig;
+use framework::http::HttpRequest;
+use framework::http::Method;
+use framework::json::from_json;
+use serde::Deserialize;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+use crate::codec::Decoder;
+use crate::codec::Encoder;
+
+// confluent wire format, magic byte + 4 bytes big endian schema id + avro binary
+const MAGIC_BYTE: u8 = 0;
+
+// message is encoded in confluent wire format, with schema of subject "{topic}-value"
+#[derive(Clone)]
+pub struct AvroCodec {
+    registry: Arc<SchemaRegistry>,
+}
+
+impl AvroCodec {
+    pub const fn new(registry: Arc<SchemaRegistry>) -> Self {
+        Self { registry }
+    }
+}
+
+impl<T> Encoder<T> for AvroCodec
+where
+    T: Serialize + Debug,
+{
+    fn encode(&self, topic: &str, message: &T) -> impl Future<Output = Result<Vec<u8>, Exception>> + Send {
+        // subject follows TopicNameStrategy
+        let subject = format!("{topic}-value");
+        let value = to_value(message);
+        async move {
+            let (id, schema) = self.registry.latest_schema(&subject).await?;
+            let value = value?.resolve(&schema)?;
+            let datum = GenericDatumWriter::builder(&schema).build()?.write_value_to_vec(value)?;
+            let mut payload = Vec::with_capacity(datum.len() + 5);
+            payload.push(MAGIC_BYTE);
+            #[allow(clippy::big_endian_bytes)]
+            payload.extend_from_slice(&id.to_be_bytes());
+            payload.extend(datum);
+            Ok(payload)
+        }
+    }
+
+    fn display<'a>(&self, message: &T, _payload: &'a [u8]) -> Cow<'a, str> {
+        Cow::Owned(format!("{message:?}"))
+    }
+}
+
+impl<T> Decoder<T> for AvroCodec
+where
+    T: DeserializeOwned + Debug,
+{
+    async fn prepare(&self, payload: &[u8]) -> Result<(), Exception> {
+        let (id, _) = avro_payload(payload)?;
+        self.registry.schema(id).await?;
+        Ok(())
+    }
+
+    fn decode(&self, payload: &[u8]) -> Result<T, Exception> {
+        let value = self.registry.decode(payload)?;
+        Ok(from_value(&value)?)
+    }
+
+    fn display<'a>(&self, message: Option<&T>, payload: &'a [u8]) -> Cow<'a, str> {
+        message.map_or_else(|| format!("avro(bytes={})", payload.len()), |message| format!("{message:?}")).into()
+    }
+}
+
+// confluent schema registry client, schemas are cached by id and subject, cached latest schema is used until restart
+pub struct SchemaRegistry {
+    url: String,
+    // api key and secret for confluent cloud
+    credentials: Option<(String, String)>,
+    client: HttpClient,
+    schemas: Mutex<HashMap<u32, Arc<Schema>>>,
+    subjects: Mutex<HashMap<String, (u32, Arc<Schema>)>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SchemaResponse {
+    id: Option<u32>,
+    schema: String,
+}
+
+impl SchemaRegistry {
+    pub fn new(url: String, credentials: Option<(String, String)>) -> Self {
+        Self {
+            url,
+            credentials,
+            client: HttpClient::new(HttpClientConfig::default()),
+            schemas: Mutex::new(HashMap::new()),
+            subjects: Mutex::new(HashMap::new()),
+        }
+    }
+
+    async fn latest_schema(&self, subject: &str) -> Result<(u32, Arc<Schema>), Exception> {
+        if let Some((id, schema)) = self.subjects.lock().unwrap().get(subject) {
+            return Ok((*id, Arc::clone(schema)));
+        }
+        let response = self.get(&format!("{}/subjects/{subject}/versions/latest", self.url)).await?;
+        let id = response.id.ok_or_else(|| exception!(format!("schema id not found, subject={subject}")))?;
+        let schema = Arc::new(Schema::parse_str(&response.schema)?);
+        self.schemas.lock().unwrap().insert(id, Arc::clone(&schema));
+        self.subjects.lock().unwrap().insert(subject.to_owned(), (id, Arc::clone(&schema)));
+        Ok((id, schema))
+    }
+
+    async fn schema(&self, id: u32) -> Result<Arc<Schema>, Exception> {
+        if let Some(schema) = self.cached_schema(id) {
+            return Ok(schema);
+        }
+        let response = self.get(&format!("{}/schemas/ids/{id}", self.url)).await?;
+        let schema = Arc::new(Schema::parse_str(&response.schema)?);
+        self.schemas.lock().unwrap().insert(id, Arc::clone(&schema));
+        Ok(schema)
+    }
+
+    fn cached_schema(&self, id: u32) -> Option<Arc<Schema>> {
+        self.schemas.lock().unwrap().get(&id).cloned()
+    }
+
+    fn decode(&self, payload: &[u8]) -> Result<Value, Exception> {
+        let (id, mut datum) = avro_payload(payload)?;
+        let schema = self.cached_schema(id).ok_or_else(|| exception!(format!("schema is not loaded, id={id}")))?;
+        Ok(GenericDatumReader::builder(&schema).build()?.read_value(&mut datum)?)
+    }
+
+    async fn get(&self, url: &str) -> Result<SchemaResponse, Exception> {
+        let mut request = HttpRequest::new(Method::GET, url);
+        if let Some((ref user, ref password)) = self.credentials {
+            request.basic_auth(user, password)?;
+        }
+        let response = self.client.execute(request).await?;
+        if response.status != 200 {
+            return Err(exception!(format!(
+                "failed to get schema, url={url}, status={}, body={}",
+                response.status,
+                String::from_utf8_lossy(&response.body)
+            )));
+        }
+        from_json(response.text()?)
+    }
+}
+
+#[allow(clippy::big_endian_bytes)]
+fn avro_payload(payload: &[u8]) -> Result<(u32, &[u8]), Exception> {
+    if let Some((&MAGIC_BYTE, rest)) = payload.split_first()
+        && let Some((id, datum)) = rest.split_first_chunk::<4>()
+    {
+        return Ok((u32::from_be_bytes(*id), datum));
+    }
+    Err(exception!("invalid avro payload, expect confluent wire format"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use apache_avro::Schema;
+    use serde::Deserialize;
+    use serde::Serialize;
+
+    use super::AvroCodec;
+    use super::SchemaRegistry;
+    use super::avro_payload;
+    use crate::codec::Decoder;
+    use crate::codec::Encoder as _;
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct TestMessage {
+        name: String,
+        count: i64,
+    }
+
+    #[tokio::test]
+    async fn avro() {
+        let schema = Arc::new(
+            Schema::parse_str(
+                r#"{"type":"record","name":"TestMessage","fields":[{"name":"name","type":"string"},{"name":"count","type":"long"}]}"#,
+            )
+            .unwrap(),
+        );
+        let registry = SchemaRegistry::new("http://localhost:8081".to_owned(), None);
+        registry.subjects.lock().unwrap().insert("test-value".to_owned(), (1, Arc::clone(&schema)));
+        registry.schemas.lock().unwrap().insert(1, schema);
+        let codec = AvroCodec::new(Arc::new(registry));
+
+        let message = TestMessage { name: "test".to_owned(), count: 10 };
+        let payload = codec.encode("test", &message).await.unwrap();
+        assert_eq!(payload.get(..5), Some([0, 0, 0, 0, 1].as_slice()));
+
+        Decoder::<TestMessage>::prepare(&codec, &payload).await.unwrap();
+        let decoded: TestMessage = codec.decode(&payload).unwrap();
+        assert_eq!(decoded, message);
+        assert_eq!(Decoder::display(&codec, Some(&decoded), &payload), r#"TestMessage { name: "test", count: 10 }"#);
+        assert_eq!(Decoder::<TestMessage>::display(&codec, None, &payload), "avro(bytes=11)");
+    }
+
+    #[test]
+    fn parse_avro_payload() {
+        let (id, datum) = avro_payload(&[0, 0, 0, 1, 2, 10, 20]).unwrap();
+        assert_eq!(id, 258);
+        assert_eq!(datum, &[10, 20]);
+
+        avro_payload(&[1, 0, 0, 0, 1]).unwrap_err();
+        avro_payload(&[0, 0, 1]).unwrap_err();
+    }
+}
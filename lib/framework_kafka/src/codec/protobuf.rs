@@ -0,0 +1,71 @@
+use std::borrow::Cow;
+use std::fmt::Debug;
+
+use framework::exception;
+use framework::exception::Exception;
+use prost::Message;
+
+use crate::codec::Decoder;
+use crate::codec::Encoder;
+
+// message is encoded as protobuf binary, e.g. struct generated by prost-build
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ProtobufCodec;
+
+impl<T> Encoder<T> for ProtobufCodec
+where
+    T: Message + Debug,
+{
+    fn encode(&self, _topic: &str, message: &T) -> impl Future<Output = Result<Vec<u8>, Exception>> + Send {
+        let payload = message.encode_to_vec();
+        async { Ok(payload) }
+    }
+
+    fn display<'a>(&self, message: &T, _payload: &'a [u8]) -> Cow<'a, str> {
+        Cow::Owned(format!("{message:?}"))
+    }
+}
+
+impl<T> Decoder<T> for ProtobufCodec
+where
+    T: Message + Default + Debug,
+{
+    fn decode(&self, payload: &[u8]) -> Result<T, Exception> {
+        T::decode(payload).map_err(|err| exception!("failed to decode protobuf message", source = err))
+    }
+
+    fn display<'a>(&self, message: Option<&T>, payload: &'a [u8]) -> Cow<'a, str> {
+        message.map_or_else(|| format!("protobuf(bytes={})", payload.len()), |message| format!("{message:?}")).into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ProtobufCodec;
+    use crate::codec::Decoder;
+    use crate::codec::Encoder as _;
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    struct TestMessage {
+        #[prost(string, tag = "1")]
+        name: String,
+        #[prost(int64, tag = "2")]
+        count: i64,
+    }
+
+    #[tokio::test]
+    async fn protobuf() {
+        let message = TestMessage { name: "test".to_owned(), count: 10 };
+        let payload = ProtobufCodec.encode("test", &message).await.unwrap();
+
+        let decoded: TestMessage = ProtobufCodec.decode(&payload).unwrap();
+        assert_eq!(decoded, message);
+        assert_eq!(
+            Decoder::display(&ProtobufCodec, Some(&decoded), &payload),
+            r#"TestMessage { name: "test", count: 10 }"#
+        );
+        assert_eq!(Decoder::<TestMessage>::display(&ProtobufCodec, None, &[0xff]), "protobuf(bytes=1)");
+
+        Decoder::<TestMessage>::decode(&ProtobufCodec, &[0xff]).unwrap_err();
+    }
+}
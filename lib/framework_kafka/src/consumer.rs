@@ -1,13 +1,18 @@
 use std::any::type_name;
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
 use std::collections::HashMap;
 use std::collections::HashSet;
 use std::future::Future;
+use std::mem;
 use std::pin::Pin;
 use std::str::from_utf8;
 use std::sync::Arc;
+use std::sync::Mutex;
 use std::time::Duration;
 use std::time::Instant;
 
+use bytes::Bytes;
 use chrono::DateTime;
 use chrono::SecondsFormat;
 use chrono::Utc;
@@ -15,32 +20,51 @@ use framework::console;
 use framework::context;
 use framework::exception;
 use framework::exception::Exception;
-use framework::json::from_json;
 use framework::log;
 use framework::log::metrics::Counter;
 use framework::log::metrics::Metrics;
+use framework::log::parse_traceparent;
+// re-exported, same policy is shared with scheduled jobs
+pub use framework::retry::RetryPolicy;
+use framework::retry::with_retry;
 use framework::stats;
+use framework::write_str;
 use futures::future::join_all;
 use rdkafka::ClientConfig;
 use rdkafka::Message as _;
+use rdkafka::Offset;
 use rdkafka::Timestamp;
+use rdkafka::TopicPartitionList;
 use rdkafka::config::RDKafkaLogLevel;
-use rdkafka::consumer::BaseConsumer;
 use rdkafka::consumer::CommitMode;
 use rdkafka::consumer::Consumer as _;
+use rdkafka::consumer::ConsumerGroupMetadata;
+use rdkafka::consumer::StreamConsumer;
 use rdkafka::error::KafkaError;
+use rdkafka::message::BorrowedHeaders;
 use rdkafka::message::BorrowedMessage;
 use rdkafka::message::Headers as _;
-use rdkafka::message::OwnedMessage;
-use rdkafka::util::Timeout;
-use serde::de::DeserializeOwned;
+use rdkafka::message::OwnedHeaders;
+use regex::Regex;
+use tokio::sync::Semaphore;
+use tokio::task;
+use tokio::task::JoinHandle;
 use tokio::task::JoinSet;
 use tokio::time;
 use tokio_util::sync::CancellationToken;
 
 use crate::CLIENT;
+use crate::CORRELATION_ID;
 use crate::REF_ID;
+use crate::TRACEPARENT;
 use crate::Topic;
+use crate::codec::Decoder;
+use crate::codec::JsonCodec;
+use crate::dead_letter::DeadLetterProducer;
+use crate::rebalance::RebalanceCallback;
+use crate::rebalance::RebalanceContext;
+pub use crate::rebalance::RebalanceEvent;
+use crate::security::SecurityConfig;
 
 // decoded message handed to call-site handlers; the framework works with the raw rdkafka message.
 pub struct Message<T> {
@@ -48,26 +72,199 @@ pub struct Message<T> {
     pub payload: T,
 }
 
-type MessageHandler<S> =
-    Box<dyn Fn(S, Vec<BorrowedMessage>) -> Pin<Box<dyn Future<Output = Result<(), Exception>> + Send>> + Send>;
+// copied from polled message once, payload is shared with handler without copy, e.g. by RawCodec,
+// headers and key are decoded only when read
+pub(crate) struct RawMessage {
+    pub(crate) topic: String,
+    pub(crate) partition: i32,
+    pub(crate) offset: i64,
+    pub(crate) timestamp: Timestamp,
+    pub(crate) key: Option<Vec<u8>>,
+    // none for tombstone
+    pub(crate) payload: Option<Bytes>,
+    pub(crate) headers: Option<OwnedHeaders>,
+}
+
+impl RawMessage {
+    fn new(message: &BorrowedMessage) -> Self {
+        Self {
+            topic: message.topic().to_owned(),
+            partition: message.partition(),
+            offset: message.offset(),
+            timestamp: message.timestamp(),
+            key: message.key().map(<[u8]>::to_vec),
+            payload: message.payload().map(Bytes::copy_from_slice),
+            headers: message.headers().map(BorrowedHeaders::detach),
+        }
+    }
+}
+
+// (topic, partition) -> next offset to consume
+type Offsets = BTreeMap<(String, i32), i64>;
+
+// (topic, None) applies to all partitions of topic
+type Seeks = BTreeMap<(String, Option<i32>), SeekPosition>;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SeekPosition {
+    // epoch millis, seek to earliest offset whose timestamp >= given time
+    Timestamp(i64),
+    Offset(i64),
+}
+
+// passed to bulk handler added by add_bulk_handler_with_ack(), offsets are committed only after commit() is called,
+// uncommitted messages will be consumed again after restart or rebalance,
+// if handler fails and messages are not sent to dead letter topic, partitions are consumed again from failed batch
+#[derive(Clone)]
+pub struct Ack {
+    offsets: Offsets,
+    pending_commits: Arc<Mutex<Offsets>>,
+    group_metadata: Option<Arc<ConsumerGroupMetadata>>,
+}
+
+impl Ack {
+    // can be called after handler returns, e.g. after file is flushed
+    pub fn commit(self) {
+        merge_offsets(&mut self.pending_commits.lock().unwrap(), self.offsets);
+    }
+
+    // offsets committed by transactional producer, refer to Producer::commit_transaction_with_ack()
+    pub(crate) fn transaction_offsets(&self) -> Result<(TopicPartitionList, Arc<ConsumerGroupMetadata>), Exception> {
+        let group_metadata = self
+            .group_metadata
+            .as_ref()
+            .map(Arc::clone)
+            .ok_or_else(|| exception!("consumer group metadata is not available"))?;
+        Ok((partition_list(&self.offsets)?, group_metadata))
+    }
+}
+
+// runtime control of consumption, e.g. pause when elasticsearch is down or disk is full, resume after recovered
+#[derive(Clone, Default)]
+pub struct ConsumerControl {
+    paused: Arc<Mutex<PausedPartitions>>,
+}
+
+// (topic, None) pauses all partitions of topic
+type PausedPartitions = BTreeSet<(String, Option<i32>)>;
+
+impl ConsumerControl {
+    pub fn pause_topic(&self, topic: &str) {
+        console!("pause kafka consumer, topic={topic}");
+        self.paused.lock().unwrap().insert((topic.to_owned(), None));
+    }
+
+    pub fn pause_partition(&self, topic: &str, partition: i32) {
+        console!("pause kafka consumer, topic={topic}, partition={partition}");
+        self.paused.lock().unwrap().insert((topic.to_owned(), Some(partition)));
+    }
+
+    // resumes topic and all its paused partitions
+    pub fn resume_topic(&self, topic: &str) {
+        console!("resume kafka consumer, topic={topic}");
+        self.paused.lock().unwrap().retain(|(paused_topic, _)| paused_topic != topic);
+    }
+
+    pub fn resume_partition(&self, topic: &str, partition: i32) {
+        console!("resume kafka consumer, topic={topic}, partition={partition}");
+        self.paused.lock().unwrap().remove(&(topic.to_owned(), Some(partition)));
+    }
+
+    pub fn is_paused(&self, topic: &str, partition: i32) -> bool {
+        let paused = self.paused.lock().unwrap();
+        paused.contains(&(topic.to_owned(), None)) || paused.contains(&(topic.to_owned(), Some(partition)))
+    }
+}
+
+type MessageHandler<S> = Box<
+    dyn Fn(S, Vec<BorrowedMessage>, PollStats) -> Pin<Box<dyn Future<Output = Result<(), Exception>> + Send>> + Send,
+>;
+
+// recorded as stats of message action, to tell kafka slowness from handler slowness
+#[derive(Clone, Copy)]
+struct PollStats {
+    poll_elapsed: Duration,
+    // of previous poll, as commit happens after handlers complete
+    commit_elapsed: Duration,
+}
+
+impl PollStats {
+    fn record(&self, batch_size: usize) {
+        stats!(
+            kafka_poll_elapsed = self.poll_elapsed.as_nanos(),
+            kafka_commit_elapsed = self.commit_elapsed.as_nanos(),
+            kafka_batch_size = batch_size
+        );
+    }
+}
 
 pub struct ConsumerConfig {
     pub poll_max_wait_time: Duration,
     pub poll_max_records: usize,
+    // max records per poll of given topics, e.g. so flood of "event" doesn't starve "action-log-v2",
+    // topic reached limit is paused for rest of the poll
+    pub topic_max_records: HashMap<&'static str, usize>,
+    // publish messages failed to decode or handle to "{topic}.dlq" with error headers, otherwise log and commit,
+    // except failed messages of handlers with ack, which are consumed again,
+    // messages failed to publish to dead letter topic are not committed and consumed again
+    pub dead_letter: bool,
+    // max in-flight handlers per topic, messages with same key are handled sequentially and count as one
+    pub max_concurrent_handlers: usize,
+    // interval to fetch committed and high watermark offsets of assigned partitions, reported by consumer_metrics()
+    pub lag_report_interval: Option<Duration>,
+    // max time to wait for in-flight handlers after shutdown signal, offsets of aborted handlers are not committed
+    pub shutdown_timeout: Duration,
+    pub security: SecurityConfig,
 }
 
 impl Default for ConsumerConfig {
     fn default() -> Self {
-        Self { poll_max_wait_time: Duration::from_secs(1), poll_max_records: 1000 }
+        Self {
+            poll_max_wait_time: Duration::from_secs(1),
+            poll_max_records: 1000,
+            topic_max_records: HashMap::new(),
+            dead_letter: false,
+            max_concurrent_handlers: 100,
+            lag_report_interval: Some(Duration::from_secs(30)),
+            shutdown_timeout: Duration::from_secs(10),
+            security: SecurityConfig::default(),
+        }
     }
 }
 
 pub struct MessageConsumer<S> {
     config: ClientConfig,
     handlers: HashMap<&'static str, MessageHandler<S>>,
+    // (regex, handler), matched if topic has no exact handler
+    pattern_handlers: Vec<(Regex, MessageHandler<S>)>,
     poll_max_wait_time: Duration,
     poll_max_records: usize,
+    topic_max_records: HashMap<&'static str, usize>,
+    dead_letter: Option<Arc<DeadLetterProducer>>,
+    max_concurrent_handlers: usize,
+    lag_report_interval: Option<Duration>,
+    shutdown_timeout: Duration,
+    counter: Arc<Counter>,
+    // (topic, partition) -> lag
+    lag: Arc<Mutex<BTreeMap<(String, i32), i64>>>,
+    manual_commit_topics: HashSet<&'static str>,
+    pending_commits: Arc<Mutex<Offsets>>,
+    control: ConsumerControl,
+    rebalance_callbacks: Vec<RebalanceCallback>,
+    assignment: Arc<Mutex<BTreeSet<(String, i32)>>>,
+    // refreshed on every poll, as generation changes after rebalance
+    group_metadata: Arc<Mutex<Option<Arc<ConsumerGroupMetadata>>>>,
+    seeks: Seeks,
+}
+
+// shared by all messages of one topic
+struct HandlerContext<C> {
+    topic: &'static str,
+    codec: C,
     counter: Arc<Counter>,
+    dead_letter: Option<Arc<DeadLetterProducer>>,
+    retry: RetryPolicy,
+    concurrency: Arc<Semaphore>,
 }
 
 impl<S> MessageConsumer<S>
@@ -76,84 +273,300 @@ where
 {
     // group_id usually be env!("CARGO_BIN_NAME")
     pub fn new(bootstrap_servers: String, group_id: &'static str, config: &ConsumerConfig) -> Self {
+        let dead_letter =
+            config.dead_letter.then(|| Arc::new(DeadLetterProducer::new(&bootstrap_servers, &config.security)));
+        let mut client_config = ClientConfig::new();
+        client_config
+            .set("group.id", group_id)
+            .set("bootstrap.servers", bootstrap_servers)
+            .set("enable.auto.commit", "false")
+            .set_log_level(RDKafkaLogLevel::Info);
+        config.security.apply(&mut client_config);
         Self {
-            config: ClientConfig::new()
-                .set("group.id", group_id)
-                .set("bootstrap.servers", bootstrap_servers)
-                .set("enable.auto.commit", "false")
-                .set_log_level(RDKafkaLogLevel::Info)
-                .to_owned(),
+            config: client_config,
             handlers: HashMap::new(),
+            pattern_handlers: vec![],
             poll_max_wait_time: config.poll_max_wait_time,
             poll_max_records: config.poll_max_records,
+            topic_max_records: config.topic_max_records.clone(),
+            dead_letter,
+            max_concurrent_handlers: config.max_concurrent_handlers,
+            lag_report_interval: config.lag_report_interval,
+            shutdown_timeout: config.shutdown_timeout,
             counter: Arc::new(Counter::new()),
+            lag: Arc::new(Mutex::new(BTreeMap::new())),
+            manual_commit_topics: HashSet::new(),
+            pending_commits: Arc::new(Mutex::new(BTreeMap::new())),
+            control: ConsumerControl::default(),
+            rebalance_callbacks: vec![],
+            assignment: Arc::new(Mutex::new(BTreeSet::new())),
+            group_metadata: Arc::new(Mutex::new(None)),
+            seeks: BTreeMap::new(),
         }
     }
 
+    pub fn control(&self) -> ConsumerControl {
+        self.control.clone()
+    }
+
+    // seek is applied once when partition is first assigned, e.g. replay action logs into rebuilt index,
+    // committed offsets are overwritten after replayed messages are handled
+    pub fn seek_to_timestamp(&mut self, topic: &str, timestamp: DateTime<Utc>) {
+        self.seeks.insert((topic.to_owned(), None), SeekPosition::Timestamp(timestamp.timestamp_millis()));
+    }
+
+    pub fn seek_to_offset(&mut self, topic: &str, partition: i32, offset: i64) {
+        self.seeks.insert((topic.to_owned(), Some(partition)), SeekPosition::Offset(offset));
+    }
+
+    // e.g. flush buffered writes of revoked partitions, called within poll, must be quick
+    pub fn on_rebalance<F>(&mut self, callback: F)
+    where
+        F: Fn(&RebalanceEvent) + Send + Sync + 'static,
+    {
+        self.rebalance_callbacks.push(Box::new(callback));
+    }
+
     pub fn consumer_metrics(&self) -> impl Fn(&mut Metrics) + use<S> {
         let counter = Arc::clone(&self.counter);
+        let lag = Arc::clone(&self.lag);
+        let assignment = Arc::clone(&self.assignment);
         move |metrics| {
             metrics.stats.push(("active_message_handlers", counter.max() as u64));
+            metrics.stats.push(("kafka_assigned_partitions", assignment.lock().unwrap().len() as u64));
+            let lag = lag.lock().unwrap();
+            if !lag.is_empty() {
+                let total: i64 = lag.values().sum();
+                metrics.stats.push(("kafka_consumer_lag", total.max(0) as u64));
+                let mut partitions = String::new();
+                for ((topic, partition), value) in lag.iter() {
+                    write_str!(partitions, "{topic}[{partition}]={value}\n");
+                }
+                metrics.info.push(("kafka_consumer_lag", partitions));
+            }
         }
     }
 
-    pub fn add_handler<H, Fut, M>(&mut self, topic: &Topic<M>, handler: H)
+    // messages with same key are handled sequentially in offset order, and next poll starts after all handlers of
+    // current poll completed, so ordering per key holds across polls, at cost of slow key delaying the next poll
+    pub fn add_handler<H, Fut, M, C>(&mut self, topic: &Topic<M, C>, handler: H)
     where
         H: Fn(S, Message<M>) -> Fut + Copy + Send + Sync + 'static,
         Fut: Future<Output = Result<(), Exception>> + Send + 'static,
-        M: DeserializeOwned + Send + 'static,
+        M: Send + 'static,
+        C: Decoder<M>,
         S: Clone + Send + Sync + 'static,
     {
-        let topic = topic.name;
-        let counter = Arc::clone(&self.counter);
-        let handler = move |state: S, messages: Vec<BorrowedMessage>| {
-            let messages: Vec<OwnedMessage> = messages.iter().map(BorrowedMessage::detach).collect();
-            handle_messages(topic, messages, handler, &state, &counter)
+        self.add_handler_with_retry(topic, handler, RetryPolicy::default());
+    }
+
+    pub fn add_handler_with_retry<H, Fut, M, C>(&mut self, topic: &Topic<M, C>, handler: H, retry: RetryPolicy)
+    where
+        H: Fn(S, Message<M>) -> Fut + Copy + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), Exception>> + Send + 'static,
+        M: Send + 'static,
+        C: Decoder<M>,
+        S: Clone + Send + Sync + 'static,
+    {
+        let context = Arc::new(self.handler_context(topic, retry));
+        let handler = move |state: S, messages: Vec<BorrowedMessage>, poll_stats: PollStats| {
+            let messages: Vec<RawMessage> = messages.iter().map(RawMessage::new).collect();
+            handle_messages(messages, handler, &state, &context, poll_stats)
         };
 
-        self.handlers.insert(topic, Box::new(handler));
+        self.handlers.insert(topic.name, Box::new(handler));
     }
 
-    pub fn add_bulk_handler<H, Fut, M>(&mut self, topic: &Topic<M>, handler: H)
+    pub fn add_bulk_handler<H, Fut, M, C>(&mut self, topic: &Topic<M, C>, handler: H)
     where
         H: Fn(S, Vec<Message<M>>) -> Fut + Copy + Send + Sync + 'static,
         Fut: Future<Output = Result<(), Exception>> + Send + 'static,
-        M: DeserializeOwned + Send + 'static,
+        M: Send + 'static,
+        C: Decoder<M>,
     {
-        let topic = topic.name;
-        let counter = Arc::clone(&self.counter);
-        let handler = move |state: S, messages: Vec<BorrowedMessage>| {
-            let messages: Vec<OwnedMessage> = messages.iter().map(BorrowedMessage::detach).collect();
-            handle_bulk_messages(topic, messages, handler, state, Arc::clone(&counter))
+        self.add_bulk_handler_with_retry(topic, handler, RetryPolicy::default());
+    }
+
+    pub fn add_bulk_handler_with_retry<H, Fut, M, C>(&mut self, topic: &Topic<M, C>, handler: H, retry: RetryPolicy)
+    where
+        H: Fn(S, Vec<Message<M>>) -> Fut + Copy + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), Exception>> + Send + 'static,
+        M: Send + 'static,
+        C: Decoder<M>,
+    {
+        let context = Arc::new(self.handler_context(topic, retry));
+        let handler = move |state: S, messages: Vec<BorrowedMessage>, poll_stats: PollStats| {
+            let messages: Vec<RawMessage> = messages.iter().map(RawMessage::new).collect();
+            handle_bulk_messages(messages, handler, type_name::<H>(), state, Arc::clone(&context), poll_stats)
         };
 
-        self.handlers.insert(topic, Box::new(handler));
+        self.handlers.insert(topic.name, Box::new(handler));
     }
 
-    pub async fn start(self, state: S, shutdown_signal: CancellationToken) {
-        let topics: Vec<&str> = self.handlers.keys().copied().collect();
+    // subscribes all topics matching regex, e.g. "action-log-.*", new topics are picked up on metadata refresh,
+    // refer to topic.metadata.refresh.interval.ms
+    pub fn add_bulk_handler_pattern<H, Fut, M>(&mut self, pattern: &'static str, handler: H)
+    where
+        H: Fn(S, Vec<Message<M>>) -> Fut + Copy + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), Exception>> + Send + 'static,
+        M: Send + 'static,
+        JsonCodec: Decoder<M>,
+    {
+        // librdkafka treats subscription starting with "^" as regex
+        let regex = Regex::new(&format!("^{pattern}")).expect("topic pattern must be valid regex"); // fail fast on startup
+        let context = Arc::new(self.handler_context(&Topic::<M>::new(pattern), RetryPolicy::default()));
+        let handler = move |state: S, messages: Vec<BorrowedMessage>, poll_stats: PollStats| {
+            let messages: Vec<RawMessage> = messages.iter().map(RawMessage::new).collect();
+            handle_bulk_messages(messages, handler, type_name::<H>(), state, Arc::clone(&context), poll_stats)
+        };
+
+        self.pattern_handlers.push((regex, Box::new(handler)));
+    }
+
+    // handler must call ack.commit() once messages are durably processed, otherwise offsets are not committed
+    pub fn add_bulk_handler_with_ack<H, Fut, M, C>(&mut self, topic: &Topic<M, C>, handler: H, retry: RetryPolicy)
+    where
+        H: Fn(S, Vec<Message<M>>, Ack) -> Fut + Copy + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), Exception>> + Send + 'static,
+        M: Send + 'static,
+        C: Decoder<M>,
+    {
+        let context = Arc::new(self.handler_context(topic, retry));
+        let pending_commits = Arc::clone(&self.pending_commits);
+        let group_metadata = Arc::clone(&self.group_metadata);
+        let bulk_handler = move |state: S, messages: Vec<BorrowedMessage>, poll_stats: PollStats| {
+            let ack = Ack {
+                offsets: next_offsets(&messages),
+                pending_commits: Arc::clone(&pending_commits),
+                group_metadata: group_metadata.lock().unwrap().clone(),
+            };
+            let raw_messages: Vec<RawMessage> = messages.iter().map(RawMessage::new).collect();
+            let ack_handler = move |handler_state: S, decoded_messages: Vec<Message<M>>| {
+                handler(handler_state, decoded_messages, ack.clone())
+            };
+            handle_bulk_messages(raw_messages, ack_handler, type_name::<H>(), state, Arc::clone(&context), poll_stats)
+        };
+
+        self.manual_commit_topics.insert(topic.name);
+        self.handlers.insert(topic.name, Box::new(bulk_handler));
+    }
+
+    fn handler(&self, topic: &str) -> Option<&MessageHandler<S>> {
+        self.handlers.get(topic).or_else(|| {
+            self.pattern_handlers.iter().find(|(regex, _)| regex.is_match(topic)).map(|(_, handler)| handler)
+        })
+    }
+
+    fn handler_context<M, C>(&self, topic: &Topic<M, C>, retry: RetryPolicy) -> HandlerContext<C>
+    where
+        C: Clone,
+    {
+        HandlerContext {
+            topic: topic.name,
+            codec: topic.codec.clone(),
+            counter: Arc::clone(&self.counter),
+            dead_letter: self.dead_letter.clone(),
+            retry,
+            concurrency: Arc::new(Semaphore::new(self.max_concurrent_handlers)),
+        }
+    }
+
+    pub async fn start(mut self, state: S, shutdown_signal: CancellationToken) {
+        let mut topics: Vec<&str> = self.handlers.keys().copied().collect();
+        topics.extend(self.pattern_handlers.iter().map(|(regex, _)| regex.as_str()));
         console!(
             "start kafka consumer, broker={}, topics={:?}",
             self.config.get("bootstrap.servers").expect("broker must not be null"),
             topics
         );
 
-        let consumer: BaseConsumer = self.config.create().expect("failed to create consumer"); // fail fast on startup
+        let context = RebalanceContext::new(mem::take(&mut self.rebalance_callbacks), Arc::clone(&self.assignment));
+        let consumer: Arc<KafkaConsumer> =
+            Arc::new(self.config.create_with_context(context).expect("failed to create consumer")); // fail fast on startup
         consumer.subscribe(&topics).expect("failed to subscribe topic"); // fail fast on startup
 
+        let mut last_lag_report = Instant::now();
+        let mut paused_partitions = BTreeSet::new();
+        let mut seeked_partitions = BTreeSet::new();
+        let mut commit_elapsed = Duration::ZERO;
         loop {
-            match poll_message_groups(&consumer, self.poll_max_wait_time, self.poll_max_records) {
-                Ok(topic_messages) => {
+            if let Err(e) = apply_control(&consumer, &self.control, &mut paused_partitions) {
+                console!("ERROR failed to pause or resume partitions, error={e:?}");
+            }
+
+            if let Some(interval) = self.lag_report_interval
+                && last_lag_report.elapsed() >= interval
+            {
+                last_lag_report = Instant::now();
+                match consumer_lag(&consumer).await {
+                    Ok(lag) => *self.lag.lock().unwrap() = lag,
+                    Err(e) => console!("WARN failed to fetch consumer lag, error={e:?}"),
+                }
+            }
+
+            let poll_start = Instant::now();
+            let limit = PollLimit {
+                max_wait_time: self.poll_max_wait_time,
+                max_records: self.poll_max_records,
+                topic_max_records: &self.topic_max_records,
+            };
+            let result = poll_message_groups(&consumer, &limit, &self.control, &shutdown_signal).await;
+            let poll_stats = PollStats { poll_elapsed: poll_start.elapsed(), commit_elapsed };
+            *self.group_metadata.lock().unwrap() = consumer.group_metadata().map(Arc::new);
+            for event in consumer.context().take_events() {
+                log_rebalance(event).await;
+            }
+            match result {
+                Ok(mut topic_messages) => {
+                    // messages polled before revocation will be consumed by new owner from last committed offset
+                    let mut skipped = consumer.context().take_revoked();
+                    // messages polled before seek will be consumed again from seek position
+                    let seeks = pending_seeks(&self.seeks, &self.assignment.lock().unwrap(), &seeked_partitions);
+                    skipped.extend(seeks.keys().cloned());
+                    if !skipped.is_empty() {
+                        for messages in topic_messages.values_mut() {
+                            messages.retain(|message| {
+                                !skipped.contains(&(message.topic().to_owned(), message.partition()))
+                            });
+                        }
+                    }
+                    if !seeks.is_empty() {
+                        match seek(&consumer, seeks.clone()).await {
+                            Ok(()) => seeked_partitions.extend(seeks.into_keys()),
+                            Err(e) => console!("ERROR failed to seek partitions, error={e:?}"),
+                        }
+                    }
+                    // polled messages are not dispatched after shutdown signal, they will be consumed again after restart
+                    if shutdown_signal.is_cancelled() {
+                        topic_messages.clear();
+                    }
                     let mut handles = Vec::with_capacity(topic_messages.len());
                     for (topic, messages) in topic_messages {
-                        if let Some(handler) = self.handlers.get(topic.as_str()) {
-                            handles.push(tokio::spawn(handler(state.clone(), messages)));
+                        if let Some(handler) = self.handler(&topic) {
+                            let manual_commit = self.manual_commit_topics.contains(topic.as_str());
+                            let offsets = if manual_commit { BTreeMap::new() } else { next_offsets(&messages) };
+                            // without dead letter topic, failed messages of auto committed topics are skipped
+                            let rewind =
+                                (manual_commit || self.dead_letter.is_some()).then(|| first_offsets(&messages));
+                            let handle = tokio::spawn(handler(state.clone(), messages, poll_stats));
+                            handles.push(HandlerTask { topic, offsets, rewind, handle });
                         }
                     }
-                    join_all(handles).await;
-                    if let Err(e) = consumer.commit_consumer_state(CommitMode::Async) {
+                    let (mut offsets, failed) = join_handlers(handles, &shutdown_signal, self.shutdown_timeout).await;
+                    merge_offsets(&mut offsets, mem::take(&mut *self.pending_commits.lock().unwrap()));
+                    let mut rewinds = rewind_failed(&mut offsets, failed);
+                    let commit_start = Instant::now();
+                    if let Err(e) = commit(&consumer, &offsets).await {
                         console!("ERROR failed to commit messages, error={e:?}");
                     }
+                    commit_elapsed = commit_start.elapsed();
+                    // revoked partitions are consumed by new owner from last committed offset
+                    rewinds.retain(|partition, _| self.assignment.lock().unwrap().contains(partition));
+                    if !rewinds.is_empty()
+                        && let Err(e) = seek(&consumer, rewinds).await
+                    {
+                        console!("ERROR failed to rewind failed partitions, error={e:?}");
+                    }
                 }
                 Err(e) => {
                     console!("ERROR failed to poll messages, error={e:?}");
@@ -169,46 +582,310 @@ where
     }
 }
 
-fn poll_message_groups(
-    consumer: &BaseConsumer,
+// applied on every poll, so partitions assigned after rebalance are paused as well
+fn apply_control(
+    consumer: &KafkaConsumer,
+    control: &ConsumerControl,
+    paused_partitions: &mut BTreeSet<(String, i32)>,
+) -> Result<(), KafkaError> {
+    let assignment = consumer.assignment()?;
+    let mut pause = TopicPartitionList::new();
+    let mut resume = TopicPartitionList::new();
+    let mut current = BTreeSet::new();
+    for element in assignment.elements() {
+        let partition = (element.topic().to_owned(), element.partition());
+        if control.is_paused(element.topic(), element.partition()) {
+            if !paused_partitions.contains(&partition) {
+                pause.add_partition(element.topic(), element.partition());
+            }
+            current.insert(partition);
+        } else if paused_partitions.contains(&partition) {
+            resume.add_partition(element.topic(), element.partition());
+        }
+    }
+    if pause.count() > 0 {
+        consumer.pause(&pause)?;
+    }
+    if resume.count() > 0 {
+        consumer.resume(&resume)?;
+    }
+    *paused_partitions = current;
+    Ok(())
+}
+
+// messages are received asynchronously, so polling doesn't block runtime worker thread
+type KafkaConsumer = StreamConsumer<RebalanceContext>;
+
+struct HandlerTask {
+    topic: String,
+    // committed after handler completes, empty if committed by ack
+    offsets: Offsets,
+    // first offsets of polled messages, failed messages are consumed again from there, None if failure is skipped
+    rewind: Option<Offsets>,
+    // fails if messages are neither handled nor sent to dead letter topic
+    handle: JoinHandle<Result<(), Exception>>,
+}
+
+// waits for all handlers, after shutdown signal waits up to shutdown_timeout and aborts remaining handlers,
+// returns offsets of completed handlers and first offsets of failed handlers to consume again
+async fn join_handlers(
+    mut handles: Vec<HandlerTask>,
+    shutdown_signal: &CancellationToken,
+    shutdown_timeout: Duration,
+) -> (Offsets, Offsets) {
+    let drain_timeout = async {
+        shutdown_signal.cancelled().await;
+        time::sleep(shutdown_timeout).await;
+    };
+    // join handle can only be awaited once, so results of completed handlers are kept if drain times out
+    let mut results: Vec<Option<bool>> = vec![None; handles.len()];
+    tokio::select! {
+        _ = join_all(handles.iter_mut().zip(results.iter_mut()).map(|(task, result)| async move {
+            *result = Some(matches!((&mut task.handle).await, Ok(Ok(()))));
+        })) => {}
+        () = drain_timeout => {}
+    }
+    let mut offsets = BTreeMap::new();
+    let mut failed = BTreeMap::new();
+    for (task, result) in handles.into_iter().zip(results) {
+        match (result, task.rewind) {
+            (Some(false), Some(rewind)) => failed.extend(rewind),
+            (Some(_), _) => merge_offsets(&mut offsets, task.offsets),
+            (None, _) => {
+                console!("WARN abort message handler after shutdown timeout, topic={}", task.topic);
+                task.handle.abort();
+            }
+        }
+    }
+    (offsets, failed)
+}
+
+// failed messages are not committed, including offsets acked before failure, and consumed again from first offset,
+// otherwise next acked batch of same partition commits past them
+fn rewind_failed(offsets: &mut Offsets, failed: Offsets) -> BTreeMap<(String, i32), SeekPosition> {
+    let mut rewinds = BTreeMap::new();
+    for (partition, first_offset) in failed {
+        if let Some(offset) = offsets.get_mut(&partition) {
+            *offset = (*offset).min(first_offset);
+        }
+        rewinds.insert(partition, SeekPosition::Offset(first_offset));
+    }
+    rewinds
+}
+
+fn pending_seeks(
+    seeks: &Seeks,
+    assignment: &BTreeSet<(String, i32)>,
+    seeked_partitions: &BTreeSet<(String, i32)>,
+) -> BTreeMap<(String, i32), SeekPosition> {
+    let mut pending = BTreeMap::new();
+    if seeks.is_empty() {
+        return pending;
+    }
+    for partition in assignment.difference(seeked_partitions) {
+        let (ref topic, number) = *partition;
+        if let Some(position) = seeks.get(&(topic.clone(), Some(number))).or_else(|| seeks.get(&(topic.clone(), None)))
+        {
+            pending.insert(partition.clone(), *position);
+        }
+    }
+    pending
+}
+
+// seek and offsets_for_times block until broker responds, run on blocking thread to not stall runtime worker
+async fn seek(consumer: &Arc<KafkaConsumer>, seeks: BTreeMap<(String, i32), SeekPosition>) -> Result<(), Exception> {
+    const TIMEOUT: Duration = Duration::from_secs(5);
+    let consumer = Arc::clone(consumer);
+    Ok(task::spawn_blocking(move || {
+        let mut timestamps = TopicPartitionList::new();
+        for ((topic, partition), position) in &seeks {
+            match *position {
+                SeekPosition::Offset(offset) => consumer.seek(topic, *partition, Offset::Offset(offset), TIMEOUT)?,
+                SeekPosition::Timestamp(timestamp) => {
+                    timestamps.add_partition_offset(topic, *partition, Offset::Offset(timestamp))?;
+                }
+            }
+        }
+        if timestamps.count() > 0 {
+            // offset is End if no message after timestamp
+            for element in consumer.offsets_for_times(timestamps, TIMEOUT)?.elements() {
+                consumer.seek(element.topic(), element.partition(), element.offset(), TIMEOUT)?;
+            }
+        }
+        console!("seek partitions, seeks={seeks:?}");
+        Ok::<(), KafkaError>(())
+    })
+    .await??)
+}
+
+fn next_offsets(messages: &[BorrowedMessage]) -> Offsets {
+    let mut offsets = BTreeMap::new();
+    for message in messages {
+        let offset = offsets.entry((message.topic().to_owned(), message.partition())).or_insert(0);
+        *offset = (*offset).max(message.offset() + 1);
+    }
+    offsets
+}
+
+fn first_offsets(messages: &[BorrowedMessage]) -> Offsets {
+    let mut offsets = BTreeMap::new();
+    for message in messages {
+        let offset = offsets.entry((message.topic().to_owned(), message.partition())).or_insert(message.offset());
+        *offset = (*offset).min(message.offset());
+    }
+    offsets
+}
+
+fn merge_offsets(offsets: &mut Offsets, other: Offsets) {
+    for (partition, offset) in other {
+        let current = offsets.entry(partition).or_insert(offset);
+        *current = (*current).max(offset);
+    }
+}
+
+async fn commit(consumer: &Arc<KafkaConsumer>, offsets: &Offsets) -> Result<(), Exception> {
+    if offsets.is_empty() {
+        return Ok(());
+    }
+    let partitions = partition_list(offsets)?;
+    let consumer = Arc::clone(consumer);
+    // commit before next poll, so processed messages are committed before partitions can be revoked,
+    // sync commit blocks until broker responds, so it runs on blocking thread
+    Ok(task::spawn_blocking(move || consumer.commit(&partitions, CommitMode::Sync)).await??)
+}
+
+fn partition_list(offsets: &Offsets) -> Result<TopicPartitionList, KafkaError> {
+    let mut partitions = TopicPartitionList::new();
+    for ((topic, partition), offset) in offsets {
+        partitions.add_partition_offset(topic, *partition, Offset::Offset(*offset))?;
+    }
+    Ok(partitions)
+}
+
+// queries broker per partition, runs on blocking thread
+async fn consumer_lag(consumer: &Arc<KafkaConsumer>) -> Result<BTreeMap<(String, i32), i64>, Exception> {
+    const TIMEOUT: Duration = Duration::from_secs(5);
+    let consumer = Arc::clone(consumer);
+    Ok(task::spawn_blocking(move || {
+        let committed = consumer.committed_offsets(consumer.assignment()?, TIMEOUT)?;
+        let mut lag = BTreeMap::new();
+        for element in committed.elements() {
+            let (low, high) = consumer.fetch_watermarks(element.topic(), element.partition(), TIMEOUT)?;
+            lag.insert((element.topic().to_owned(), element.partition()), partition_lag(element.offset(), low, high));
+        }
+        Ok::<_, KafkaError>(lag)
+    })
+    .await??)
+}
+
+// no committed offset means consumer starts from beginning of retained messages
+fn partition_lag(committed: Offset, low: i64, high: i64) -> i64 {
+    match committed {
+        Offset::Offset(offset) => high - offset.max(low),
+        Offset::Beginning | Offset::End | Offset::Stored | Offset::Invalid | Offset::OffsetTail(_) => high - low,
+    }
+}
+
+struct PollLimit<'a> {
     max_wait_time: Duration,
     max_records: usize,
-) -> Result<HashMap<String, Vec<BorrowedMessage<'_>>>, KafkaError> {
+    topic_max_records: &'a HashMap<&'static str, usize>,
+}
+
+async fn poll_message_groups<'a>(
+    consumer: &'a KafkaConsumer,
+    limit: &PollLimit<'_>,
+    control: &ConsumerControl,
+    shutdown_signal: &CancellationToken,
+) -> Result<HashMap<String, Vec<BorrowedMessage<'a>>>, KafkaError> {
     let mut messages: HashMap<String, Vec<BorrowedMessage>> = HashMap::new();
-    let start_time = Instant::now();
+    let deadline = time::Instant::now() + limit.max_wait_time;
     let mut count = 1;
+    let mut limited_partitions = TopicPartitionList::new();
     loop {
-        let elapsed = start_time.elapsed();
-        if elapsed >= max_wait_time {
+        if count >= limit.max_records {
             break;
         }
 
-        if count >= max_records {
-            break;
+        tokio::select! {
+            result = consumer.recv() => {
+                let message = result?;
+                let topic = message.topic();
+                let topic_count = messages.get(topic).map_or(0, Vec::len) + 1;
+                if limit.topic_max_records.get(topic).is_some_and(|max| topic_count == *max)
+                    && let Err(e) = pause_topic(consumer, topic, &mut limited_partitions)
+                {
+                    console!("WARN failed to pause topic reached max records, topic={topic}, error={e:?}");
+                }
+                messages.entry(topic.to_owned()).or_default().push(message);
+                count += 1;
+            }
+            () = time::sleep_until(deadline) => break,
+            () = shutdown_signal.cancelled() => break,
         }
 
-        if let Some(result) = consumer.poll(Timeout::After(max_wait_time.saturating_sub(elapsed))) {
-            let message = result?;
-            let topic = message.topic().to_owned();
-            messages.entry(topic).or_default().push(message);
-            count += 1;
+        // stop polling once partitions are revoked, to handle messages polled before rebalance only
+        if consumer.context().has_revoked() || shutdown_signal.is_cancelled() {
+            break;
+        }
+    }
+    if limited_partitions.count() > 0 {
+        // partitions paused by control are resumed by apply_control()
+        let mut resume = TopicPartitionList::new();
+        for element in limited_partitions.elements() {
+            if !control.is_paused(element.topic(), element.partition()) {
+                resume.add_partition(element.topic(), element.partition());
+            }
+        }
+        // keep received messages, otherwise they are dropped without being handled
+        if let Err(e) = consumer.resume(&resume) {
+            console!("ERROR failed to resume topic reached max records, partitions={resume:?}, error={e:?}");
         }
     }
     Ok(messages)
 }
 
-fn handle_bulk_messages<H, S, M, Fut>(
-    topic: &'static str,
-    raw_messages: Vec<OwnedMessage>,
+// paused partitions resume from last consumed position, prefetched messages are discarded
+fn pause_topic(consumer: &KafkaConsumer, topic: &str, paused: &mut TopicPartitionList) -> Result<(), KafkaError> {
+    let mut partitions = TopicPartitionList::new();
+    for element in consumer.assignment()?.elements_for_topic(topic) {
+        partitions.add_partition(topic, element.partition());
+        paused.add_partition(topic, element.partition());
+    }
+    consumer.pause(&partitions)
+}
+
+async fn log_rebalance(event: RebalanceEvent) {
+    let _result = log::action("rebalance", None, async {
+        match event {
+            RebalanceEvent::Assigned(partitions) => {
+                log!("partitions assigned, partitions={partitions:?}");
+                stats!(kafka_assigned_partitions = partitions.len());
+            }
+            RebalanceEvent::Revoked(partitions) => {
+                log!("partitions revoked, partitions={partitions:?}");
+                stats!(kafka_revoked_partitions = partitions.len());
+            }
+        }
+        Ok::<(), Exception>(())
+    })
+    .await;
+}
+
+fn handle_bulk_messages<H, S, M, C, Fut>(
+    raw_messages: Vec<RawMessage>,
     handler: H,
+    handler_name: &'static str,
     state: S,
-    counter: Arc<Counter>,
+    context: Arc<HandlerContext<C>>,
+    poll_stats: PollStats,
 ) -> Pin<Box<dyn Future<Output = Result<(), Exception>> + Send>>
 where
-    S: Send + 'static,
-    H: Fn(S, Vec<Message<M>>) -> Fut + Send + 'static,
+    S: Clone + Send + Sync + 'static,
+    H: Fn(S, Vec<Message<M>>) -> Fut + Send + Sync + 'static,
     Fut: Future<Output = Result<(), Exception>> + Send + 'static,
-    M: DeserializeOwned + Send + 'static,
+    M: Send + 'static,
+    C: Decoder<M>,
 {
     let ref_id = raw_messages
         .iter()
@@ -216,63 +893,126 @@ where
         .collect::<Option<HashSet<String>>>()
         .map(|set| set.into_iter().collect::<Vec<String>>());
 
-    Box::pin(log::action("message", ref_id, async move {
-        let _counter = counter.increase();
-        context!(topic = topic, fn = type_name::<H>());
-        let mut bytes = 0;
-        let mut messages: Vec<Message<M>> = Vec::with_capacity(raw_messages.len());
-        for raw in &raw_messages {
-            let key = key(raw);
-            let payload = payload(raw);
-            log!("[message] key={:?}, payload={}", key, payload);
-            bytes += payload.len();
-            match from_json::<M>(&payload) {
-                Ok(payload) => messages.push(Message { key, payload }),
-                Err(e) => {
-                    log!(
-                        exception = exception!("failed to decode message", code = "KAFKA_INVALID_MESSAGE", source = e)
-                    );
+    Box::pin(async move {
+        let mut dead_letter_result = Ok(());
+        let result = log::action("message", ref_id, async {
+            let _counter = context.counter.increase();
+            context!(topic = context.topic, fn = handler_name);
+            poll_stats.record(raw_messages.len());
+            let mut bytes = 0;
+            let mut decode_failures = 0;
+            let mut messages: Vec<Message<M>> = Vec::with_capacity(raw_messages.len());
+            let mut decoded_messages = Vec::with_capacity(raw_messages.len());
+            for raw in &raw_messages {
+                let key = key(raw);
+                let payload = payload(raw);
+                let result = decode(&context.codec, payload).await;
+                log!("[message] key={:?}, payload={}", key, context.codec.display(result.as_ref().ok(), payload));
+                bytes += payload.len();
+                match result {
+                    Ok(decoded) => {
+                        messages.push(Message { key, payload: decoded });
+                        decoded_messages.push(raw);
+                    }
+                    Err(e) => {
+                        let error = exception!("failed to decode message", code = "KAFKA_INVALID_MESSAGE", source = e);
+                        decode_failures += 1;
+                        if let Some(ref dead_letter) = context.dead_letter
+                            && let Err(dead_letter_error) = dead_letter.send(raw, &error).await
+                        {
+                            log!(exception = dead_letter_error);
+                            dead_letter_result = Err(dead_letter_error);
+                        }
+                        log!(exception = error);
+                    }
                 }
             }
-        }
-        stats!(kafka_read_messages = messages.len(), kafka_read_bytes = bytes);
-        if let Some(timestamp) = raw_messages.iter().filter_map(timestamp).min() {
-            log!("[message] timestamp={:?}", timestamp.to_rfc3339_opts(SecondsFormat::Millis, true));
-            let lag = Utc::now() - timestamp;
-            stats!(kafka_consumer_lag = lag.num_nanoseconds().unwrap_or_default());
-        }
-        if let Some(clients) = raw_messages
-            .iter()
-            .map(|raw| header(raw, CLIENT).map(str::to_owned))
-            .collect::<Option<HashSet<String>>>()
-            .map(|set| set.into_iter().collect::<Vec<String>>())
-        {
-            context!(client = clients);
-        }
-        handler(state, messages).await
-    }))
+            stats!(kafka_read_messages = messages.len(), kafka_read_bytes = bytes);
+            if decode_failures > 0 {
+                stats!(kafka_decode_failures = decode_failures);
+            }
+            if let Some(timestamp) = raw_messages.iter().filter_map(timestamp).min() {
+                log!("[message] timestamp={:?}", timestamp.to_rfc3339_opts(SecondsFormat::Millis, true));
+                let lag = Utc::now() - timestamp;
+                stats!(kafka_consumer_lag = lag.num_nanoseconds().unwrap_or_default());
+            }
+            if let Some(clients) = raw_messages
+                .iter()
+                .map(|raw| header(raw, CLIENT).map(str::to_owned))
+                .collect::<Option<HashSet<String>>>()
+                .map(|set| set.into_iter().collect::<Vec<String>>())
+            {
+                context!(client = clients);
+            }
+            let correlation_ids: HashSet<&str> =
+                raw_messages.iter().filter_map(|raw| header(raw, CORRELATION_ID)).collect();
+            if !correlation_ids.is_empty() {
+                context!(correlation_id = correlation_ids.into_iter().map(str::to_owned).collect::<Vec<_>>());
+            }
+            let trace_ids: HashSet<&str> =
+                raw_messages.iter().filter_map(|raw| header(raw, TRACEPARENT).and_then(parse_traceparent)).collect();
+            if !trace_ids.is_empty() {
+                // trace is only propagated if all messages share it
+                context!(trace_id = trace_ids.into_iter().map(str::to_owned).collect::<Vec<_>>());
+            }
+            // decoded messages are moved into handler, decode again for retry
+            let mut messages = Some(messages);
+            let result = with_retry(context.retry, "kafka_handler_retries", || {
+                let messages = messages.take().unwrap_or_else(|| {
+                    decoded_messages
+                        .iter()
+                        .filter_map(|raw| {
+                            context
+                                .codec
+                                .decode_bytes(payload(raw))
+                                .ok()
+                                .map(|decoded| Message { key: key(raw), payload: decoded })
+                        })
+                        .collect()
+                });
+                handler(state.clone(), messages)
+            })
+            .await;
+            if let Err(ref error) = result
+                && let Some(ref dead_letter) = context.dead_letter
+            {
+                let results = join_all(decoded_messages.into_iter().map(|raw| dead_letter.send(raw, error))).await;
+                if let Err(e) = results.into_iter().collect::<Result<(), _>>() {
+                    log!(exception = e);
+                    dead_letter_result = Err(e);
+                }
+            }
+            result
+        })
+        .await;
+        // failed messages sent to dead letter topic are handled, otherwise consumed again
+        dead_letter_result?;
+        if context.dead_letter.is_some() { Ok(()) } else { result }
+    })
 }
 
 struct MessageNode {
-    message: OwnedMessage,
+    message: RawMessage,
     next: Option<Vec<MessageNode>>,
 }
 
-fn handle_messages<H, S, M, Fut>(
-    topic: &'static str,
-    messages: Vec<OwnedMessage>,
+fn handle_messages<H, S, M, C, Fut>(
+    messages: Vec<RawMessage>,
     handler: H,
     state: &S,
-    counter: &Arc<Counter>,
+    context: &Arc<HandlerContext<C>>,
+    poll_stats: PollStats,
 ) -> Pin<Box<dyn Future<Output = Result<(), Exception>> + Send>>
 where
-    S: Clone + Send + 'static,
+    S: Clone + Send + Sync + 'static,
     H: Fn(S, Message<M>) -> Fut + Copy + Send + Sync + 'static,
     Fut: Future<Output = Result<(), Exception>> + Send + 'static,
-    M: DeserializeOwned + Send + 'static,
+    M: Send + 'static,
+    C: Decoder<M>,
 {
-    let mut handles = JoinSet::new();
+    let batch_size = messages.len();
     let mut nodes: HashMap<String, MessageNode> = HashMap::new();
+    let mut unkeyed_messages = vec![];
     for message in messages {
         if let Some(key) = key(&message) {
             if let Some(node) = nodes.get_mut(&key) {
@@ -285,48 +1025,67 @@ where
                 nodes.insert(key, MessageNode { message, next: None });
             }
         } else {
-            let state = state.clone();
-            let counter = Arc::clone(counter);
-            handles.spawn(async move {
-                let _counter = counter.increase();
-                handle_message(topic, message, handler, state).await;
-            });
+            unkeyed_messages.push(MessageNode { message, next: None });
         }
     }
 
-    for node in nodes.into_values() {
-        let state = state.clone();
-        let counter = Arc::clone(counter);
-        handles.spawn(async move {
-            let _counter = counter.increase();
-            handle_message(topic, node.message, handler, state.clone()).await;
-            if let Some(next) = node.next {
-                for next_node in next {
-                    handle_message(topic, next_node.message, handler, state.clone()).await;
-                }
-            }
-        });
-    }
-
+    let state = state.clone();
+    let context = Arc::clone(context);
     Box::pin(async move {
-        handles.join_all().await;
-        Ok(())
+        let mut handles = JoinSet::new();
+        for node in unkeyed_messages.into_iter().chain(nodes.into_values()) {
+            // wait for permit before spawning, so in-flight handlers per topic are bounded
+            let permit = Arc::clone(&context.concurrency).acquire_owned().await.expect("semaphore must not be closed");
+            let state = state.clone();
+            let context = Arc::clone(&context);
+            handles.spawn(async move {
+                let _permit = permit;
+                let _counter = context.counter.increase();
+                let mut result =
+                    handle_message(node.message, handler, state.clone(), &context, poll_stats, batch_size).await;
+                if let Some(next) = node.next {
+                    for next_node in next {
+                        let next_result =
+                            handle_message(next_node.message, handler, state.clone(), &context, poll_stats, batch_size)
+                                .await;
+                        result = result.and(next_result);
+                    }
+                }
+                result
+            });
+        }
+        // fails if any message failed to send to dead letter topic, so batch is consumed again
+        handles.join_all().await.into_iter().collect()
     })
 }
 
-async fn handle_message<H, S, M, Fut>(topic: &'static str, raw_message: OwnedMessage, handler: H, state: S)
+async fn handle_message<H, S, M, C, Fut>(
+    raw_message: RawMessage,
+    handler: H,
+    state: S,
+    context: &HandlerContext<C>,
+    poll_stats: PollStats,
+    batch_size: usize,
+) -> Result<(), Exception>
 where
-    H: Fn(S, Message<M>) -> Fut,
+    S: Clone,
+    H: Fn(S, Message<M>) -> Fut + Copy,
     Fut: Future<Output = Result<(), Exception>>,
-    M: DeserializeOwned,
+    C: Decoder<M>,
 {
     let ref_id = header(&raw_message, REF_ID).map(|id| vec![id.to_owned()]);
+    let mut dead_letter_result = Ok(());
     let _result = log::action("message", ref_id, async {
         let key = key(&raw_message);
         let payload = payload(&raw_message);
-        context!(topic = topic, key = format!("{:?}", key), fn = type_name::<H>());
-        log!("[message] payload={}", payload);
+        context!(topic = context.topic, key = format!("{:?}", key), fn = type_name::<H>());
+        poll_stats.record(batch_size);
+        let result = decode(&context.codec, payload).await;
+        log!("[message] payload={}", context.codec.display(result.as_ref().ok(), payload));
         stats!(kafka_read_entries = 1, kafka_read_bytes = payload.len());
+        if result.is_err() {
+            stats!(kafka_decode_failures = 1);
+        }
         if let Some(timestamp) = timestamp(&raw_message) {
             log!("[message] timestamp={:?}", timestamp.to_rfc3339_opts(SecondsFormat::Millis, true));
             let lag = Utc::now() - timestamp;
@@ -335,16 +1094,47 @@ where
         if let Some(client) = header(&raw_message, CLIENT) {
             context!(client = client);
         }
-        let payload: M = from_json(&payload)
-            .map_err(|e| exception!("failed to decode message", code = "KAFKA_INVALID_MESSAGE", source = e))?;
-        handler(state, Message { key, payload }).await
+        if let Some(correlation_id) = header(&raw_message, CORRELATION_ID) {
+            context!(correlation_id = correlation_id);
+        }
+        if let Some(trace_id) = header(&raw_message, TRACEPARENT).and_then(parse_traceparent) {
+            context!(trace_id = trace_id);
+        }
+        let result = match result {
+            Ok(message_payload) => {
+                // decoded payload is moved into handler, decode again for retry
+                let mut message_payload = Some(message_payload);
+                with_retry(context.retry, "kafka_handler_retries", || {
+                    let message_payload = message_payload.take().or_else(|| context.codec.decode_bytes(payload).ok());
+                    let key = key.clone();
+                    let state = state.clone();
+                    async move {
+                        match message_payload {
+                            Some(decoded) => handler(state, Message { key, payload: decoded }).await,
+                            None => Err(exception!("failed to decode message", code = "KAFKA_INVALID_MESSAGE")),
+                        }
+                    }
+                })
+                .await
+            }
+            Err(e) => Err(exception!("failed to decode message", code = "KAFKA_INVALID_MESSAGE", source = e)),
+        };
+        if let Err(ref error) = result
+            && let Some(ref dead_letter) = context.dead_letter
+            && let Err(e) = dead_letter.send(&raw_message, error).await
+        {
+            log!(exception = e);
+            dead_letter_result = Err(e);
+        }
+        result
     })
     .await;
+    dead_letter_result
 }
 
 // ref_id and client headers are set and consumed by the framework only.
-fn header<'a>(message: &'a OwnedMessage, name: &str) -> Option<&'a str> {
-    let headers = message.headers()?;
+fn header<'a>(message: &'a RawMessage, name: &str) -> Option<&'a str> {
+    let headers = message.headers.as_ref()?;
     // headers are framework-written utf8; from_utf8 borrows on the happy path, falling back to "".
     headers
         .iter()
@@ -353,17 +1143,143 @@ fn header<'a>(message: &'a OwnedMessage, name: &str) -> Option<&'a str> {
         .map(|data| from_utf8(data).unwrap_or_default())
 }
 
-fn key(message: &OwnedMessage) -> Option<String> {
-    message.key().map(|data| String::from_utf8_lossy(data).to_string())
+fn key(message: &RawMessage) -> Option<String> {
+    message.key.as_deref().map(|data| String::from_utf8_lossy(data).to_string())
 }
 
-fn payload(message: &OwnedMessage) -> String {
-    message.payload().map(|data| String::from_utf8_lossy(data).to_string()).unwrap_or_default()
+fn payload(message: &RawMessage) -> &Bytes {
+    static EMPTY: Bytes = Bytes::new();
+    message.payload.as_ref().unwrap_or(&EMPTY)
+}
+
+async fn decode<M, C>(codec: &C, payload: &Bytes) -> Result<M, Exception>
+where
+    C: Decoder<M>,
+{
+    codec.prepare(payload).await?;
+    codec.decode_bytes(payload)
 }
 
-fn timestamp(message: &OwnedMessage) -> Option<DateTime<Utc>> {
-    match message.timestamp() {
+const fn timestamp(message: &RawMessage) -> Option<DateTime<Utc>> {
+    match message.timestamp {
         Timestamp::CreateTime(time) => DateTime::from_timestamp_millis(time),
         Timestamp::NotAvailable | Timestamp::LogAppendTime(_) => None,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::collections::BTreeSet;
+
+    use std::time::Duration;
+
+    use framework::exception;
+    use rdkafka::Offset;
+    use tokio_util::sync::CancellationToken;
+
+    use super::HandlerTask;
+    use super::SeekPosition;
+    use super::join_handlers;
+    use super::merge_offsets;
+    use super::partition_lag;
+    use super::pending_seeks;
+    use super::rewind_failed;
+
+    #[test]
+    fn lag() {
+        assert_eq!(partition_lag(Offset::Offset(90), 0, 100), 10);
+        assert_eq!(partition_lag(Offset::Offset(10), 50, 100), 50);
+        assert_eq!(partition_lag(Offset::Invalid, 50, 100), 50);
+    }
+
+    #[test]
+    fn merge() {
+        let mut offsets = BTreeMap::from([(("action".to_owned(), 0), 10), (("action".to_owned(), 1), 20)]);
+        merge_offsets(&mut offsets, BTreeMap::from([(("action".to_owned(), 0), 5), (("action".to_owned(), 1), 25)]));
+        merge_offsets(&mut offsets, BTreeMap::from([(("event".to_owned(), 0), 1)]));
+        assert_eq!(
+            offsets,
+            BTreeMap::from([
+                (("action".to_owned(), 0), 10),
+                (("action".to_owned(), 1), 25),
+                (("event".to_owned(), 0), 1)
+            ])
+        );
+    }
+
+    #[test]
+    fn seeks() {
+        let seeks = BTreeMap::from([
+            (("action".to_owned(), None), SeekPosition::Timestamp(1000)),
+            (("action".to_owned(), Some(1)), SeekPosition::Offset(5)),
+        ]);
+        let assignment = BTreeSet::from([
+            ("action".to_owned(), 0),
+            ("action".to_owned(), 1),
+            ("action".to_owned(), 2),
+            ("event".to_owned(), 0),
+        ]);
+        let seeked = BTreeSet::from([("action".to_owned(), 2)]);
+        assert_eq!(
+            pending_seeks(&seeks, &assignment, &seeked),
+            BTreeMap::from([
+                (("action".to_owned(), 0), SeekPosition::Timestamp(1000)),
+                (("action".to_owned(), 1), SeekPosition::Offset(5))
+            ])
+        );
+        assert!(pending_seeks(&BTreeMap::new(), &assignment, &seeked).is_empty());
+    }
+
+    #[tokio::test]
+    async fn rewind_failed_batch_then_commit() {
+        let partition = ("action".to_owned(), 0);
+        let shutdown_signal = CancellationToken::new();
+
+        // batch 10..20 failed without ack, while late ack of previous batch is pending
+        let failed_task = HandlerTask {
+            topic: "action".to_owned(),
+            offsets: BTreeMap::new(),
+            rewind: Some(BTreeMap::from([(partition.clone(), 10)])),
+            handle: tokio::spawn(async { Err(exception!("failed")) }),
+        };
+        let (mut offsets, failed) = join_handlers(vec![failed_task], &shutdown_signal, Duration::ZERO).await;
+        merge_offsets(&mut offsets, BTreeMap::from([(partition.clone(), 10)]));
+        let rewinds = rewind_failed(&mut offsets, failed);
+        assert_eq!(offsets, BTreeMap::from([(partition.clone(), 10)]));
+        assert_eq!(rewinds, BTreeMap::from([(partition.clone(), SeekPosition::Offset(10))]));
+
+        // consumed again from 10 and acked
+        let succeeded_task = HandlerTask {
+            topic: "action".to_owned(),
+            offsets: BTreeMap::new(),
+            rewind: Some(BTreeMap::from([(partition.clone(), 10)])),
+            handle: tokio::spawn(async { Ok(()) }),
+        };
+        let (mut committed, not_failed) = join_handlers(vec![succeeded_task], &shutdown_signal, Duration::ZERO).await;
+        merge_offsets(&mut committed, BTreeMap::from([(partition.clone(), 20)]));
+        assert!(rewind_failed(&mut committed, not_failed).is_empty());
+        assert_eq!(committed, BTreeMap::from([(partition, 20)]));
+    }
+
+    #[test]
+    fn rewind_failed_caps_acked_offsets() {
+        let mut offsets = BTreeMap::from([(("action".to_owned(), 0), 20), (("action".to_owned(), 1), 5)]);
+        let rewinds = rewind_failed(&mut offsets, BTreeMap::from([(("action".to_owned(), 0), 10)]));
+        assert_eq!(offsets, BTreeMap::from([(("action".to_owned(), 0), 10), (("action".to_owned(), 1), 5)]));
+        assert_eq!(rewinds, BTreeMap::from([(("action".to_owned(), 0), SeekPosition::Offset(10))]));
+    }
+
+    #[tokio::test]
+    async fn skip_failed_batch_without_rewind() {
+        let task = HandlerTask {
+            topic: "event".to_owned(),
+            offsets: BTreeMap::from([(("event".to_owned(), 0), 20)]),
+            rewind: None,
+            handle: tokio::spawn(async { Err(exception!("failed")) }),
+        };
+        let (offsets, failed) = join_handlers(vec![task], &CancellationToken::new(), Duration::ZERO).await;
+        assert_eq!(offsets, BTreeMap::from([(("event".to_owned(), 0), 20)]));
+        assert!(failed.is_empty());
+    }
+}
@@ -0,0 +1,78 @@
+use framework::exception;
+use framework::exception::Exception;
+use framework::log;
+use framework::stats;
+use rdkafka::ClientConfig;
+use rdkafka::message::Header;
+use rdkafka::message::Headers as _;
+use rdkafka::message::OwnedHeaders;
+use rdkafka::producer::FutureProducer;
+use rdkafka::producer::FutureRecord;
+use rdkafka::util::Timeout;
+
+use crate::consumer::RawMessage;
+use crate::security::SecurityConfig;
+
+const ERROR_CODE: &str = "dlq_error_code";
+const ERROR_MESSAGE: &str = "dlq_error_message";
+const PARTITION: &str = "dlq_partition";
+const OFFSET: &str = "dlq_offset";
+
+// failed messages are published to "{topic}.dlq" as is, with original headers and error metadata
+pub(crate) struct DeadLetterProducer {
+    producer: FutureProducer,
+}
+
+impl DeadLetterProducer {
+    pub(crate) fn new(bootstrap_servers: &str, security: &SecurityConfig) -> Self {
+        let mut config = ClientConfig::new();
+        config
+            .set("bootstrap.servers", bootstrap_servers)
+            .set("message.timeout.ms", "5000")
+            .set("compression.codec", "zstd");
+        security.apply(&mut config);
+        let producer: FutureProducer = config.create().expect("failed to create dead letter producer");
+        Self { producer }
+    }
+
+    // fails if not delivered, then message must not be committed
+    pub(crate) async fn send(&self, message: &RawMessage, error: &Exception) -> Result<(), Exception> {
+        let topic = format!("{}.dlq", message.topic);
+
+        let mut headers = OwnedHeaders::new();
+        if let Some(original_headers) = message.headers.as_ref() {
+            for header in original_headers.iter() {
+                headers = headers.insert(header);
+            }
+        }
+        let partition = message.partition.to_string();
+        let offset = message.offset.to_string();
+        headers = headers
+            .insert(Header { key: ERROR_CODE, value: error.code })
+            .insert(Header { key: ERROR_MESSAGE, value: Some(&error.message) })
+            .insert(Header { key: PARTITION, value: Some(&partition) })
+            .insert(Header { key: OFFSET, value: Some(&offset) });
+
+        let mut record = FutureRecord::<[u8], [u8]>::to(&topic).headers(headers);
+        if let Some(key) = message.key.as_deref() {
+            record = record.key(key);
+        }
+        if let Some(payload) = message.payload.as_deref() {
+            record = record.payload(payload);
+        }
+        if let Some(timestamp) = message.timestamp.to_millis() {
+            record = record.timestamp(timestamp);
+        }
+
+        stats!(kafka_dead_letter_messages = 1);
+        log!("send to dead letter topic, topic={topic}, partition={partition}, offset={offset}");
+        self.producer.send(record, Timeout::Never).await.map_err(|(err, _)| {
+            exception!(
+                format!("failed to send to dead letter topic, topic={topic}"),
+                code = "KAFKA_DEAD_LETTER_FAILED",
+                source = err
+            )
+        })?;
+        Ok(())
+    }
+}
@@ -1,19 +1,39 @@
 use std::marker::PhantomData;
 
+use crate::codec::JsonCodec;
+
+pub mod admin;
+pub mod appender;
+pub mod codec;
 pub mod consumer;
+mod dead_letter;
 pub mod producer;
+mod rebalance;
+pub mod security;
 
-pub struct Topic<T> {
+pub struct Topic<T, C = JsonCodec> {
     pub name: &'static str,
+    codec: C,
     _marker: PhantomData<T>,
 }
 
 impl<T> Topic<T> {
+    // message is encoded as json
     pub const fn new(name: &'static str) -> Self {
-        Self { name, _marker: PhantomData }
+        Self { name, codec: JsonCodec, _marker: PhantomData }
+    }
+}
+
+impl<T, C> Topic<T, C> {
+    // e.g. Topic::with_codec("event", ProtobufCodec), Topic::with_codec("event", AvroCodec::new(registry))
+    pub const fn with_codec(name: &'static str, codec: C) -> Self {
+        Self { name, codec, _marker: PhantomData }
     }
 }
 
 type Header = &'static str;
 const REF_ID: Header = "ref_id";
+const CORRELATION_ID: Header = "correlation_id";
 const CLIENT: Header = "client";
+// w3c trace context, same as http header
+const TRACEPARENT: Header = "traceparent";
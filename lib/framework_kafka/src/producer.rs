@@ -1,56 +1,323 @@
-use std::fmt::Debug;
+use std::sync::Arc;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
 
 use chrono::Utc;
 use framework::console;
+use framework::exception;
 use framework::exception::Exception;
-use framework::json::to_json;
 use framework::log;
 use framework::log::current_action_id;
+use framework::log::current_correlation_id;
+use framework::log::current_traceparent;
+use framework::log::metrics::Metrics;
 use framework::span;
 use framework::stats;
+use futures::future::join_all;
 use rdkafka::ClientConfig;
 use rdkafka::message::Header;
 use rdkafka::message::OwnedHeaders;
 use rdkafka::producer::FutureProducer;
 use rdkafka::producer::FutureRecord;
+use rdkafka::producer::Producer as _;
 use rdkafka::util::Timeout;
-use serde::Serialize;
+use tokio::task;
 
 use crate::CLIENT;
+use crate::CORRELATION_ID;
 use crate::REF_ID;
+use crate::TRACEPARENT;
 use crate::Topic;
+use crate::codec::Encoder;
+use crate::consumer::Ack;
+use crate::security::SecurityConfig;
+
+// for blocking transaction operations, commit waits for pending messages to be delivered
+const TRANSACTION_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Copy)]
+pub enum Acks {
+    // fire and forget, highest throughput, messages may be lost
+    None,
+    // leader acknowledged, lost if leader fails before replication
+    Leader,
+    // all in-sync replicas acknowledged
+    All,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Compression {
+    None,
+    Gzip,
+    Snappy,
+    Lz4,
+    Zstd,
+}
+
+pub struct ProducerConfig {
+    pub acks: Acks,
+    // max time to deliver message including retries, send() fails after timeout
+    pub message_timeout: Duration,
+    // wait time to accumulate messages into batch, higher value improves throughput with more latency
+    pub linger: Duration,
+    // max bytes per batch per partition
+    pub batch_size: usize,
+    pub compression: Compression,
+    // max bytes per request, must not exceed broker's message.max.bytes
+    pub max_request_size: usize,
+    // no duplicates on retry, and ordering is preserved per partition, requires acks = All
+    pub idempotence: bool,
+    // enables begin_transaction(), must be unique per producer instance and stable across restarts, e.g. "{app}-{pod}",
+    // idempotence is enabled by transaction as well
+    pub transactional_id: Option<String>,
+    pub security: SecurityConfig,
+}
+
+impl Default for ProducerConfig {
+    fn default() -> Self {
+        Self {
+            acks: Acks::All,
+            message_timeout: Duration::from_secs(5),
+            linger: Duration::from_millis(5),
+            batch_size: 1_000_000,
+            compression: Compression::Zstd,
+            max_request_size: 1_000_000,
+            idempotence: false,
+            transactional_id: None,
+            security: SecurityConfig::default(),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct SendOptions {
+    pub key: Option<String>,
+    // sent along with framework headers client and ref_id, e.g. ("schema_version", "2")
+    pub headers: Vec<(&'static str, String)>,
+}
+
+// called with topic and error when message sent by send_async() failed to deliver
+pub type DeliveryFailureCallback = Arc<dyn Fn(&str, &Exception) + Send + Sync>;
 
 pub struct Producer {
-    producer: FutureProducer,
+    inner: FutureProducer,
     client: &'static str,
+    delivery_failures: Arc<AtomicU64>,
+    on_delivery_failure: Option<DeliveryFailureCallback>,
 }
 
 impl Producer {
     // client usually be env!("CARGO_BIN_NAME")
-    pub fn new(bootstrap_servers: String, client: &'static str) -> Self {
+    pub fn new(bootstrap_servers: String, client: &'static str, config: &ProducerConfig) -> Self {
         console!("create kafka producer, broker={bootstrap_servers}");
-        let producer: FutureProducer = ClientConfig::new()
+        let acks = match config.acks {
+            Acks::None => "0",
+            Acks::Leader => "1",
+            Acks::All => "all",
+        };
+        let compression = match config.compression {
+            Compression::None => "none",
+            Compression::Gzip => "gzip",
+            Compression::Snappy => "snappy",
+            Compression::Lz4 => "lz4",
+            Compression::Zstd => "zstd",
+        };
+        let mut client_config = ClientConfig::new();
+        client_config
             .set("bootstrap.servers", bootstrap_servers)
-            .set("message.timeout.ms", "5000")
-            .set("compression.codec", "zstd")
-            .create()
-            .expect("failed to create producer");
-        Self { producer, client }
+            .set("acks", acks)
+            .set("message.timeout.ms", config.message_timeout.as_millis().to_string())
+            .set("linger.ms", config.linger.as_millis().to_string())
+            .set("batch.size", config.batch_size.to_string())
+            .set("compression.codec", compression)
+            .set("message.max.bytes", config.max_request_size.to_string());
+        if config.idempotence {
+            client_config.set("enable.idempotence", "true");
+        }
+        if let Some(ref transactional_id) = config.transactional_id {
+            client_config.set("transactional.id", transactional_id);
+        }
+        config.security.apply(&mut client_config);
+        let producer: FutureProducer = client_config.create().expect("failed to create producer");
+        if config.transactional_id.is_some() {
+            // fences previous producer with same transactional id, fail fast on startup
+            producer.init_transactions(TRANSACTION_TIMEOUT).expect("failed to init transactions");
+        }
+        Self { inner: producer, client, delivery_failures: Arc::new(AtomicU64::new(0)), on_delivery_failure: None }
     }
 
-    pub async fn send<T>(&self, topic: &Topic<T>, key: Option<String>, message: &T) -> Result<(), Exception>
+    pub async fn send<T, C>(&self, topic: &Topic<T, C>, key: Option<String>, message: &T) -> Result<(), Exception>
     where
-        T: Serialize + Debug,
+        C: Encoder<T>,
     {
+        self.send_with_options(topic, message, SendOptions { key, ..SendOptions::default() }).await
+    }
+
+    pub async fn send_with_options<T, C>(
+        &self,
+        topic: &Topic<T, C>,
+        message: &T,
+        options: SendOptions,
+    ) -> Result<(), Exception>
+    where
+        C: Encoder<T>,
+    {
+        let SendOptions { key, headers } = options;
         let _span = span!("kafka");
-        let payload = to_json(message)?;
+        let payload = topic.codec.encode(topic.name, message).await?;
 
         stats!(kafka_write_messages = 1, kafka_write_bytes = payload.len());
 
+        log!(
+            "send, topic={}, key={key:?}, headers={headers:?}, payload={}",
+            topic.name,
+            topic.codec.display(message, &payload)
+        );
+        let result = self.inner.send(self.record(topic, key.as_ref(), &headers, &payload), Timeout::Never).await;
+        if let Err((err, _)) = result {
+            return Err(err.into());
+        }
+        Ok(())
+    }
+
+    // enqueues message without waiting for delivery, e.g. for hot path where latency matters more than confirmation,
+    // delivery failure is logged and reported by on_delivery_failure() callback and producer_metrics()
+    pub async fn send_async<T, C>(
+        &self,
+        topic: &Topic<T, C>,
+        message: &T,
+        options: SendOptions,
+    ) -> Result<(), Exception>
+    where
+        C: Encoder<T>,
+    {
+        let SendOptions { key, headers } = options;
+        let payload = topic.codec.encode(topic.name, message).await?;
+
+        stats!(kafka_write_messages = 1, kafka_write_bytes = payload.len());
+
+        log!(
+            "send async, topic={}, key={key:?}, headers={headers:?}, payload={}",
+            topic.name,
+            topic.codec.display(message, &payload)
+        );
+        let delivery = match self.inner.send_result(self.record(topic, key.as_ref(), &headers, &payload)) {
+            Ok(delivery) => delivery,
+            Err((err, _)) => return Err(err.into()),
+        };
+
+        let topic_name = topic.name;
+        let failures = Arc::clone(&self.delivery_failures);
+        let callback = self.on_delivery_failure.clone();
+        tokio::spawn(async move {
+            let error = match delivery.await {
+                Ok(Ok(_)) => return,
+                Ok(Err((err, _))) => Exception::from(err),
+                Err(_) => exception!("message delivery canceled, producer is closed"),
+            };
+            failures.fetch_add(1, Ordering::Relaxed);
+            console!("WARN failed to deliver kafka message, topic={topic_name}, error={error}");
+            if let Some(callback) = callback {
+                callback(topic_name, &error);
+            }
+        });
+        Ok(())
+    }
+
+    pub fn on_delivery_failure<F>(&mut self, callback: F)
+    where
+        F: Fn(&str, &Exception) + Send + Sync + 'static,
+    {
+        self.on_delivery_failure = Some(Arc::new(callback));
+    }
+
+    // reports failed deliveries of send_async() since last collection
+    pub fn producer_metrics(&self) -> impl Fn(&mut Metrics) + use<> {
+        let failures = Arc::clone(&self.delivery_failures);
+        move |metrics| {
+            metrics.stats.push(("kafka_delivery_failures", failures.swap(0, Ordering::Relaxed)));
+        }
+    }
+
+    // only one transaction at a time per producer, messages sent within transaction are visible to
+    // consumers with isolation.level=read_committed (default) after commit
+    pub fn begin_transaction(&self) -> Result<(), Exception> {
+        Ok(self.inner.begin_transaction()?)
+    }
+
+    // flushes pending messages, call abort_transaction() if failed
+    pub async fn commit_transaction(&self) -> Result<(), Exception> {
+        let _span = span!("kafka");
+        let producer = self.inner.clone();
+        Ok(task::spawn_blocking(move || producer.commit_transaction(TRANSACTION_TIMEOUT)).await??)
+    }
+
+    // commits consumed offsets of ack along with produced messages atomically, for exactly once consume-transform-produce,
+    // ack must not be committed by ack.commit() again
+    pub async fn commit_transaction_with_ack(&self, ack: Ack) -> Result<(), Exception> {
+        let _span = span!("kafka");
+        let (offsets, group_metadata) = ack.transaction_offsets()?;
+        let producer = self.inner.clone();
+        Ok(task::spawn_blocking(move || {
+            producer.send_offsets_to_transaction(&offsets, &group_metadata, TRANSACTION_TIMEOUT)?;
+            producer.commit_transaction(TRANSACTION_TIMEOUT)
+        })
+        .await??)
+    }
+
+    pub async fn abort_transaction(&self) -> Result<(), Exception> {
+        let _span = span!("kafka");
+        let producer = self.inner.clone();
+        Ok(task::spawn_blocking(move || producer.abort_transaction(TRANSACTION_TIMEOUT)).await??)
+    }
+
+    // enqueue all messages before awaiting delivery, fails if any message failed to deliver
+    pub async fn send_batch<T, C>(
+        &self,
+        topic: &Topic<T, C>,
+        messages: Vec<(Option<String>, &T)>,
+    ) -> Result<(), Exception>
+    where
+        C: Encoder<T>,
+    {
+        let _span = span!("kafka");
+        let mut records = Vec::with_capacity(messages.len());
+        let mut bytes = 0;
+        for (key, message) in messages {
+            let payload = topic.codec.encode(topic.name, message).await?;
+            bytes += payload.len();
+            log!("send, topic={}, key={key:?}, payload={}", topic.name, topic.codec.display(message, &payload));
+            records.push((key, payload));
+        }
+
+        stats!(kafka_write_messages = records.len(), kafka_write_bytes = bytes);
+
+        let results = join_all(
+            records
+                .iter()
+                .map(|(key, payload)| self.inner.send(self.record(topic, key.as_ref(), &[], payload), Timeout::Never)),
+        )
+        .await;
+        for result in results {
+            if let Err((err, _)) = result {
+                return Err(err.into());
+            }
+        }
+        Ok(())
+    }
+
+    fn record<'a, T, C>(
+        &self,
+        topic: &Topic<T, C>,
+        key: Option<&'a String>,
+        custom_headers: &[(&'static str, String)],
+        payload: &'a [u8],
+    ) -> FutureRecord<'a, String, [u8]> {
         let mut record =
-            FutureRecord::<String, String>::to(topic.name).timestamp(Utc::now().timestamp_millis()).payload(&payload);
+            FutureRecord::<String, [u8]>::to(topic.name).timestamp(Utc::now().timestamp_millis()).payload(payload);
 
-        if let Some(ref key) = key {
+        if let Some(key) = key {
             record = record.key(key);
         }
 
@@ -58,13 +325,15 @@ impl Producer {
         if let Some(ref_id) = current_action_id() {
             headers = headers.insert(Header { key: REF_ID, value: Some(&ref_id) });
         }
-        record = record.headers(headers);
-
-        log!("send, topic={}, key={key:?}, payload={payload}", topic.name);
-        let result = self.producer.send(record, Timeout::Never).await;
-        if let Err((err, _)) = result {
-            return Err(err.into());
+        if let Some(correlation_id) = current_correlation_id() {
+            headers = headers.insert(Header { key: CORRELATION_ID, value: Some(&correlation_id) });
         }
-        Ok(())
+        if let Some(traceparent) = current_traceparent() {
+            headers = headers.insert(Header { key: TRACEPARENT, value: Some(&traceparent) });
+        }
+        for (name, value) in custom_headers {
+            headers = headers.insert(Header { key: name, value: Some(value) });
+        }
+        record.headers(headers)
     }
 }
@@ -0,0 +1,137 @@
+use std::collections::BTreeSet;
+use std::mem;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use framework::console;
+use rdkafka::ClientContext;
+use rdkafka::TopicPartitionList;
+use rdkafka::consumer::ConsumerContext;
+use rdkafka::consumer::Rebalance;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RebalanceEvent {
+    // (topic, partition)
+    Assigned(Vec<(String, i32)>),
+    Revoked(Vec<(String, i32)>),
+}
+
+pub(crate) type RebalanceCallback = Box<dyn Fn(&RebalanceEvent) + Send + Sync>;
+
+// callbacks are called within poll, revoked before partitions are revoked, assigned after partitions are assigned
+pub(crate) struct RebalanceContext {
+    callbacks: Vec<RebalanceCallback>,
+    assignment: Arc<Mutex<BTreeSet<(String, i32)>>>,
+    // logged as action by consumer loop after poll
+    events: Mutex<Vec<RebalanceEvent>>,
+    // revoked during current poll, consumer loop drops polled messages of these partitions
+    revoked: Mutex<BTreeSet<(String, i32)>>,
+}
+
+impl RebalanceContext {
+    pub(crate) fn new(callbacks: Vec<RebalanceCallback>, assignment: Arc<Mutex<BTreeSet<(String, i32)>>>) -> Self {
+        Self { callbacks, assignment, events: Mutex::new(vec![]), revoked: Mutex::new(BTreeSet::new()) }
+    }
+
+    pub(crate) fn take_events(&self) -> Vec<RebalanceEvent> {
+        mem::take(&mut self.events.lock().unwrap())
+    }
+
+    pub(crate) fn take_revoked(&self) -> BTreeSet<(String, i32)> {
+        mem::take(&mut self.revoked.lock().unwrap())
+    }
+
+    pub(crate) fn has_revoked(&self) -> bool {
+        !self.revoked.lock().unwrap().is_empty()
+    }
+
+    fn notify(&self, event: RebalanceEvent) {
+        for callback in &self.callbacks {
+            callback(&event);
+        }
+        self.events.lock().unwrap().push(event);
+    }
+}
+
+impl ClientContext for RebalanceContext {}
+
+impl ConsumerContext for RebalanceContext {
+    fn pre_rebalance(&self, rebalance: &Rebalance<'_>) {
+        if let Rebalance::Revoke(list) = rebalance {
+            let partitions = partitions(list);
+            {
+                let mut assignment = self.assignment.lock().unwrap();
+                for partition in &partitions {
+                    assignment.remove(partition);
+                }
+            }
+            self.revoked.lock().unwrap().extend(partitions.iter().cloned());
+            self.notify(RebalanceEvent::Revoked(partitions));
+        }
+    }
+
+    fn post_rebalance(&self, rebalance: &Rebalance<'_>) {
+        match rebalance {
+            Rebalance::Assign(list) => {
+                let partitions = partitions(list);
+                self.assignment.lock().unwrap().extend(partitions.iter().cloned());
+                self.notify(RebalanceEvent::Assigned(partitions));
+            }
+            Rebalance::Revoke(_) => {}
+            Rebalance::Error(err) => console!("WARN kafka consumer rebalance failed, error={err}"),
+        }
+    }
+}
+
+fn partitions(list: &TopicPartitionList) -> Vec<(String, i32)> {
+    list.elements().iter().map(|element| (element.topic().to_owned(), element.partition())).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+    use std::sync::Arc;
+    use std::sync::Mutex;
+
+    use rdkafka::TopicPartitionList;
+    use rdkafka::consumer::ConsumerContext as _;
+    use rdkafka::consumer::Rebalance;
+
+    use super::RebalanceContext;
+    use super::RebalanceEvent;
+
+    #[test]
+    fn rebalance() {
+        let notified = Arc::new(Mutex::new(vec![]));
+        let callback_notified = Arc::clone(&notified);
+        let assignment = Arc::new(Mutex::new(BTreeSet::new()));
+        let context = RebalanceContext::new(
+            vec![Box::new(move |event: &RebalanceEvent| callback_notified.lock().unwrap().push(event.clone()))],
+            Arc::clone(&assignment),
+        );
+
+        let mut list = TopicPartitionList::new();
+        list.add_partition("topic", 0);
+        list.add_partition("topic", 1);
+        context.post_rebalance(&Rebalance::Assign(&list));
+        assert_eq!(assignment.lock().unwrap().len(), 2);
+        assert!(!context.has_revoked());
+
+        let mut revoked = TopicPartitionList::new();
+        revoked.add_partition("topic", 1);
+        context.pre_rebalance(&Rebalance::Revoke(&revoked));
+        context.post_rebalance(&Rebalance::Revoke(&revoked));
+        assert_eq!(*assignment.lock().unwrap(), BTreeSet::from([("topic".to_owned(), 0)]));
+        assert!(context.has_revoked());
+        assert_eq!(context.take_revoked(), BTreeSet::from([("topic".to_owned(), 1)]));
+        assert!(!context.has_revoked());
+
+        let events = vec![
+            RebalanceEvent::Assigned(vec![("topic".to_owned(), 0), ("topic".to_owned(), 1)]),
+            RebalanceEvent::Revoked(vec![("topic".to_owned(), 1)]),
+        ];
+        assert_eq!(*notified.lock().unwrap(), events);
+        assert_eq!(context.take_events(), events);
+        assert!(context.take_events().is_empty());
+    }
+}
@@ -0,0 +1,68 @@
+use rdkafka::ClientConfig;
+
+// e.g. MSK with iam is not supported, use SCRAM instead
+#[derive(Debug, Clone, Default)]
+pub struct SecurityConfig {
+    pub protocol: SecurityProtocol,
+    pub sasl: Option<SaslConfig>,
+    // CA certificate to verify broker, use system CA store if not set
+    pub ssl_ca_location: Option<String>,
+    // client certificate and key for mTLS
+    pub ssl_certificate_location: Option<String>,
+    pub ssl_key_location: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub enum SecurityProtocol {
+    #[default]
+    Plaintext,
+    Ssl,
+    SaslPlaintext,
+    SaslSsl,
+}
+
+#[derive(Debug, Clone)]
+pub struct SaslConfig {
+    pub mechanism: SaslMechanism,
+    pub username: String,
+    pub password: String,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum SaslMechanism {
+    Plain,
+    ScramSha256,
+    ScramSha512,
+}
+
+impl SecurityConfig {
+    pub(crate) fn apply(&self, config: &mut ClientConfig) {
+        let protocol = match self.protocol {
+            SecurityProtocol::Plaintext => "plaintext",
+            SecurityProtocol::Ssl => "ssl",
+            SecurityProtocol::SaslPlaintext => "sasl_plaintext",
+            SecurityProtocol::SaslSsl => "sasl_ssl",
+        };
+        config.set("security.protocol", protocol);
+        if let Some(ref sasl) = self.sasl {
+            let mechanism = match sasl.mechanism {
+                SaslMechanism::Plain => "PLAIN",
+                SaslMechanism::ScramSha256 => "SCRAM-SHA-256",
+                SaslMechanism::ScramSha512 => "SCRAM-SHA-512",
+            };
+            config
+                .set("sasl.mechanism", mechanism)
+                .set("sasl.username", &sasl.username)
+                .set("sasl.password", &sasl.password);
+        }
+        if let Some(ref location) = self.ssl_ca_location {
+            config.set("ssl.ca.location", location);
+        }
+        if let Some(ref location) = self.ssl_certificate_location {
+            config.set("ssl.certificate.location", location);
+        }
+        if let Some(ref location) = self.ssl_key_location {
+            config.set("ssl.key.location", location);
+        }
+    }
+}
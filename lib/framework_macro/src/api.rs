@@ -33,7 +33,11 @@ pub(crate) fn build(tokens: TokenStream) -> Result<TokenStream> {
         let model = parse_method(method)?;
         method.attrs.retain(|attr| {
             let path = attr.path();
-            !path.is_ident("get") && !path.is_ident("post") && !path.is_ident("put") && !path.is_ident("path")
+            !path.is_ident("get")
+                && !path.is_ident("post")
+                && !path.is_ident("put")
+                && !path.is_ident("path")
+                && !path.is_ident("validate")
         });
         method.sig.asyncness = None;
         let response_type = &model.response_type;
@@ -57,7 +61,7 @@ pub(crate) fn build(tokens: TokenStream) -> Result<TokenStream> {
             use framework::web::api::ApiClient;
             use framework::web::api::__into_response;
             use framework::web::body::Json;
-            use framework::web::body::Query;
+            use framework::web::extract::Query;
 
             use super::*;
 
@@ -103,6 +107,7 @@ fn parse_method(method: &TraitItemFn) -> Result<MethodModel> {
 
     let mut http_method = None;
     let mut path = None;
+    let mut validate = false;
 
     for attr in &method.attrs {
         let attr_path = attr.path();
@@ -114,12 +119,22 @@ fn parse_method(method: &TraitItemFn) -> Result<MethodModel> {
             http_method = Some((quote!(MethodFilter::PUT), quote!(Json), format_ident!("put")));
         } else if attr_path.is_ident("path") {
             path = Some(attr.parse_args::<LitStr>()?);
+        } else if attr_path.is_ident("validate") {
+            validate = true;
         }
     }
 
-    let (filter, extractor, client_call) = http_method.ok_or_else(|| {
+    let (filter, mut extractor, client_call) = http_method.ok_or_else(|| {
         Error::new_spanned(method, "missing HTTP method attribute, expected #[get], #[post] or #[put]")
     })?;
+    // #[validate] calls request.validate() before invoking service method
+    if validate {
+        extractor = if client_call == "get" {
+            quote!(framework::web::extract::ValidatedQuery)
+        } else {
+            quote!(framework::web::body::ValidatedJson)
+        };
+    }
     let path = path.ok_or_else(|| Error::new_spanned(method, "missing #[path(\"...\")] attribute"))?;
 
     let mut inputs = method.sig.inputs.iter();
@@ -251,7 +266,7 @@ mod tests {
                     use framework::web::api::ApiClient;
                     use framework::web::api::__into_response;
                     use framework::web::body::Json;
-                    use framework::web::body::Query;
+                    use framework::web::extract::Query;
 
                     use super::*;
 
@@ -313,6 +328,33 @@ mod tests {
         );
     }
 
+    #[test]
+    fn build_api_with_validate() {
+        let source = quote! {
+            #[api]
+            pub trait UserService {
+                #[post]
+                #[validate]
+                #[path("/user/create")]
+                async fn create(&self, request: CreateUserRequest) -> Result<(), Exception>;
+            }
+        };
+
+        let output = build(source).unwrap().to_string();
+
+        assert!(output.contains(
+            &quote! {
+                on(MethodFilter::POST, async move |framework::web::body::ValidatedJson(req): framework::web::body::ValidatedJson<CreateUserRequest>| {
+                    context!(fn = format!("{}::create", std::any::type_name::<T>()));
+                    let result = svc.create(req).await;
+                    __into_response(result)
+                })
+            }
+            .to_string()
+        ));
+        assert!(!output.contains("# [validate]"));
+    }
+
     #[test]
     fn build_api_with_optional() {
         let source = quote! {
@@ -350,7 +392,7 @@ mod tests {
                     use framework::web::api::ApiClient;
                     use framework::web::api::__into_response;
                     use framework::web::body::Json;
-                    use framework::web::body::Query;
+                    use framework::web::extract::Query;
 
                     use super::*;
 
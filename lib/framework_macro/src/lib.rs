@@ -46,6 +46,7 @@ pub fn enum8(stream: proc_macro::TokenStream) -> proc_macro::TokenStream {
 /// `#[api]` derives an axum route builder and an HTTP client from a trait.
 /// Each method must be `async fn`, annotated with one of `#[get]`, `#[post]`, `#[put]` plus `#[path("/...")]`,
 /// take `&self` and a single request parameter, and return `Result<..., Exception>`.
+/// `#[validate]` on a method validates the request with `Validator` before the service is called.
 /// Generates a sibling module (snake_case of the trait name) exposing `route(service)` and `client(http_client, api_url)`.
 #[proc_macro_attribute]
 pub fn api(_attr: proc_macro::TokenStream, item: proc_macro::TokenStream) -> proc_macro::TokenStream {